@@ -0,0 +1,89 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use crate::{model::FailedAttempts, HackClientError};
+
+/// Count the failed attempts to connect with a majority token, per IP address or subnet, to slow down enumeration of tokens.
+/// Implemented by [`MemoryAttemptStore`] for a single server and by [`HackClient`](crate::HackClient) to share the counters
+/// between several servers through CouchDB.
+#[async_trait]
+pub trait AttemptStore: Send + Sync {
+    async fn get_failed_attempts(
+        &self,
+        key: &str,
+    ) -> Result<Option<FailedAttempts>, HackClientError>;
+
+    /// Count a new failure for this key, forgetting the previous ones if the latest is older than `forget_after` seconds.
+    /// Return the updated counter.
+    async fn record_failed_attempt(
+        &self,
+        key: &str,
+        now: u64,
+        forget_after: u64,
+    ) -> Result<FailedAttempts, HackClientError>;
+
+    async fn clear_failed_attempts(&self, key: &str) -> Result<(), HackClientError>;
+}
+
+/// Keep the counters in memory. They are lost on restart.
+#[derive(Default)]
+pub struct MemoryAttemptStore {
+    attempts: Mutex<HashMap<String, FailedAttempts>>,
+}
+
+impl MemoryAttemptStore {
+    /// Above this number of counters, the stale ones are removed when a failure is recorded
+    const PRUNE_THRESHOLD: usize = 10_000;
+}
+
+#[async_trait]
+impl AttemptStore for MemoryAttemptStore {
+    async fn get_failed_attempts(
+        &self,
+        key: &str,
+    ) -> Result<Option<FailedAttempts>, HackClientError> {
+        Ok(self.attempts.lock().unwrap().get(key).cloned())
+    }
+
+    async fn record_failed_attempt(
+        &self,
+        key: &str,
+        now: u64,
+        forget_after: u64,
+    ) -> Result<FailedAttempts, HackClientError> {
+        let mut attempts = self.attempts.lock().unwrap();
+        if attempts.len() >= Self::PRUNE_THRESHOLD {
+            attempts.retain(|_, failed| now.saturating_sub(failed.latest_failure) < forget_after);
+        }
+        let failed = attempts
+            .entry(key.to_string())
+            .or_insert_with(|| FailedAttempts::new(key));
+        failed.record_failure(now, forget_after);
+        Ok(failed.clone())
+    }
+
+    async fn clear_failed_attempts(&self, key: &str) -> Result<(), HackClientError> {
+        self.attempts.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AttemptStore, MemoryAttemptStore};
+
+    #[tokio::test]
+    async fn test_memory_attempt_store() {
+        let store = MemoryAttemptStore::default();
+        assert_eq!(store.get_failed_attempts("ip:a").await.unwrap(), None);
+        store.record_failed_attempt("ip:a", 100, 50).await.unwrap();
+        let failed = store.record_failed_attempt("ip:a", 120, 50).await.unwrap();
+        assert_eq!((failed.failures, failed.latest_failure), (2, 120));
+        // the old failures are forgotten
+        let failed = store.record_failed_attempt("ip:a", 200, 50).await.unwrap();
+        assert_eq!(failed.failures, 1);
+        store.clear_failed_attempts("ip:a").await.unwrap();
+        assert_eq!(store.get_failed_attempts("ip:a").await.unwrap(), None);
+    }
+}
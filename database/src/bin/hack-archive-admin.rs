@@ -0,0 +1,291 @@
+//! Administration of the majority token database in CouchDB: creating it, minting the first tokens,
+//! inspecting and revoking tokens, backups and conflict resolution.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand};
+use database::{
+    get_timestamp,
+    model::{MajorityToken, MajorityTokenAdminFlags},
+    FieldWithTime, HackClient, HackClientError, MajorityTokenStore, Mergeable, TokenHasher,
+};
+use display_error_chain::DisplayErrorChain;
+use rand::{distributions::Alphanumeric, Rng};
+
+/// Manage the majority tokens stored in CouchDB. The connection settings use the same environment variables as the server.
+#[derive(Parser, Debug)]
+#[clap(name = "hack-archive-admin")]
+pub struct Opts {
+    #[clap(long, env = "HACK_ARCHIVE_COUCHDB_URI")]
+    uri: String,
+    #[clap(long, env = "HACK_ARCHIVE_COUCHDB_USERNAME")]
+    username: String,
+    /// File containing the password of CouchDB
+    #[clap(long, env = "HACK_ARCHIVE_COUCHDB_PASSWORD_FILE")]
+    password_file: PathBuf,
+    /// File containing the key used to hash the secret of the tokens. Must be the one of the server.
+    #[clap(long, env = "HACK_ARCHIVE_TOKEN_STORE_HASH_KEY_FILE")]
+    hash_key_file: PathBuf,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Create the databases and their indexes. Can be run again on an existing installation.
+    Init,
+    /// Create a token not certified by any other token, and print its secret
+    CreateRootToken {
+        /// Also give access to the administration pages
+        #[clap(long)]
+        admin: bool,
+        /// Number of days the token is valid for. It never expire if unset.
+        #[clap(long)]
+        days: Option<u64>,
+    },
+    /// Print a token, the tokens that certified it, its usage and its conflict history
+    Inspect {
+        /// The id or the secret of the token
+        token: String,
+    },
+    /// Revoke a token and every token it certified, recursively. Can be run again if interrupted.
+    Revoke {
+        /// The id or the secret of the token
+        token: String,
+        #[clap(long)]
+        reason: String,
+    },
+    /// Write every token as JSON, one per line
+    Export {
+        /// Write to this file instead of the standard output
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Add the tokens of an export. Tokens that already exist are merged with the imported version.
+    Import { input: PathBuf },
+    /// Resolve the conflicts of every document, which are otherwise only resolved when the document is read
+    ResolveConflicts,
+}
+
+#[derive(Debug)]
+enum AdminError {
+    Database(HackClientError),
+    Io(PathBuf, io::Error),
+    InvalidLine(PathBuf, usize, serde_json::Error),
+    TokenNotFound(String),
+}
+
+impl std::error::Error for AdminError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Database(e) => Some(e),
+            Self::Io(_, e) => Some(e),
+            Self::InvalidLine(_, _, e) => Some(e),
+            Self::TokenNotFound(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Database(_) => write!(f, "Error while using the database"),
+            Self::Io(path, _) => write!(f, "Error while accessing {:?}", path),
+            Self::InvalidLine(path, line, _) => {
+                write!(f, "The line {} of {:?} isn't a valid token", line, path)
+            }
+            Self::TokenNotFound(id) => write!(f, "The majority token {} doesn't exist", id),
+        }
+    }
+}
+
+impl From<HackClientError> for AdminError {
+    fn from(e: HackClientError) -> Self {
+        Self::Database(e)
+    }
+}
+
+/// Read a secret from a file. A trailing line break is ignored, as in the server.
+fn read_secret(path: &Path) -> Result<String, AdminError> {
+    fs::read_to_string(path)
+        .map(|content| content.trim_end_matches(['\n', '\r']).to_string())
+        .map_err(|e| AdminError::Io(path.to_path_buf(), e))
+}
+
+async fn create_root_token(
+    client: &HackClient,
+    admin: bool,
+    days: Option<u64>,
+) -> Result<(), AdminError> {
+    let secret = loop {
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(16)
+            .map(char::from)
+            .collect();
+        if client.get_majority_token(&secret).await?.is_none() {
+            break secret;
+        }
+    };
+    let id = client.token_hasher().hash(&secret);
+    let token = MajorityToken {
+        _id: id.clone(),
+        _rev: String::new(),
+        _deleted: None,
+        certify: BTreeSet::new(),
+        certified_at: BTreeMap::new(),
+        admin_flags: FieldWithTime::new(MajorityTokenAdminFlags {
+            can_certify: true,
+            need_certification: false,
+            revoked: false,
+            is_admin: admin,
+            revocation_reason: None,
+        }),
+        latest_certification_timestamp: 0,
+        expires_at: FieldWithTime::new(
+            days.map(|days| get_timestamp().saturating_add(days.saturating_mul(24 * 3600))),
+        ),
+        _conflicts: Vec::new(),
+    };
+    client.save_majority_token(token).await?;
+    println!("token id: {}", id);
+    // only the hash is stored, so this is the only time the secret can be seen
+    println!("token secret: {}", secret);
+    Ok(())
+}
+
+async fn inspect(client: &HackClient, token: &str) -> Result<(), AdminError> {
+    let id = client.token_hasher().to_id(token);
+    let token = client
+        .get_majority_token_by_id(&id)
+        .await?
+        .ok_or_else(|| AdminError::TokenNotFound(id.clone()))?;
+    //unwrap: a token should never fail to serialize
+    println!("{}", serde_json::to_string_pretty(&token).unwrap());
+    if token.is_expired(get_timestamp()) {
+        println!("this token has expired");
+    }
+
+    let certifiers = client.get_certifiers(&id).await?;
+    if certifiers.is_empty() {
+        println!("certified by: nobody (root token)");
+    }
+    for certifier in &certifiers {
+        println!("certified by: {}", certifier._id);
+    }
+
+    match client.get_token_usage(&id).await? {
+        Some(usage) => {
+            let total = usage.total();
+            println!(
+                "usage: {} connections, {} requests, from {} networks, first seen at {}, last seen at {}",
+                total.connections,
+                total.requests,
+                total.networks.len(),
+                total.first_seen,
+                total.last_seen
+            );
+        }
+        None => println!("usage: never used"),
+    }
+
+    let conflict_history = client.get_conflict_history(&id).await?;
+    println!("{} resolved conflicts", conflict_history.len());
+    for entry in &conflict_history {
+        println!(
+            "conflict resolved at {} between {} versions",
+            entry.timestamp,
+            entry.conflicting_docs.len()
+        );
+    }
+    Ok(())
+}
+
+async fn export(client: &HackClient, output: Option<&Path>) -> Result<(), AdminError> {
+    let io_error = |e| AdminError::Io(output.unwrap_or(Path::new("-")).to_path_buf(), e);
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path).map_err(io_error)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut tokens = client.list_majority_tokens().await?;
+    tokens.sort_by(|first, second| first._id.cmp(&second._id));
+    for mut token in tokens {
+        // the revision is specific to this database
+        token._rev = String::new();
+        token._conflicts.clear();
+        //unwrap: a token should never fail to serialize
+        writeln!(writer, "{}", serde_json::to_string(&token).unwrap()).map_err(io_error)?;
+    }
+    writer.flush().map_err(io_error)?;
+    Ok(())
+}
+
+async fn import(client: &HackClient, input: &Path) -> Result<(), AdminError> {
+    let file = File::open(input).map_err(|e| AdminError::Io(input.to_path_buf(), e))?;
+    let mut imported_count = 0;
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| AdminError::Io(input.to_path_buf(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut token: MajorityToken = serde_json::from_str(&line)
+            .map_err(|e| AdminError::InvalidLine(input.to_path_buf(), line_number + 1, e))?;
+        token._rev = String::new();
+        token._conflicts.clear();
+        let token = match client.get_majority_token_by_id(&token._id).await? {
+            Some(mut existing) => {
+                existing.merge(&token);
+                existing
+            }
+            None => token,
+        };
+        client.save_majority_token(token).await?;
+        imported_count += 1;
+    }
+    println!("{} majority tokens imported", imported_count);
+    Ok(())
+}
+
+async fn run(opts: Opts) -> Result<(), AdminError> {
+    let password = read_secret(&opts.password_file)?;
+    let hasher = TokenHasher::new(read_secret(&opts.hash_key_file)?.as_bytes());
+    // the databases are created if they don't exist
+    let client =
+        HackClient::new_from_connection_info(&opts.uri, &opts.username, &password, hasher).await?;
+
+    match opts.command {
+        Command::Init => {
+            client.install_indexes().await?;
+            println!("databases and indexes ready");
+        }
+        Command::CreateRootToken { admin, days } => create_root_token(&client, admin, days).await?,
+        Command::Inspect { token } => inspect(&client, &token).await?,
+        Command::Revoke { token, reason } => {
+            let id = client.token_hasher().to_id(&token);
+            let revoked_count = client.revoke_majority_token_cascading(&id, &reason).await?;
+            println!("{} majority tokens revoked", revoked_count);
+        }
+        Command::Export { output } => export(&client, output.as_deref()).await?,
+        Command::Import { input } => import(&client, &input).await?,
+        Command::ResolveConflicts => {
+            let resolved_count = client.resolve_conflicts().await?;
+            println!("{} documents had conflicts", resolved_count);
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    if let Err(error) = run(Opts::parse()).await {
+        eprintln!("{}", DisplayErrorChain::new(&error));
+        std::process::exit(1);
+    }
+}
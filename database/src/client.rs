@@ -1,24 +1,40 @@
-use std::{error::Error, fmt::Display, mem::swap};
+use std::{collections::HashMap, error::Error, fmt::Display, io, mem::swap, path::PathBuf};
 
+use async_trait::async_trait;
 use couch_rs::{
     database::Database,
     document::TypedCouchDocument,
     error::CouchError,
+    http::StatusCode,
     types::{
         document::{DocumentCreatedDetails, DocumentId},
+        find::{FindQuery, SortSpec},
+        index::IndexFields,
         query::QueryParams,
     },
     Client,
-    http::StatusCode
 };
+use reqwest::{header::CONTENT_TYPE, Method};
+use serde::{de::DeserializeOwned, Deserialize};
 use serde_json::json;
 use uuid::Uuid;
 
-use crate::{get_timestamp, model::MajorityToken, Mergeable};
+use crate::{
+    get_timestamp,
+    model::{ConflictLogEntry, FailedAttempts, MajorityToken, NodeUsage, TokenUsage},
+    AttemptStore, MajorityTokenStore, Mergeable, TokenHasher, UsageStore,
+};
 
 #[derive(Debug)]
 pub enum HackClientError {
     InternalDBError(CouchError),
+    /// Error reading or writing the file of a [`FileTokenStore`](crate::FileTokenStore)
+    FileStoreIOError(PathBuf, io::Error),
+    /// A line of the file of a [`FileTokenStore`](crate::FileTokenStore) isn't a valid token. Line numbers start at 1.
+    FileStoreInvalidLine(PathBuf, usize, serde_json::Error),
+    /// Error of a request made directly to the CouchDB API, see [`CouchDbApi`]
+    CouchDbRequestError(reqwest::Error),
+    InvalidCouchDbResponse(serde_json::Error),
 }
 
 impl Error for HackClientError {}
@@ -31,6 +47,24 @@ impl Display for HackClientError {
                 "Internal error while communcating with the dabase: {}",
                 e
             ),
+            Self::FileStoreIOError(path, e) => {
+                write!(
+                    f,
+                    "Error while accessing the token file at {:?}: {}",
+                    path, e
+                )
+            }
+            Self::FileStoreInvalidLine(path, line, e) => write!(
+                f,
+                "The line {} of the token file at {:?} is invalid: {}",
+                line, path, e
+            ),
+            Self::CouchDbRequestError(e) => {
+                write!(f, "Error while sending a request to CouchDB: {}", e)
+            }
+            Self::InvalidCouchDbResponse(e) => {
+                write!(f, "CouchDB returned an unexpected response: {}", e)
+            }
         }
     }
 }
@@ -41,26 +75,121 @@ impl From<CouchError> for HackClientError {
     }
 }
 
+impl From<reqwest::Error> for HackClientError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::CouchDbRequestError(e)
+    }
+}
+
 impl HackClientError {
     pub fn end_user_error_message(&self) -> String {
         match self {
-            Self::InternalDBError(_) => "there has been an internal database error".to_string(),
+            Self::InternalDBError(_)
+            | Self::FileStoreIOError(_, _)
+            | Self::FileStoreInvalidLine(_, _, _)
+            | Self::CouchDbRequestError(_)
+            | Self::InvalidCouchDbResponse(_) => {
+                "there has been an internal database error".to_string()
+            }
         }
     }
 }
 
+/// Direct access to the HTTP API of CouchDB, for the requests couch_rs doesn't provide
+#[derive(Clone)]
+struct CouchDbApi {
+    http: reqwest::Client,
+    uri: String,
+    username: String,
+    password: String,
+}
+
+impl CouchDbApi {
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<T, HackClientError> {
+        let mut request = self
+            .http
+            .request(
+                method,
+                format!("{}/{}", self.uri.trim_end_matches('/'), path),
+            )
+            .basic_auth(&self.username, Some(&self.password));
+        if let Some(body) = body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+        let response = request.send().await?.error_for_status()?;
+        serde_json::from_slice(&response.bytes().await?)
+            .map_err(HackClientError::InvalidCouchDbResponse)
+    }
+}
+
+#[derive(Deserialize)]
+struct ChangesResponse {
+    results: Vec<Change>,
+}
+
+/// The leaf revisions of a document, in the `all_docs` style
+#[derive(Deserialize)]
+struct Change {
+    id: String,
+    changes: Vec<ChangeRevision>,
+    /// Whether the winning revision is deleted. It is only the case when every leaf revision is.
+    #[serde(default)]
+    deleted: bool,
+}
+
+#[derive(Deserialize)]
+struct ChangeRevision {
+    rev: String,
+}
+
+#[derive(Deserialize)]
+struct PurgeResponse {
+    purged: HashMap<String, Vec<String>>,
+}
+
 #[derive(Clone)]
 pub struct HackClient {
     majority_token: Database,
     /// A database logging resolution of conflict, for debugging purpose. Is only written to.
     conflict_log: Database,
+    /// The failed attempts to connect with a majority token, per IP address or subnet
+    failed_attempts: Database,
+    /// How each majority token is used
+    token_usage: Database,
+    api: CouchDbApi,
+    hasher: TokenHasher,
 }
 
 impl HackClient {
-    pub async fn new(db_client: Client) -> Result<Self, HackClientError> {
+    /// The names of the databases start with `prefix`, so tests can use their own
+    async fn new(
+        uri: &str,
+        username: &str,
+        password: &str,
+        hasher: TokenHasher,
+        prefix: &str,
+    ) -> Result<Self, HackClientError> {
+        let db_client = Client::new(uri, username, password)?;
+        let db = |name: &str| format!("{}{}", prefix, name);
         Ok(Self {
-            majority_token: db_client.db("majority_token").await?,
-            conflict_log: db_client.db("conflict_log").await?,
+            majority_token: db_client.db(&db("majority_token")).await?,
+            conflict_log: db_client.db(&db("conflict_log")).await?,
+            failed_attempts: db_client.db(&db("failed_attempts")).await?,
+            token_usage: db_client.db(&db("token_usage")).await?,
+            api: CouchDbApi {
+                http: reqwest::Client::new(),
+                uri: uri.to_string(),
+                username: username.to_string(),
+                password: password.to_string(),
+            },
+            hasher,
         })
     }
 
@@ -68,29 +197,44 @@ impl HackClient {
         uri: &str,
         username: &str,
         password: &str,
+        hasher: TokenHasher,
     ) -> Result<Self, HackClientError> {
-        let client = Client::new(uri, username, password)?;
-        Self::new(client).await
+        Self::new(uri, username, password, hasher, "").await
     }
 
+    /// Only the conflicts of majority tokens are logged, as the entries are read as [`ConflictLogEntry`].
+    /// Documents stored under a plaintext secret (not yet migrated majority tokens) are not logged, so the log never contain a secret
     async fn log_handled_conflict<T: TypedCouchDocument>(
         &self,
         db_name: &str,
+        doc_id: &str,
         raw_docs: &[T],
         conflicting_docs: Vec<T>,
-    ) -> Result<DocumentCreatedDetails, HackClientError> {
-        Ok(self
-            .conflict_log
-            .save(&mut json!(
-                {
-                    "_id": Uuid::new_v4(),
-                    "db": db_name,
-                    "timestamp": get_timestamp(),
-                    "raw_docs": raw_docs,
-                    "conflicting_docs": conflicting_docs
-                }
-            ))
-            .await?)
+    ) -> Result<Option<DocumentCreatedDetails>, HackClientError> {
+        if db_name != self.majority_token.name() {
+            return Ok(None);
+        }
+        if !TokenHasher::is_hashed(doc_id) {
+            log::warn!(
+                "a conflict in {} was resolved but not logged, as the document isn't stored under a hashed id",
+                db_name
+            );
+            return Ok(None);
+        }
+        Ok(Some(
+            self.conflict_log
+                .save(&mut json!(
+                    {
+                        "_id": Uuid::new_v4(),
+                        "db": db_name,
+                        "doc_id": doc_id,
+                        "timestamp": get_timestamp(),
+                        "raw_docs": raw_docs,
+                        "conflicting_docs": conflicting_docs
+                    }
+                ))
+                .await?,
+        ))
     }
 
     /// Resolve a conflict.
@@ -136,11 +280,14 @@ impl HackClient {
             let mut logged_conflicts = vec![base_document.clone()];
 
             let mut raw_docs: Vec<T> = Vec::new();
+            // the losing revisions are deleted without their content, which is kept by the merge result
+            let mut to_save = Vec::new();
             //TODO: the different part with write and read will be here
             //4. merge the conflicting documents
             for mut other_document in conflicts.into_iter() {
                 logged_conflicts.push(other_document.clone());
                 base_document.merge(&other_document);
+                to_save.push(deletion_marker(&other_document));
                 other_document.mark_as_deleted();
                 raw_docs.push(other_document);
             }
@@ -149,11 +296,13 @@ impl HackClient {
 
             raw_docs.push(base_document.clone());
             logged_conflicts.push(base_document.clone());
+            //unwrap: documents should never fail to serialize
+            to_save.push(serde_json::to_value(&base_document).unwrap());
 
-            self.log_handled_conflict(database.name(), &raw_docs, logged_conflicts)
+            self.log_handled_conflict(database.name(), &document_id, &raw_docs, logged_conflicts)
                 .await?;
 
-            for result in database.bulk_docs(&mut raw_docs).await? {
+            for result in database.bulk_docs(&mut to_save).await? {
                 if let Err(e) = result {
                     if !matches!(
                         e.status(),
@@ -229,17 +378,466 @@ impl HackClient {
         }
     }
 
+    /// Resolve the conflicts of every document of the database. Return the number of documents that had conflicts.
+    async fn resolve_all_conflicts<T: TypedCouchDocument + std::fmt::Debug + Mergeable>(
+        &self,
+        database: &Database,
+    ) -> Result<usize, HackClientError> {
+        let ids = database
+            .get_all::<T>()
+            .await?
+            .rows
+            .iter()
+            .map(|document| document.get_id().to_string())
+            .collect::<Vec<_>>();
+        let mut resolved_count = 0;
+        for mut document in database
+            .get_bulk_params::<T>(ids, Some(QueryParams::default().conflicts(true)))
+            .await?
+            .rows
+        {
+            if !document.get_conflicts_mut().is_empty() {
+                self.handle_conflict::<T>(database, document.get_id().into(), None)
+                    .await?;
+                resolved_count += 1;
+            }
+        }
+        Ok(resolved_count)
+    }
+
+    /// Resolve the conflicts of every majority token, usage statistic and failed attempt counter, which are otherwise only
+    /// resolved when read. Return the number of documents that had conflicts.
+    pub async fn resolve_conflicts(&self) -> Result<usize, HackClientError> {
+        Ok(self
+            .resolve_all_conflicts::<MajorityToken>(&self.majority_token)
+            .await?
+            + self
+                .resolve_all_conflicts::<TokenUsage>(&self.token_usage)
+                .await?
+            + self
+                .resolve_all_conflicts::<FailedAttempts>(&self.failed_attempts)
+                .await?)
+    }
+
+    /// Create the indexes used to query the databases. Indexes that already exist are left unchanged.
+    /// The databases themselves are created when the client is.
+    pub async fn install_indexes(&self) -> Result<(), HackClientError> {
+        // used by get_conflict_history
+        self.conflict_log
+            .insert_index(
+                "by-db-and-doc-id",
+                IndexFields::new(vec![
+                    SortSpec::Simple("db".to_string()),
+                    SortSpec::Simple("doc_id".to_string()),
+                ]),
+                None,
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
     /// If the _rev value is defined, update the token, otherwise, attempt to create it
     pub async fn save_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError> {
         self.save_and_resolve_conflict(&self.majority_token, token)
             .await
     }
 
-    pub async fn get_majority_token(
+    pub async fn get_majority_token_by_id(
         &self,
-        password: &str,
+        id: &str,
+    ) -> Result<Option<MajorityToken>, HackClientError> {
+        self.get_and_resolve_conflict_one(&self.majority_token, id.into())
+            .await
+    }
+
+    /// The conflicts of the token are resolved first, then its latest version is replaced by a deletion marker without content,
+    /// so no version of a token stored under its plaintext secret keeps the ids it certified.
+    /// The id itself is still kept by CouchDB in the marker, until it is purged by [`HackClient::purge_deleted_plaintext_tokens`].
+    pub async fn delete_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError> {
+        loop {
+            let current = match self.get_majority_token_by_id(&token._id).await? {
+                Some(current) => current,
+                None => return Ok(()),
+            };
+            match self
+                .majority_token
+                .save(&mut deletion_marker(&current))
+                .await
+            {
+                Ok(_) => return Ok(()),
+                // modified since it was read
+                Err(e) if e.status() == Some(StatusCode::CONFLICT) => continue,
+                Err(e) => return Err(HackClientError::InternalDBError(e)),
+            }
+        }
+    }
+
+    /// Conflicts are resolved
+    pub async fn list_majority_tokens(&self) -> Result<Vec<MajorityToken>, HackClientError> {
+        let ids = self
+            .majority_token
+            .get_all::<MajorityToken>()
+            .await?
+            .rows
+            .into_iter()
+            .map(|token| token._id)
+            .collect();
+        self.get_and_resolve_conflict(&self.majority_token, ids)
+            .await
+    }
+
+    pub async fn get_conflict_history(
+        &self,
+        id: &str,
+    ) -> Result<Vec<ConflictLogEntry>, HackClientError> {
+        let query = FindQuery::new(json!({
+            "db": self.majority_token.name(),
+            "doc_id": id,
+        }))
+        .limit(1000);
+        let mut entries = self
+            .conflict_log
+            .find::<ConflictLogEntry>(&query)
+            .await?
+            .rows;
+        entries.sort_by_key(|entry| entry.timestamp);
+        Ok(entries)
+    }
+
+    /// Permanently remove the deleted majority tokens stored under their plaintext secret, with all their revisions,
+    /// as the id of their deletion marker is still the secret. Tokens still stored under their secret are kept.
+    /// Purges aren't replicated, so this must be done on every replica. Return the number of purged tokens.
+    pub async fn purge_deleted_plaintext_tokens(&self) -> Result<usize, HackClientError> {
+        let db_name = self.majority_token.name();
+        let changes: ChangesResponse = self
+            .api
+            .request(
+                Method::GET,
+                &format!("{}/_changes?style=all_docs", db_name),
+                None,
+            )
+            .await?;
+        let mut to_purge = Vec::new();
+        for change in changes.results {
+            if TokenHasher::is_hashed(&change.id) || change.id.starts_with("_design/") {
+                continue;
+            }
+            if change.deleted {
+                let revisions = change.changes.into_iter().map(|change| change.rev);
+                to_purge.push((change.id, revisions.collect::<Vec<_>>()));
+            } else {
+                log::warn!("a majority token is still stored under its plaintext secret, and isn't purged. It should be migrated first.");
+            }
+        }
+
+        let mut purged_count = 0;
+        // CouchDB limit the number of documents purged by a single request to 100 by default
+        for batch in to_purge.chunks(100) {
+            let response: PurgeResponse = self
+                .api
+                .request(
+                    Method::POST,
+                    &format!("{}/_purge", db_name),
+                    Some(json!(batch.iter().cloned().collect::<HashMap<_, _>>())),
+                )
+                .await?;
+            purged_count += response.purged.len();
+        }
+        Ok(purged_count)
+    }
+
+    /// Entries that couldn't be removed are kept, and a warning is logged.
+    /// Entries about other databases than the majority tokens are skipped, as they aren't [`ConflictLogEntry`].
+    pub async fn purge_plaintext_conflict_history(&self) -> Result<usize, HackClientError> {
+        let mut purged_count = 0;
+        for raw_entry in self.conflict_log.get_all::<serde_json::Value>().await?.rows {
+            if raw_entry["db"] != self.majority_token.name() {
+                continue;
+            }
+            let entry = match serde_json::from_value::<ConflictLogEntry>(raw_entry) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::warn!("failed to read a conflict log entry: {}", e);
+                    continue;
+                }
+            };
+            if entry.contains_plaintext_token() {
+                if self.conflict_log.remove(&entry).await {
+                    purged_count += 1;
+                } else {
+                    log::warn!("failed to remove the conflict log entry {}", entry._id);
+                }
+            }
+        }
+        Ok(purged_count)
+    }
+
+    pub async fn get_failed_attempts(
+        &self,
+        key: &str,
+    ) -> Result<Option<FailedAttempts>, HackClientError> {
+        self.get_and_resolve_conflict_one(&self.failed_attempts, key.into())
+            .await
+    }
+
+    pub async fn record_failed_attempt(
+        &self,
+        key: &str,
+        now: u64,
+        forget_after: u64,
+    ) -> Result<FailedAttempts, HackClientError> {
+        let mut failed = self
+            .get_failed_attempts(key)
+            .await?
+            .unwrap_or_else(|| FailedAttempts::new(key));
+        failed.record_failure(now, forget_after);
+        self.save_and_resolve_conflict(&self.failed_attempts, failed.clone())
+            .await?;
+        Ok(failed)
+    }
+
+    pub async fn clear_failed_attempts(&self, key: &str) -> Result<(), HackClientError> {
+        if let Some(mut failed) = self.get_failed_attempts(key).await? {
+            failed.mark_as_deleted();
+            self.save_and_resolve_conflict(&self.failed_attempts, failed)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_token_usage(&self, id: &str) -> Result<Option<TokenUsage>, HackClientError> {
+        self.get_and_resolve_conflict_one(&self.token_usage, id.into())
+            .await
+    }
+
+    /// Conflicts are not resolved
+    pub async fn list_token_usage(&self) -> Result<Vec<TokenUsage>, HackClientError> {
+        Ok(self.token_usage.get_all::<TokenUsage>().await?.rows)
+    }
+
+    /// As only this server modify its counters, a concurrent modification by another server is merged without loss
+    pub async fn add_token_usage(
+        &self,
+        id: &str,
+        node: &str,
+        usage: &NodeUsage,
+    ) -> Result<(), HackClientError> {
+        let mut token_usage = self
+            .get_token_usage(id)
+            .await?
+            .unwrap_or_else(|| TokenUsage::new(id));
+        token_usage
+            .nodes
+            .entry(node.to_string())
+            .or_default()
+            .add(usage);
+        self.save_and_resolve_conflict(&self.token_usage, token_usage)
+            .await
+    }
+}
+
+/// A revision deleting the document, without any of its content
+fn deletion_marker<T: TypedCouchDocument>(document: &T) -> serde_json::Value {
+    json!({
+        "_id": document.get_id(),
+        "_rev": document.get_rev(),
+        "_deleted": true,
+    })
+}
+
+#[async_trait]
+impl MajorityTokenStore for HackClient {
+    fn token_hasher(&self) -> &TokenHasher {
+        &self.hasher
+    }
+
+    async fn get_majority_token_by_id(
+        &self,
+        id: &str,
     ) -> Result<Option<MajorityToken>, HackClientError> {
-        self.get_and_resolve_conflict_one(&self.majority_token, password.into())
+        HackClient::get_majority_token_by_id(self, id).await
+    }
+
+    async fn save_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError> {
+        HackClient::save_majority_token(self, token).await
+    }
+
+    async fn delete_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError> {
+        HackClient::delete_majority_token(self, token).await
+    }
+
+    async fn list_majority_tokens(&self) -> Result<Vec<MajorityToken>, HackClientError> {
+        HackClient::list_majority_tokens(self).await
+    }
+
+    async fn get_conflict_history(
+        &self,
+        id: &str,
+    ) -> Result<Vec<ConflictLogEntry>, HackClientError> {
+        HackClient::get_conflict_history(self, id).await
+    }
+
+    /// Purge the deleted tokens stored under their plaintext secret
+    async fn compact(&self) -> Result<(), HackClientError> {
+        let purged_count = self.purge_deleted_plaintext_tokens().await?;
+        log::info!(
+            "purged {} deleted majority tokens stored under their secret",
+            purged_count
+        );
+        Ok(())
+    }
+
+    async fn purge_plaintext_conflict_history(&self) -> Result<usize, HackClientError> {
+        HackClient::purge_plaintext_conflict_history(self).await
+    }
+}
+
+#[async_trait]
+impl AttemptStore for HackClient {
+    async fn get_failed_attempts(
+        &self,
+        key: &str,
+    ) -> Result<Option<FailedAttempts>, HackClientError> {
+        HackClient::get_failed_attempts(self, key).await
+    }
+
+    async fn record_failed_attempt(
+        &self,
+        key: &str,
+        now: u64,
+        forget_after: u64,
+    ) -> Result<FailedAttempts, HackClientError> {
+        HackClient::record_failed_attempt(self, key, now, forget_after).await
+    }
+
+    async fn clear_failed_attempts(&self, key: &str) -> Result<(), HackClientError> {
+        HackClient::clear_failed_attempts(self, key).await
+    }
+}
+
+#[async_trait]
+impl UsageStore for HackClient {
+    async fn get_token_usage(&self, id: &str) -> Result<Option<TokenUsage>, HackClientError> {
+        HackClient::get_token_usage(self, id).await
+    }
+
+    async fn list_token_usage(&self) -> Result<Vec<TokenUsage>, HackClientError> {
+        HackClient::list_token_usage(self).await
+    }
+
+    async fn add_token_usage(
+        &self,
+        id: &str,
+        node: &str,
+        usage: &NodeUsage,
+    ) -> Result<(), HackClientError> {
+        HackClient::add_token_usage(self, id, node, usage).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use reqwest::Method;
+
+    use super::ChangesResponse;
+    use crate::{
+        model::{MajorityToken, MajorityTokenAdminFlags},
+        FieldWithTime, HackClient, MajorityTokenStore, TokenHasher,
+    };
+
+    fn new_token(id: &str) -> MajorityToken {
+        MajorityToken {
+            _id: id.to_string(),
+            _rev: String::new(),
+            _deleted: None,
+            certify: BTreeSet::new(),
+            admin_flags: FieldWithTime::new(MajorityTokenAdminFlags {
+                can_certify: true,
+                need_certification: false,
+                revoked: false,
+                is_admin: false,
+                revocation_reason: None,
+            }),
+            certified_at: BTreeMap::new(),
+            latest_certification_timestamp: 0,
+            expires_at: FieldWithTime::default(),
+            _conflicts: Vec::new(),
+        }
+    }
+
+    /// Need a CouchDB server, given by the HACK_ARCHIVE_TEST_COUCHDB_URI, HACK_ARCHIVE_TEST_COUCHDB_USERNAME and
+    /// HACK_ARCHIVE_TEST_COUCHDB_PASSWORD environment variables, and is skipped without them.
+    /// Its databases are created for the test, and removed at the end.
+    #[tokio::test]
+    async fn test_couchdb_migration_leaves_no_plaintext_id() {
+        let variable = |name: &str| std::env::var(format!("HACK_ARCHIVE_TEST_COUCHDB_{}", name));
+        let (uri, username, password) =
+            match (variable("URI"), variable("USERNAME"), variable("PASSWORD")) {
+                (Ok(uri), Ok(username), Ok(password)) => (uri, username, password),
+                _ => {
+                    eprintln!("no CouchDB server to test with, skipped");
+                    return;
+                }
+            };
+        let hasher = TokenHasher::new(b"key");
+        let prefix = format!("pmd_hack_archive_test_{}_", std::process::id());
+        let client = HackClient::new(&uri, &username, &password, hasher.clone(), &prefix)
+            .await
+            .unwrap();
+
+        let mut root = new_token("root-secret");
+        root.certify.insert("a-secret".to_string());
+        client.save_majority_token(root.clone()).await.unwrap();
+        // saved again without its revision, so the conflict is resolved, deleting the previous revision
+        client.save_majority_token(root).await.unwrap();
+        client
+            .save_majority_token(new_token("a-secret"))
+            .await
+            .unwrap();
+
+        assert_eq!(client.migrate_to_hashed_ids().await.unwrap(), 2);
+
+        // the changes list every document, including the deleted ones
+        let changes: ChangesResponse = client
+            .api
+            .request(
+                Method::GET,
+                &format!("{}/_changes?style=all_docs", client.majority_token.name()),
+                None,
+            )
+            .await
+            .unwrap();
+        let ids = changes
+            .results
+            .iter()
+            .map(|change| change.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.iter().all(|id| TokenHasher::is_hashed(id)));
+        let root = client
+            .get_majority_token("root-secret")
             .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            root.certify.into_iter().collect::<Vec<_>>(),
+            vec![hasher.hash("a-secret")]
+        );
+
+        for name in [
+            "majority_token",
+            "conflict_log",
+            "failed_attempts",
+            "token_usage",
+        ] {
+            client
+                .api
+                .request::<serde_json::Value>(Method::DELETE, &format!("{}{}", prefix, name), None)
+                .await
+                .unwrap();
+        }
     }
 }
@@ -53,3 +53,10 @@ impl<T: PartialEq + std::fmt::Debug + Clone> FieldWithTime<T> {
         best
     }
 }
+
+/// A default value that lose against any value set later
+impl<T: PartialEq + std::fmt::Debug + Clone + Default> Default for FieldWithTime<T> {
+    fn default() -> Self {
+        Self(T::default(), 0)
+    }
+}
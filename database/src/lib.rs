@@ -3,6 +3,18 @@ use std::time::{Duration, SystemTime};
 
 pub use client::{HackClient, HackClientError};
 
+mod store;
+pub use store::{FileTokenStore, MajorityTokenStore, MemoryTokenStore};
+
+mod attempt_store;
+pub use attempt_store::{AttemptStore, MemoryAttemptStore};
+
+mod usage_store;
+pub use usage_store::{MemoryUsageStore, UsageStore};
+
+mod token_hash;
+pub use token_hash::TokenHasher;
+
 pub mod model;
 
 mod field_with_time;
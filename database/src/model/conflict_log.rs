@@ -0,0 +1,31 @@
+use couch_rs::document::TypedCouchDocument;
+use couch_rs::{types::document::DocumentId, CouchDocument};
+use serde::{Deserialize, Serialize};
+
+use crate::{model::MajorityToken, TokenHasher};
+
+/// A conflict between multiple versions of a token, and how it was resolved
+#[derive(Serialize, Deserialize, CouchDocument, Debug, Clone)]
+pub struct ConflictLogEntry {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub _id: DocumentId,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub _rev: String,
+    /// The id of the token in conflict. Absent from entries logged before it was added.
+    #[serde(default)]
+    pub doc_id: String,
+    pub timestamp: u64,
+    /// Every conflicting version, followed by the result of their merge
+    pub conflicting_docs: Vec<MajorityToken>,
+}
+
+impl ConflictLogEntry {
+    /// Whether this entry contain a token, or a reference to a token, stored under its plaintext secret
+    pub fn contains_plaintext_token(&self) -> bool {
+        !TokenHasher::is_hashed(&self.doc_id)
+            || self.conflicting_docs.iter().any(|token| {
+                !TokenHasher::is_hashed(&token._id)
+                    || token.certify.iter().any(|id| !TokenHasher::is_hashed(id))
+            })
+    }
+}
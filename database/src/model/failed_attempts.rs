@@ -0,0 +1,61 @@
+use couch_rs::document::TypedCouchDocument;
+use couch_rs::{types::document::DocumentId, CouchDocument};
+use serde::{Deserialize, Serialize};
+
+use crate::Mergeable;
+
+/// The failed attempts to connect with a majority token, from an IP address or a subnet
+#[derive(Serialize, Deserialize, CouchDocument, Debug, Clone, PartialEq, Eq)]
+pub struct FailedAttempts {
+    /// The IP address or subnet the attempts come from, like `ip:192.0.2.1` or `subnet:192.0.2.0/24`
+    pub _id: DocumentId,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub _rev: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _deleted: Option<bool>,
+    /// The number of failures since the counter was last reset
+    pub failures: u32,
+    /// Timestamp of the latest failure
+    pub latest_failure: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default = "Vec::default")]
+    pub _conflicts: Vec<FailedAttempts>,
+}
+
+impl FailedAttempts {
+    pub fn new(key: &str) -> Self {
+        Self {
+            _id: key.to_string(),
+            _rev: String::new(),
+            _deleted: None,
+            failures: 0,
+            latest_failure: 0,
+            _conflicts: Vec::new(),
+        }
+    }
+
+    /// Count a new failure at `now`. Failures older than `forget_after` seconds are forgotten first.
+    pub fn record_failure(&mut self, now: u64, forget_after: u64) {
+        if now.saturating_sub(self.latest_failure) >= forget_after {
+            self.failures = 0;
+        }
+        self.failures = self.failures.saturating_add(1);
+        self.latest_failure = self.latest_failure.max(now);
+    }
+}
+
+impl Mergeable for FailedAttempts {
+    /// Concurrent failures may be lost, but the counter never goes down
+    fn merge(&mut self, other: &Self) {
+        self.failures = self.failures.max(other.failures);
+        self.latest_failure = self.latest_failure.max(other.latest_failure);
+    }
+
+    fn mark_as_deleted(&mut self) {
+        self._deleted = Some(true);
+    }
+
+    fn get_conflicts_mut(&mut self) -> &mut Vec<Self> {
+        &mut self._conflicts
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use couch_rs::document::TypedCouchDocument;
 use couch_rs::{types::document::DocumentId, CouchDocument};
@@ -8,19 +8,26 @@ use crate::{FieldWithTime, Mergeable};
 
 #[derive(Serialize, Deserialize, CouchDocument, Debug, Clone)]
 pub struct MajorityToken {
-    /// The password of the token. Also the primary key.
+    /// The hash of the secret of the token, computed by [`TokenHasher`](crate::TokenHasher). Also the primary key.
+    /// Tokens created before hashing was introduced use the secret itself, until they are migrated.
     pub _id: DocumentId,
     #[serde(skip_serializing_if = "String::is_empty")]
     pub _rev: String,
     //TODO: user just bool instead of Option. false do nothing and is skipped
     #[serde(skip_serializing_if = "Option::is_none")]
     pub _deleted: Option<bool>,
-    /// The list of user this majority token has certified
+    /// The ids of the tokens this majority token has certified
     // Assumed to be append-only
     pub certify: BTreeSet<String>,
+    /// When each token of `certify` was certified. Missing for certifications made before they were recorded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub certified_at: BTreeMap<String, u64>,
     pub admin_flags: FieldWithTime<MajorityTokenAdminFlags>,
     #[serde(default = "u64::default")]
     pub latest_certification_timestamp: u64,
+    /// Timestamp after which the token is no longer valid, None if it never expire. Set by the certifier, and extended by renewals.
+    #[serde(default)]
+    pub expires_at: FieldWithTime<Option<u64>>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(default = "Vec::default")]
     pub _conflicts: Vec<MajorityToken>,
@@ -29,11 +36,26 @@ pub struct MajorityToken {
 impl Mergeable for MajorityToken {
     fn merge(&mut self, other: &Self) {
         self.certify.extend(other.certify.iter().cloned());
+        for (id, timestamp) in &other.certified_at {
+            self.certified_at
+                .entry(id.clone())
+                .and_modify(|existing| *existing = (*existing).min(*timestamp))
+                .or_insert(*timestamp);
+        }
+        // a revocation is never undone by a merge, whichever version of the flags wins
+        let revoked = self.admin_flags.0.revoked || other.admin_flags.0.revoked;
         self.admin_flags.merge(&other.admin_flags);
-        self.admin_flags.0.revoked |= other.admin_flags.0.revoked;
+        self.admin_flags.0.revoked = revoked;
         if other.latest_certification_timestamp > self.latest_certification_timestamp {
             self.latest_certification_timestamp = other.latest_certification_timestamp;
         };
+        // the latest change wins. If both were changed during the same second, the latest expiry wins,
+        // so the result doesn't depend on the order the versions are merged in.
+        if other.expires_at.1 == self.expires_at.1 {
+            self.expires_at.0 = latest_expiry(self.expires_at.0, other.expires_at.0);
+        } else {
+            self.expires_at.merge(&other.expires_at);
+        }
     }
 
     fn mark_as_deleted(&mut self) {
@@ -45,6 +67,29 @@ impl Mergeable for MajorityToken {
     }
 }
 
+impl MajorityToken {
+    /// Add a token to the certified ones, recording when it happened
+    pub fn add_certified(&mut self, id: &str, now: u64) {
+        self.certify.insert(id.to_string());
+        self.certified_at.entry(id.to_string()).or_insert(now);
+        self.latest_certification_timestamp = now;
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at
+            .get()
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// None never expire, so it is later than any timestamp
+fn latest_expiry(first: Option<u64>, second: Option<u64>) -> Option<u64> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.max(second)),
+        _ => None,
+    }
+}
+
 #[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
 pub struct MajorityTokenAdminFlags {
     /// Whether the list of user certified should be ignored when determining certified user
@@ -53,4 +98,10 @@ pub struct MajorityTokenAdminFlags {
     pub need_certification: bool,
     /// Whether this token is valid
     pub revoked: bool,
+    /// Whether this token give access to the administration pages
+    #[serde(default)]
+    pub is_admin: bool,
+    /// Why the token was revoked, as written by the admin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revocation_reason: Option<String>,
 }
@@ -1,2 +1,11 @@
 mod majority_token;
 pub use majority_token::*;
+
+mod conflict_log;
+pub use conflict_log::*;
+
+mod failed_attempts;
+pub use failed_attempts::*;
+
+mod token_usage;
+pub use token_usage::*;
@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use couch_rs::document::TypedCouchDocument;
+use couch_rs::{types::document::DocumentId, CouchDocument};
+use serde::{Deserialize, Serialize};
+
+use crate::Mergeable;
+
+/// How a majority token was used, as counted by a single server
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeUsage {
+    /// Number of times the token was entered
+    pub connections: u64,
+    /// Number of requests made with a session of the token
+    pub requests: u64,
+    /// Timestamp of the first use, 0 if never used
+    pub first_seen: u64,
+    /// Timestamp of the latest use
+    pub last_seen: u64,
+    /// Keyed hashes of the subnets the token was used from, up to [`NodeUsage::MAX_NETWORKS`]
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub networks: BTreeSet<String>,
+}
+
+impl NodeUsage {
+    /// Enough to tell a token shared publicly from one used by a single person
+    pub const MAX_NETWORKS: usize = 64;
+
+    pub fn record(&mut self, now: u64, network: Option<&str>) {
+        self.see(now);
+        if let Some(network) = network {
+            if self.networks.len() < Self::MAX_NETWORKS {
+                self.networks.insert(network.to_string());
+            }
+        }
+    }
+
+    fn see(&mut self, now: u64) {
+        if self.first_seen == 0 || now < self.first_seen {
+            self.first_seen = now;
+        }
+        self.last_seen = self.last_seen.max(now);
+    }
+
+    /// Merge the timestamps and the networks of both
+    fn merge_seen(&mut self, other: &Self) {
+        if other.first_seen != 0 {
+            self.see(other.first_seen);
+        }
+        self.last_seen = self.last_seen.max(other.last_seen);
+        for network in &other.networks {
+            if self.networks.len() >= Self::MAX_NETWORKS {
+                break;
+            }
+            self.networks.insert(network.clone());
+        }
+    }
+
+    /// Add the usage counted since the previous one
+    pub fn add(&mut self, other: &Self) {
+        self.connections = self.connections.saturating_add(other.connections);
+        self.requests = self.requests.saturating_add(other.requests);
+        self.merge_seen(other);
+    }
+
+    /// Merge two versions of the counters of the same server, where the highest counts are the latest
+    pub fn merge(&mut self, other: &Self) {
+        self.connections = self.connections.max(other.connections);
+        self.requests = self.requests.max(other.requests);
+        self.merge_seen(other);
+    }
+}
+
+/// How a majority token was used. Each server only increase its own counters, so versions can be merged
+/// without losing any use (as in a grow-only counter).
+#[derive(Serialize, Deserialize, CouchDocument, Debug, Clone)]
+pub struct TokenUsage {
+    /// The id of the majority token
+    pub _id: DocumentId,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub _rev: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _deleted: Option<bool>,
+    /// The usage counted by each server, by the id it picked on startup
+    pub nodes: BTreeMap<String, NodeUsage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default = "Vec::default")]
+    pub _conflicts: Vec<TokenUsage>,
+}
+
+impl TokenUsage {
+    pub fn new(id: &str) -> Self {
+        Self {
+            _id: id.to_string(),
+            _rev: String::new(),
+            _deleted: None,
+            nodes: BTreeMap::new(),
+            _conflicts: Vec::new(),
+        }
+    }
+
+    /// The usage counted by every server
+    pub fn total(&self) -> NodeUsage {
+        let mut total = NodeUsage::default();
+        for node in self.nodes.values() {
+            total.add(node);
+        }
+        total
+    }
+}
+
+impl Mergeable for TokenUsage {
+    fn merge(&mut self, other: &Self) {
+        for (node, usage) in &other.nodes {
+            self.nodes
+                .entry(node.clone())
+                .and_modify(|existing| existing.merge(usage))
+                .or_insert_with(|| usage.clone());
+        }
+    }
+
+    fn mark_as_deleted(&mut self) {
+        self._deleted = Some(true);
+    }
+
+    fn get_conflicts_mut(&mut self) -> &mut Vec<Self> {
+        &mut self._conflicts
+    }
+}
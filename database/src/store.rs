@@ -0,0 +1,758 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    get_timestamp,
+    model::{ConflictLogEntry, MajorityToken},
+    HackClientError, Mergeable, TokenHasher,
+};
+
+/// A place where majority tokens are stored.
+/// Implemented by [`HackClient`](crate::HackClient) for CouchDB, [`FileTokenStore`] and [`MemoryTokenStore`].
+#[async_trait]
+pub trait MajorityTokenStore: Send + Sync {
+    /// Give the id a token is stored under from its secret
+    fn token_hasher(&self) -> &TokenHasher;
+
+    async fn get_majority_token_by_id(
+        &self,
+        id: &str,
+    ) -> Result<Option<MajorityToken>, HackClientError>;
+
+    /// Find the token with this secret, stored under its hash
+    async fn get_majority_token(
+        &self,
+        secret: &str,
+    ) -> Result<Option<MajorityToken>, HackClientError> {
+        self.get_majority_token_by_id(&self.token_hasher().hash(secret))
+            .await
+    }
+
+    /// If the token was modified since it was read (based on its _rev value), both version are merged
+    async fn save_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError>;
+
+    /// Remove the token. The tokens it certified are left unchanged.
+    async fn delete_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError>;
+
+    /// Every stored token, in no particular order
+    async fn list_majority_tokens(&self) -> Result<Vec<MajorityToken>, HackClientError>;
+
+    /// The conflicts between versions of this token that were resolved, oldest first
+    async fn get_conflict_history(
+        &self,
+        id: &str,
+    ) -> Result<Vec<ConflictLogEntry>, HackClientError>;
+
+    /// Drop what the store still keeps of previous versions of tokens and of deleted tokens, if anything.
+    /// Called after a migration, so the plaintext secrets don't survive it.
+    async fn compact(&self) -> Result<(), HackClientError> {
+        Ok(())
+    }
+
+    /// Remove the conflict history entries containing tokens stored under their plaintext secret.
+    /// Return the number of removed entries.
+    async fn purge_plaintext_conflict_history(&self) -> Result<usize, HackClientError>;
+
+    /// Count the tokens stored under their plaintext secret, or certifying tokens by their secret, that
+    /// [`MajorityTokenStore::migrate_to_hashed_ids`] would migrate. Such tokens can't be found from their secret.
+    async fn count_unmigrated_tokens(&self) -> Result<usize, HackClientError> {
+        Ok(self
+            .list_majority_tokens()
+            .await?
+            .iter()
+            .filter(|token| {
+                !TokenHasher::is_hashed(&token._id)
+                    || !token.certify.iter().all(|id| TokenHasher::is_hashed(id))
+            })
+            .count())
+    }
+
+    /// Move the tokens still stored under their plaintext secret to their hashed id, and hash the ids they certify.
+    /// A token already present under its hashed id (when a previous migration was interrupted) is merged with the plaintext one,
+    /// so the migration can be resumed by running it again. The store is then compacted. Return the number of migrated tokens.
+    async fn migrate_to_hashed_ids(&self) -> Result<usize, HackClientError> {
+        let hasher = self.token_hasher();
+        let mut migrated_count = 0;
+        for token in self.list_majority_tokens().await? {
+            let id_is_hashed = TokenHasher::is_hashed(&token._id);
+            if id_is_hashed && token.certify.iter().all(|id| TokenHasher::is_hashed(id)) {
+                continue;
+            }
+
+            let mut migrated = token.clone();
+            migrated.certify = token.certify.iter().map(|id| hasher.to_id(id)).collect();
+            migrated.certified_at = token
+                .certified_at
+                .iter()
+                .map(|(id, timestamp)| (hasher.to_id(id), *timestamp))
+                .collect();
+            if id_is_hashed {
+                self.save_majority_token(migrated).await?;
+            } else {
+                migrated._id = hasher.hash(&token._id);
+                migrated._conflicts.clear();
+                match self.get_majority_token_by_id(&migrated._id).await? {
+                    Some(mut already_migrated) => {
+                        already_migrated.merge(&migrated);
+                        self.save_majority_token(already_migrated).await?;
+                    }
+                    None => {
+                        migrated._rev = String::new();
+                        self.save_majority_token(migrated).await?;
+                    }
+                }
+                self.delete_majority_token(token).await?;
+            }
+            migrated_count += 1;
+        }
+        // even without newly migrated tokens, as a previous migration may have been interrupted before it
+        self.compact().await?;
+        Ok(migrated_count)
+    }
+
+    /// The tokens that certified this one. Usually only one, except for tokens created before certification was tracked
+    /// or through the admin pages.
+    async fn get_certifiers(&self, id: &str) -> Result<Vec<MajorityToken>, HackClientError> {
+        Ok(self
+            .list_majority_tokens()
+            .await?
+            .into_iter()
+            .filter(|token| token.certify.contains(id))
+            .collect())
+    }
+
+    /// Return the token and every token it certified, recursively, with each token placed after all the tokens it certified.
+    /// The root is thus the last one. Tokens certified multiple time are only returned once, and tokens that doesn't exist are skipped.
+    async fn get_descendants_deepest_first(
+        &self,
+        root: &str,
+    ) -> Result<Vec<MajorityToken>, HackClientError> {
+        let root_token = match self.get_majority_token_by_id(root).await? {
+            Some(token) => token,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut result = Vec::new();
+        let mut visited = HashSet::from([root.to_string()]);
+        // the token being visited, and the ids of its certified tokens that are yet to be visited
+        let mut stack = vec![(
+            root_token.certify.iter().rev().cloned().collect::<Vec<_>>(),
+            root_token,
+        )];
+        while let Some((remaining, _)) = stack.last_mut() {
+            match remaining.pop() {
+                Some(certified_id) => {
+                    if visited.insert(certified_id.clone()) {
+                        if let Some(certified) =
+                            self.get_majority_token_by_id(&certified_id).await?
+                        {
+                            stack.push((
+                                certified.certify.iter().rev().cloned().collect(),
+                                certified,
+                            ));
+                        }
+                    }
+                }
+                None => {
+                    //unwrap: the loop condition ensure the stack isn't empty
+                    result.push(stack.pop().unwrap().1);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Revoke the token and every token it certified, recursively, with the deepest ones first.
+    /// As the root is revoked last, a revoked token implies all its descendants are revoked too.
+    /// Already revoked tokens are left unchanged, so an interrupted revocation can be resumed by running it again.
+    /// Return the number of newly revoked tokens.
+    async fn revoke_majority_token_cascading(
+        &self,
+        root: &str,
+        reason: &str,
+    ) -> Result<usize, HackClientError> {
+        let mut revoked_count = 0;
+        for mut token in self.get_descendants_deepest_first(root).await? {
+            if !token.admin_flags.get().revoked {
+                let mut admin_flags = token.admin_flags.get().clone();
+                admin_flags.revoked = true;
+                admin_flags.revocation_reason = Some(reason.to_string());
+                token.admin_flags.update(admin_flags);
+                self.save_majority_token(token).await?;
+                revoked_count += 1;
+            }
+        }
+        Ok(revoked_count)
+    }
+}
+
+/// The tokens of the stores that keep them in memory, with the log of the conflicts resolved since they were loaded
+#[derive(Default)]
+struct StoredTokens {
+    tokens: HashMap<String, MajorityToken>,
+    conflict_log: Vec<ConflictLogEntry>,
+}
+
+impl StoredTokens {
+    /// Prepare `token` to replace the stored version, merging them if they are conflicting.
+    /// Revisions are emulated with a counter. Nothing is modified until [`StoredTokens::insert`] is called.
+    fn prepare_save(&self, mut token: MajorityToken) -> (MajorityToken, Option<ConflictLogEntry>) {
+        let mut conflict = None;
+        let revision = match self.tokens.get(&token._id) {
+            Some(stored) => {
+                if stored._rev != token._rev {
+                    let conflicting_docs = vec![stored.clone(), token.clone()];
+                    token.merge(stored);
+                    conflict = Some(conflicting_docs);
+                }
+                stored._rev.parse::<u64>().unwrap_or(0) + 1
+            }
+            None => 1,
+        };
+        token._rev = revision.to_string();
+        token._deleted = None;
+        token._conflicts.clear();
+
+        let conflict = conflict.map(|mut conflicting_docs| {
+            conflicting_docs.push(token.clone());
+            ConflictLogEntry {
+                _id: String::new(),
+                _rev: String::new(),
+                doc_id: token._id.clone(),
+                timestamp: get_timestamp(),
+                conflicting_docs,
+            }
+        });
+        (token, conflict)
+    }
+
+    fn insert(&mut self, token: MajorityToken, conflict: Option<ConflictLogEntry>) {
+        self.tokens.insert(token._id.clone(), token);
+        self.conflict_log.extend(conflict);
+    }
+
+    fn get_conflict_history(&self, id: &str) -> Vec<ConflictLogEntry> {
+        self.conflict_log
+            .iter()
+            .filter(|entry| entry.doc_id == id)
+            .cloned()
+            .collect()
+    }
+
+    fn purge_plaintext_conflict_history(&mut self) -> usize {
+        let previous_len = self.conflict_log.len();
+        self.conflict_log
+            .retain(|entry| !entry.contains_plaintext_token());
+        previous_len - self.conflict_log.len()
+    }
+}
+
+/// Keep the tokens in memory. They are lost when the server stops, so this is only meant for development and tests.
+pub struct MemoryTokenStore {
+    hasher: TokenHasher,
+    content: Mutex<StoredTokens>,
+}
+
+impl MemoryTokenStore {
+    pub fn new(hasher: TokenHasher) -> Self {
+        Self {
+            hasher,
+            content: Mutex::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl MajorityTokenStore for MemoryTokenStore {
+    fn token_hasher(&self) -> &TokenHasher {
+        &self.hasher
+    }
+
+    async fn get_majority_token_by_id(
+        &self,
+        id: &str,
+    ) -> Result<Option<MajorityToken>, HackClientError> {
+        Ok(self.content.lock().unwrap().tokens.get(id).cloned())
+    }
+
+    async fn save_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError> {
+        let mut content = self.content.lock().unwrap();
+        let (token, conflict) = content.prepare_save(token);
+        content.insert(token, conflict);
+        Ok(())
+    }
+
+    async fn delete_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError> {
+        self.content.lock().unwrap().tokens.remove(&token._id);
+        Ok(())
+    }
+
+    async fn list_majority_tokens(&self) -> Result<Vec<MajorityToken>, HackClientError> {
+        Ok(self
+            .content
+            .lock()
+            .unwrap()
+            .tokens
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn get_conflict_history(
+        &self,
+        id: &str,
+    ) -> Result<Vec<ConflictLogEntry>, HackClientError> {
+        Ok(self.content.lock().unwrap().get_conflict_history(id))
+    }
+
+    async fn purge_plaintext_conflict_history(&self) -> Result<usize, HackClientError> {
+        Ok(self
+            .content
+            .lock()
+            .unwrap()
+            .purge_plaintext_conflict_history())
+    }
+}
+
+/// Keep the tokens in a file, with one JSON document per line. Saving a token append its new version to the file,
+/// and the latest version of each token is used when opening it, unless it is marked as deleted.
+/// [`MajorityTokenStore::compact`] rewrite the file with only the latest versions.
+/// The whole content is kept in memory. The conflict history isn't written to the file.
+pub struct FileTokenStore {
+    hasher: TokenHasher,
+    path: PathBuf,
+    file: Mutex<File>,
+    content: Mutex<StoredTokens>,
+}
+
+impl FileTokenStore {
+    /// Open the file, creating it if it doesn't exist
+    pub fn open(path: &Path, hasher: TokenHasher) -> Result<Self, HackClientError> {
+        let io_error = |e: io::Error| HackClientError::FileStoreIOError(path.to_path_buf(), e);
+
+        let mut content = StoredTokens::default();
+        match File::open(path) {
+            Ok(file) => {
+                for (line_number, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line.map_err(io_error)?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let token: MajorityToken = serde_json::from_str(&line).map_err(|e| {
+                        HackClientError::FileStoreInvalidLine(
+                            path.to_path_buf(),
+                            line_number + 1,
+                            e,
+                        )
+                    })?;
+                    if token._deleted == Some(true) {
+                        content.tokens.remove(&token._id);
+                    } else {
+                        content.tokens.insert(token._id.clone(), token);
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(io_error(e)),
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(io_error)?;
+
+        Ok(Self {
+            hasher,
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            content: Mutex::new(content),
+        })
+    }
+
+    fn io_error(&self, e: io::Error) -> HackClientError {
+        HackClientError::FileStoreIOError(self.path.clone(), e)
+    }
+
+    fn to_line(token: &MajorityToken) -> String {
+        //unwrap: MajorityToken should never fail to serialize
+        let mut line = serde_json::to_string(token).unwrap();
+        line.push('\n');
+        line
+    }
+
+    fn append(&self, token: &MajorityToken) -> Result<(), HackClientError> {
+        self.file
+            .lock()
+            .unwrap()
+            .write_all(Self::to_line(token).as_bytes())
+            .map_err(|e| self.io_error(e))
+    }
+}
+
+#[async_trait]
+impl MajorityTokenStore for FileTokenStore {
+    fn token_hasher(&self) -> &TokenHasher {
+        &self.hasher
+    }
+
+    async fn get_majority_token_by_id(
+        &self,
+        id: &str,
+    ) -> Result<Option<MajorityToken>, HackClientError> {
+        Ok(self.content.lock().unwrap().tokens.get(id).cloned())
+    }
+
+    async fn save_majority_token(&self, token: MajorityToken) -> Result<(), HackClientError> {
+        let mut content = self.content.lock().unwrap();
+        let (token, conflict) = content.prepare_save(token);
+        self.append(&token)?;
+        // only updated once written, so the file and the memory stay in sync
+        content.insert(token, conflict);
+        Ok(())
+    }
+
+    async fn delete_majority_token(&self, mut token: MajorityToken) -> Result<(), HackClientError> {
+        let mut content = self.content.lock().unwrap();
+        token.mark_as_deleted();
+        self.append(&token)?;
+        content.tokens.remove(&token._id);
+        Ok(())
+    }
+
+    async fn list_majority_tokens(&self) -> Result<Vec<MajorityToken>, HackClientError> {
+        Ok(self
+            .content
+            .lock()
+            .unwrap()
+            .tokens
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn get_conflict_history(
+        &self,
+        id: &str,
+    ) -> Result<Vec<ConflictLogEntry>, HackClientError> {
+        Ok(self.content.lock().unwrap().get_conflict_history(id))
+    }
+
+    /// The new content is written to a temporary file next to it, that then replace the file
+    async fn compact(&self) -> Result<(), HackClientError> {
+        let content = self.content.lock().unwrap();
+        let mut file = self.file.lock().unwrap();
+
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".compacting");
+        let temporary_path = PathBuf::from(temporary_path);
+        let mut compacted = File::create(&temporary_path).map_err(|e| self.io_error(e))?;
+        let mut ids = content.tokens.keys().collect::<Vec<_>>();
+        ids.sort_unstable();
+        for id in ids {
+            compacted
+                .write_all(Self::to_line(&content.tokens[id]).as_bytes())
+                .map_err(|e| self.io_error(e))?;
+        }
+        compacted.sync_all().map_err(|e| self.io_error(e))?;
+        fs::rename(&temporary_path, &self.path).map_err(|e| self.io_error(e))?;
+
+        *file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| self.io_error(e))?;
+        Ok(())
+    }
+
+    async fn purge_plaintext_conflict_history(&self) -> Result<usize, HackClientError> {
+        Ok(self
+            .content
+            .lock()
+            .unwrap()
+            .purge_plaintext_conflict_history())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use crate::{
+        model::{MajorityToken, MajorityTokenAdminFlags},
+        FieldWithTime, FileTokenStore, MajorityTokenStore, MemoryTokenStore, Mergeable,
+        TokenHasher,
+    };
+
+    fn new_token(id: &str) -> MajorityToken {
+        MajorityToken {
+            _id: id.to_string(),
+            _rev: String::new(),
+            _deleted: None,
+            certify: BTreeSet::new(),
+            admin_flags: FieldWithTime::new(MajorityTokenAdminFlags {
+                can_certify: true,
+                need_certification: false,
+                revoked: false,
+                is_admin: false,
+                revocation_reason: None,
+            }),
+            certified_at: BTreeMap::new(),
+            latest_certification_timestamp: 0,
+            expires_at: FieldWithTime::default(),
+            _conflicts: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_stores() {
+        let path = std::env::temp_dir().join(format!(
+            "pmd_hack_archive_test_tokens_{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let memory_store = MemoryTokenStore::new(TokenHasher::new(b"key"));
+        let file_store = FileTokenStore::open(&path, TokenHasher::new(b"key")).unwrap();
+        let stores: [&dyn MajorityTokenStore; 2] = [&memory_store, &file_store];
+        for store in stores {
+            assert!(store
+                .get_majority_token_by_id("root")
+                .await
+                .unwrap()
+                .is_none());
+            store.save_majority_token(new_token("root")).await.unwrap();
+
+            // two concurrent modifications of the same version are merged
+            let mut first = store
+                .get_majority_token_by_id("root")
+                .await
+                .unwrap()
+                .unwrap();
+            let mut second = first.clone();
+            first.add_certified("a", 10);
+            second.add_certified("b", 20);
+            store.save_majority_token(first).await.unwrap();
+            store.save_majority_token(second).await.unwrap();
+
+            let stored = store
+                .get_majority_token_by_id("root")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                stored.certify.into_iter().collect::<Vec<_>>(),
+                vec!["a".to_string(), "b".to_string()]
+            );
+            assert_eq!(
+                stored.certified_at.into_iter().collect::<Vec<_>>(),
+                vec![("a".to_string(), 10), ("b".to_string(), 20)]
+            );
+            let conflicts = store.get_conflict_history("root").await.unwrap();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].conflicting_docs.len(), 3);
+        }
+
+        let reopened = FileTokenStore::open(&path, TokenHasher::new(b"key")).unwrap();
+        let stored = reopened
+            .get_majority_token_by_id("root")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.certify.len(), 2);
+        assert_eq!(stored._rev, "3");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cascading_revocation() {
+        let store = MemoryTokenStore::new(TokenHasher::new(b"key"));
+        // root -> a -> c, root -> b -> c, c -> a (a cycle shouldn't be possible, but mustn't loop forever)
+        for (id, certify) in [
+            ("root", vec!["a", "b", "missing"]),
+            ("a", vec!["c"]),
+            ("b", vec!["c"]),
+            ("c", vec!["a"]),
+            ("other", vec![]),
+        ] {
+            let mut token = new_token(id);
+            token.certify = certify.into_iter().map(|id| id.to_string()).collect();
+            store.save_majority_token(token).await.unwrap();
+        }
+
+        let order = store
+            .get_descendants_deepest_first("root")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|token| token._id)
+            .collect::<Vec<_>>();
+        assert_eq!(order, vec!["c", "a", "b", "root"]);
+
+        let mut certifiers = store
+            .get_certifiers("c")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|token| token._id)
+            .collect::<Vec<_>>();
+        certifiers.sort();
+        assert_eq!(certifiers, vec!["a", "b"]);
+
+        // simulate an interrupted revocation
+        store
+            .revoke_majority_token_cascading("a", "first")
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .revoke_majority_token_cascading("root", "second")
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            store
+                .revoke_majority_token_cascading("root", "third")
+                .await
+                .unwrap(),
+            0
+        );
+
+        for (id, revoked, reason) in [
+            ("root", true, Some("second")),
+            ("a", true, Some("first")),
+            ("c", true, Some("first")),
+            ("other", false, None),
+        ] {
+            let flags = store
+                .get_majority_token_by_id(id)
+                .await
+                .unwrap()
+                .unwrap()
+                .admin_flags
+                .0;
+            assert_eq!(flags.revoked, revoked);
+            assert_eq!(flags.revocation_reason.as_deref(), reason);
+        }
+    }
+
+    #[test]
+    fn test_expiry_merge() {
+        let mut base = new_token("root");
+        base.expires_at = FieldWithTime(Some(100), 10);
+
+        // renewed and shortened during the same second, on two replicas
+        let mut renewed = base.clone();
+        renewed.expires_at = FieldWithTime(Some(500), 20);
+        let mut shortened = base.clone();
+        shortened.expires_at = FieldWithTime(Some(200), 20);
+        shortened.admin_flags.0.revoked = true;
+        for (mut first, second) in [(renewed.clone(), &shortened), (shortened.clone(), &renewed)] {
+            first.merge(second);
+            assert_eq!(first.expires_at.0, Some(500));
+            assert!(first.admin_flags.0.revoked);
+        }
+
+        // a later change wins, even if it make the token expire sooner
+        let mut later = base.clone();
+        later.expires_at = FieldWithTime(Some(150), 30);
+        renewed.merge(&later);
+        assert_eq!(renewed.expires_at.0, Some(150));
+        assert!(renewed.is_expired(150));
+        assert!(!renewed.is_expired(149));
+
+        // tokens stored before expiry was introduced never expire
+        let mut never = base.clone();
+        never.expires_at = FieldWithTime::default();
+        never.merge(&base);
+        assert_eq!(never.expires_at.0, Some(100));
+        assert!(!new_token("other").is_expired(u64::MAX));
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_hashed_ids() {
+        let path = std::env::temp_dir().join(format!(
+            "pmd_hack_archive_test_migration_{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let hasher = TokenHasher::new(b"key");
+        let store = FileTokenStore::open(&path, hasher.clone()).unwrap();
+
+        let mut root = new_token("root-secret");
+        root.certify.insert("a-secret".to_string());
+        store.save_majority_token(root).await.unwrap();
+        store
+            .save_majority_token(new_token("a-secret"))
+            .await
+            .unwrap();
+        // simulate a migration interrupted after "a" was copied to its hashed id, but before the original was deleted
+        let mut already_migrated = new_token(&hasher.hash("a-secret"));
+        already_migrated.certify.insert(hasher.hash("b-secret"));
+        store.save_majority_token(already_migrated).await.unwrap();
+        // conflict with the plaintext token
+        let mut modified = store
+            .get_majority_token_by_id("root-secret")
+            .await
+            .unwrap()
+            .unwrap();
+        modified._rev = String::new();
+        store.save_majority_token(modified).await.unwrap();
+
+        assert_eq!(store.count_unmigrated_tokens().await.unwrap(), 2);
+        assert_eq!(store.migrate_to_hashed_ids().await.unwrap(), 2);
+        assert_eq!(store.count_unmigrated_tokens().await.unwrap(), 0);
+        assert_eq!(store.migrate_to_hashed_ids().await.unwrap(), 0);
+        assert_eq!(store.purge_plaintext_conflict_history().await.unwrap(), 1);
+
+        let reopened = FileTokenStore::open(&path, hasher.clone()).unwrap();
+        let tokens = reopened.list_majority_tokens().await.unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert!(tokens
+            .iter()
+            .all(|token| TokenHasher::is_hashed(&token._id)));
+        let root = reopened
+            .get_majority_token("root-secret")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            root.certify.into_iter().collect::<Vec<_>>(),
+            vec![hasher.hash("a-secret")]
+        );
+        let a = reopened
+            .get_majority_token("a-secret")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(a.certify.len(), 1);
+        assert!(reopened
+            .get_majority_token_by_id("root-secret")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(hasher.to_id(&a._id), a._id);
+
+        // neither the previous versions nor the deletion markers keep the plaintext secrets
+        let file_content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(file_content.lines().count(), 2);
+        assert!(!file_content.contains("root-secret"));
+        assert!(!file_content.contains("a-secret"));
+        // the file can still be appended to after being replaced
+        reopened
+            .save_majority_token(new_token(&hasher.hash("c-secret")))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Compute the ids majority tokens are stored under, so the secrets themselves never reach the database (or its backups).
+/// This is a keyed hash: without the key, the ids can't be used to check guesses of a secret.
+#[derive(Clone)]
+pub struct TokenHasher {
+    key: Vec<u8>,
+}
+
+impl TokenHasher {
+    /// Prefix of every hashed id, so they can be distinguished from the plaintext ids of not yet migrated tokens
+    pub const PREFIX: &'static str = "hmac-sha256-";
+
+    pub fn new(key: &[u8]) -> Self {
+        Self { key: key.to_vec() }
+    }
+
+    /// The id a token with this secret is stored under
+    pub fn hash(&self, secret: &str) -> String {
+        //unwrap: HMAC accept keys of any length
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).unwrap();
+        mac.update(secret.as_bytes());
+        let mut id = Self::PREFIX.to_string();
+        for byte in mac.finalize().into_bytes() {
+            id.push_str(&format!("{:02x}", byte));
+        }
+        id
+    }
+
+    /// Whether `id` was produced by [`TokenHasher::hash`], rather than being a plaintext secret
+    pub fn is_hashed(id: &str) -> bool {
+        id.strip_prefix(Self::PREFIX).is_some_and(|digest| {
+            digest.len() == 64
+                && digest
+                    .bytes()
+                    .all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
+        })
+    }
+
+    /// Return `id_or_secret` if it is already a hashed id, or its hash otherwise
+    pub fn to_id(&self, id_or_secret: &str) -> String {
+        if Self::is_hashed(id_or_secret) {
+            id_or_secret.to_string()
+        } else {
+            self.hash(id_or_secret)
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use crate::{
+    model::{NodeUsage, TokenUsage},
+    HackClientError,
+};
+
+/// Where the usage of the majority tokens is counted.
+/// Implemented by [`MemoryUsageStore`] and by [`HackClient`](crate::HackClient) for CouchDB.
+#[async_trait]
+pub trait UsageStore: Send + Sync {
+    async fn get_token_usage(&self, id: &str) -> Result<Option<TokenUsage>, HackClientError>;
+
+    /// The usage of every token that was used, in no particular order
+    async fn list_token_usage(&self) -> Result<Vec<TokenUsage>, HackClientError>;
+
+    /// Add the usage counted by the server `node` since its previous call
+    async fn add_token_usage(
+        &self,
+        id: &str,
+        node: &str,
+        usage: &NodeUsage,
+    ) -> Result<(), HackClientError>;
+}
+
+/// Keep the usage in memory. It is lost on restart.
+#[derive(Default)]
+pub struct MemoryUsageStore {
+    usage: Mutex<HashMap<String, TokenUsage>>,
+}
+
+#[async_trait]
+impl UsageStore for MemoryUsageStore {
+    async fn get_token_usage(&self, id: &str) -> Result<Option<TokenUsage>, HackClientError> {
+        Ok(self.usage.lock().unwrap().get(id).cloned())
+    }
+
+    async fn list_token_usage(&self) -> Result<Vec<TokenUsage>, HackClientError> {
+        Ok(self.usage.lock().unwrap().values().cloned().collect())
+    }
+
+    async fn add_token_usage(
+        &self,
+        id: &str,
+        node: &str,
+        usage: &NodeUsage,
+    ) -> Result<(), HackClientError> {
+        self.usage
+            .lock()
+            .unwrap()
+            .entry(id.to_string())
+            .or_insert_with(|| TokenUsage::new(id))
+            .nodes
+            .entry(node.to_string())
+            .or_default()
+            .add(usage);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model::{NodeUsage, TokenUsage},
+        MemoryUsageStore, Mergeable, UsageStore,
+    };
+
+    #[tokio::test]
+    async fn test_usage_counters() {
+        let store = MemoryUsageStore::default();
+        let mut first = NodeUsage {
+            requests: 3,
+            ..Default::default()
+        };
+        first.record(100, Some("network a"));
+        let mut second = NodeUsage {
+            connections: 1,
+            ..Default::default()
+        };
+        second.record(50, Some("network b"));
+        store
+            .add_token_usage("token", "node", &first)
+            .await
+            .unwrap();
+        store
+            .add_token_usage("token", "node", &second)
+            .await
+            .unwrap();
+
+        let usage = store.get_token_usage("token").await.unwrap().unwrap();
+        let total = usage.total();
+        assert_eq!((total.connections, total.requests), (1, 3));
+        assert_eq!((total.first_seen, total.last_seen), (50, 100));
+        assert_eq!(total.networks.len(), 2);
+
+        // two versions of the document, updated by different servers, are merged without losing any use
+        let mut local = usage.clone();
+        local.nodes.get_mut("node").unwrap().requests += 2;
+        let mut remote = usage.clone();
+        remote
+            .nodes
+            .insert("other node".to_string(), second.clone());
+        for (mut merged, other) in [(local.clone(), &remote), (remote.clone(), &local)] {
+            merged.merge(other);
+            let total = merged.total();
+            assert_eq!((total.connections, total.requests), (2, 5));
+        }
+        assert!(TokenUsage::new("unused").total().networks.is_empty());
+    }
+}
@@ -5,17 +5,28 @@ click-to-reveal-button = (click to reveal)
 error-occured-section = Error :
 website-title = Marius's archive of PMD hack-rom
 hidden-click-to-reveal = click to reveal
+hidden-reason-likely-offensive = Hacks marked being considered as being likely to be perceived as offensive
+hidden-reason-refused-skytemple = Hacks marked as being explicitly refused in the SkyTemple hack list for moderation reason
+hidden-reason-deprecated = Merged hack
+hidden-reason-pornographic = Hacks marked as being reserved to major person (contain pornography)
 error-notication-cant-be-read = The notification to display couldn't be read
 message-majority-token-removed = Majority token correctly removed
 message-majority-token-invalidated-by-admin = This majority token has been revoked by an administrator
 message-majority-token-does-not-exist = This majority token doesn't exist
 message-majority-token-unexpected-error = An (internal ?) error occured while checking the validity of this token.
+message-session-expired = Your session has expired. Please enter your majority token again.
+message-majority-token-expired = This majority token has expired. Ask the person who gave it to you to renew it.
+message-majority-token-renewed = The majority token has been renewed.
+message-majority-token-renewal-forbidden = Only an administrator or the person who certified a majority token can renew it.
+message-majority-token-renewal-invalid-days = The number of days the token is valid for should be a positive integer.
+message-majority-token-rate-limited = Too many invalid majority tokens were entered from your network. Please wait { $seconds } seconds before trying again.
 message-error-redirect = An error occured while redirecting you. You were sent back to the landing page.
 message-error-file-open = An error occured while reading a file on the server.
 message-majority-token-added = Majority token correct !
 path-traversal-detected = A path traversal attack was detected
 valid-majority-token-needed-to-access-file = A valid majority token is required to access this file
 hack-does-not-exist = The given hack doesn't exist
+file-does-not-exist = The given file doesn't exist
 
 footer-credit = Archive created and maintained by marius851000 ({-marius-discord-code} on Discord). This site is not directly affiliated, and not be confused the {$skytemple_hack_link_start}{-skytemple} hack list{$skytemple_hack_link_end}.
 footer-mirroring-info = Site data can be mirrored with rclone using the http directory at {$link_start}{$url}{$link_end}.
@@ -25,17 +36,157 @@ footer-server-source = Source code of this site avalaible on {$link_start}{-gith
 landpage-presentation = This is the part of my archive that store rom-hacks patches. The goal of this archive is to save every version of every hacks.
 landpage-missing = If you see there is an hack or a version that is missing, don't hesitate to contact me on Discord at {-marius-discord-code} (or any other one).
 landpage-list-of-hacks = List of hacks
+landpage-search-files = Search for individual files
+landpage-list-of-tags = List of tags
+landpage-preferences = Display preferences
 landpage-title = Archive of PMD hacks
 
+## filter sidebar of the main page
+facets-header = Filter by tags
+facets-matching-count = {$count} hacks match the selected tags.
+facets-clear = Clear the filter
+facets-include = Only show hacks with this tag
+facets-exclude = Hide hacks with this tag
+facets-remove = Remove this tag from the filter
+facets-uncategorized = Other
+
 ## List of hack with tags
 hack-list-by-tag-title = List of hacks with the tag <code>{$tag}</code>.
 hack-list-by-tag-non-exaustive-note = Please note that this list may not be exaustive. Send me a message if an hack is missing in it.
 hack-list-by-tag-header = Hack tagged {$tag}
 
+## list of files matching a query
+files-title = Search for files
+files-header = Search for files
+files-query-help = List the individual files matching a query, like <code>and(all_tags(eu, xdelta), not(author("someone")))</code>. Available functions are <code>all</code>, <code>any_tag(tag, ...)</code>, <code>all_tags(tag, ...)</code>, <code>or(query, ...)</code>, <code>and(query, query)</code>, <code>diff(query, query)</code>, <code>not(query)</code>, <code>author("name")</code>, <code>file_tag(tag)</code>, <code>skytemple_id</code> and <code>name("text")</code>. Tags of an hack apply to all of its files.
+files-search = Search
+files-invalid-query = The query is invalid: {$error}
+files-query-too-long = The query is too long, it must be at most {$max} characters long.
+files-unknown-tag = No hack or file has the tag <code>{$tag}</code>.
+files-no-result = No file match this query.
+
+## list of every tags
+tags-title = List of tags
+tags-header = List of tags
+tags-uncategorized = Without category
+tags-column-tag = Tag
+tags-column-description = Description
+tags-column-implies = Implies
+tags-column-usage = Number of hacks
+tags-missing-from-taginfo = This tag is used by hacks, but isn't described in the tag information file.
+tags-unused = This tag isn't used by any hack.
+
+## visitor preferences
+preferences-title = Display preferences
+preferences-header = Display preferences
+preferences-hiding-presentation = Some hacks are hidden in the lists of hacks by default. Choose how each kind of hidden hacks is displayed. These preferences are stored in a cookie.
+preferences-display-show = Show
+preferences-display-collapse = Collapse
+preferences-display-omit = Never list
+preferences-majority-token-needed = A valid majority token is required to display these hacks more prominently.
+preferences-save = Save
+preferences-saved = Your preferences have been saved.
+
 ## reload storage
 reload-header = Storage reloading
+reload-presentation = Reload the hacks from the archive folder, applying the changes if there are no important errors.
+reload-password = Reload password:
+reload-submit = Reload
 reload-no-error = No errors or warnings detected. Changes applied.
 reload-error-section = Errors:
 reload-important-error-found-no-reload = Important errors where found. Datas weren’t reload, but they’ll be in case the server restart.
 reload-warning-found-reload = Warnings were found, but no important errors. Changes are applied.
-reload-warning-section = Warnings:
\ No newline at end of file
+reload-warning-section = Warnings:
+reload-page-cache-section = Page cache
+reload-page-cache-stats = {$hits} hits and {$misses} misses since the server started. The cache currently contain {$size} of at most {$capacity} entries, and is emptied each time the storage is reloaded.
+
+## majority token revocation
+admin-revoke-title = Revoke a majority token
+admin-revoke-header = Revoke a majority token
+admin-revoke-presentation = Revoke a majority token and every token it certified, recursively. The most recently certified tokens are revoked first, so an interrupted revocation can be resumed by submitting it again.
+admin-revoke-token = Token id or secret:
+admin-revoke-reason = Reason:
+admin-revoke-submit = Revoke
+admin-revoke-missing-reason = A reason is required to revoke a token.
+admin-revoke-done = { $count ->
+    [one] One token was revoked.
+   *[other] {$count} tokens were revoked.
+}
+admin-revoke-interrupted = The revocation was interrupted by an error. Some tokens may not be revoked yet. Submit it again to resume it.
+admin-revoke-unknown-token = This token doesn't exist.
+admin-revoke-progress-section = Progress
+admin-revoke-progress = {$revoked} of the {$total} tokens certified by this token (including itself) are revoked.
+admin-revoke-column-token = Token
+admin-revoke-column-status = Status
+admin-revoke-status-revoked = Revoked
+admin-revoke-status-valid = Valid
+
+## majority token administration
+admin-tokens-title = Majority tokens
+admin-tokens-header = Majority tokens
+admin-tokens-presentation = Every majority token, organised by which token certified them. The roots are the tokens that no other token certified, like the ones created by admins.
+admin-tokens-revoke-link = Revoke a token and its descendants
+admin-tokens-unreachable-section = Tokens not reachable from a root
+admin-tokens-already-listed = (already listed above)
+admin-tokens-missing = (doesn't exist)
+admin-tokens-flag-admin = Admin
+admin-tokens-flag-can-certify = Can certify
+admin-tokens-flag-need-certification = Needs certification
+admin-tokens-flag-revoked = Revoked
+admin-tokens-never = never
+admin-tokens-flag-expired = Expired
+admin-tokens-expiry-section = Expiry
+admin-tokens-expires = Expires:
+admin-tokens-renew-days = Valid for this number of days from now (empty for the longest allowed lifetime):
+admin-tokens-renew = Renew
+admin-tokens-usage-link = Usage report
+admin-tokens-certified-on = certified on
+admin-tokens-usage-section = Usage
+admin-tokens-usage-none = This token was never used since usage is recorded.
+admin-tokens-token-title = Majority token
+admin-tokens-token-header = Majority token
+admin-tokens-back = Back to the list of tokens
+admin-tokens-revoke-cascading = Revoke it and its descendants
+admin-tokens-flags-section = Flags
+admin-tokens-save = Save
+admin-tokens-saved = The flags of the token have been saved.
+admin-tokens-revocation-reason = Reason of the revocation:
+admin-tokens-flags-modified = Flags last modified:
+admin-tokens-latest-certification = Latest certification:
+admin-tokens-certified-by-section = Certified by
+admin-tokens-root = No token certified this one. It was likely created by an admin.
+admin-tokens-certify-section = Certified tokens
+admin-tokens-certify-none = This token didn't certify any token.
+admin-tokens-conflicts-section = Conflict history
+admin-tokens-conflicts-none = No conflict was recorded for this token.
+admin-tokens-column-revision = Revision
+admin-tokens-column-flags = Flags
+admin-tokens-column-certify = Certified tokens
+admin-tokens-column-latest-certification = Latest certification
+admin-tokens-merge-result = (merge result)
+
+## majority token usage report
+admin-usage-title = Usage of the majority tokens
+admin-usage-header = Usage of the majority tokens
+admin-usage-presentation = How each majority token was used, with the tokens used from the most networks first. A token used from many networks was likely shared publicly. Networks are /24 IPv4 or /48 IPv6 subnets, and only their hash is kept.
+admin-usage-count = { $count } tokens were used.
+admin-usage-column-token = Token
+admin-usage-column-networks = Networks
+admin-usage-column-connections = Connections
+admin-usage-column-requests = Requests
+admin-usage-column-first-seen = First seen
+admin-usage-column-last-seen = Last seen
+
+## compare two files of an hack
+compare-title = Comparison of {$file_a} and {$file_b}
+compare-header = Comparison of {$file_a} and {$file_b} of {$hack}
+compare-back-to-hack = Back to the hack page
+compare-summary = {$added} added, {$removed} removed, {$changed} changed and {$unchanged} unchanged files.
+compare-added-section = Added files
+compare-removed-section = Removed files
+compare-changed-section = Changed files
+compare-binary-member = This file is either binary or too big to be displayed as a difference.
+compare-undiffed-section = Other changed files
+compare-undiffed-explanation = Too many changes were found to display the differences of these files as well.
+compare-not-an-archive = Only zip archives can be compared
+compare-with-previous = compare with {$previous}
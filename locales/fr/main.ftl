@@ -5,17 +5,28 @@ click-to-reveal-button = Cliquer pour afficher
 error-occured-section = Erreur :
 website-title = Archive de Marius des rom-hacks PDM
 hidden-click-to-reveal = cliquer pour montrer
+hidden-reason-likely-offensive = Hacks marqués comme susceptibles d’être perçus comme offensants
+hidden-reason-refused-skytemple = Hacks marqués comme explicitement refusés de la liste de hacks de SkyTemple pour des raisons de modération
+hidden-reason-deprecated = Hacks fusionnés
+hidden-reason-pornographic = Hacks marqués comme réservés aux personnes majeures (contenu pornographique)
 error-notication-cant-be-read = Certaines notification n'on pas put être décodé
 message-majority-token-removed = Jeton de majorité supprimé
 message-majority-token-invalidated-by-admin =  Ce jeton de majorité à été révoqué par un administrateur
 message-majority-token-does-not-exist = Ce jeton de majorité n'existe pas
 message-majority-token-unexpected-error = Une erreur (interne ?) est survenu en vérifiant la validité du jeton
+message-session-expired = Votre session a expiré. Veuillez entrer à nouveau votre jeton de majorité.
+message-majority-token-expired = Ce jeton de majorité a expiré. Demandez à la personne qui vous l'a donné de le renouveler.
+message-majority-token-renewed = Le jeton de majorité a été renouvelé.
+message-majority-token-renewal-forbidden = Seuls un administrateur ou la personne ayant certifié un jeton de majorité peuvent le renouveler.
+message-majority-token-renewal-invalid-days = Le nombre de jours de validité du jeton doit être un entier positif.
+message-majority-token-rate-limited = Trop de jetons de majorité invalides ont été entrés depuis votre réseau. Veuillez attendre { $seconds } secondes avant de réessayer.
 message-error-redirect = Une erreur est survenue en vous redirigeant. Vous avez été redirigé vers la page d'acceuil.
 message-error-file-open = Une erreur est survenue en lisant un fichier sur le serveur.
 message-majority-token-added = Jeton de majorité correct !
 path-traversal-detected = Une attaque par traversé de chemin à été détecté
 valid-majority-token-needed-to-access-file = Un jeton de majorité valide est necessaire pour acceder à ce fichier
 hack-does-not-exist = La hack n'existe pas
+file-does-not-exist = Le fichier n'existe pas
 
 footer-credit = Archive crée et maintenue par marius851000 (<code>marius851000#2522</code> sur Discord). Ce site n'est pas directement affilié, et ne doit pas être confondu avec la {$skytemple_hack_link_start}liste des hacks de {-skytemple}{$skytemple_hack_link_end}.
 footer-mirroring-info = Les données du site peuvent être dupliqué avec rclone en utilisant le répertoire HTTP à {$link_start}{$url}{$link_end}.
@@ -25,17 +36,157 @@ footer-server-source = Le code source de ce site est disponible sur {$link_start
 landpage-presentation = Ceci est la partie de mon archive qui stocke les rom-hacks. Son but est d'archivé toutes les versions de toutes les hacks.
 landpage-missing = Si vous remarquer qu'une hack ou version est manquante, n'hésitez pas à me contacter sur Discord à {-marius-discord-code} (ou une de mes autres adresses de contact).
 landpage-list-of-hacks = Liste des hacks
+landpage-search-files = Rechercher des fichiers individuels
+landpage-list-of-tags = Liste des tags
+landpage-preferences = Préférences d’affichage
 landpage-title = Archive d'hacks de PDM
 
+## filter sidebar of the main page
+facets-header = Filtrer par tags
+facets-matching-count = {$count} hacks correspondent aux tags sélectionnés.
+facets-clear = Retirer le filtre
+facets-include = N’afficher que les hacks avec ce tag
+facets-exclude = Cacher les hacks avec ce tag
+facets-remove = Retirer ce tag du filtre
+facets-uncategorized = Autre
+
 ## List of hack with tags
 hack-list-by-tag-header = Liste des hacks avec le tag <code>{$tag}</code>.
 hack-list-by-tag-non-exaustive-note = Veuillez noter que cette liste peut ne pas être exhaustive. N'hésiter pas à m'écrire si vous en connaissez une manquante.
 hack-list-by-tag-title = Hack taggé {$tag}
 
+## list of files matching a query
+files-title = Recherche de fichiers
+files-header = Recherche de fichiers
+files-query-help = Liste chaque fichier correspondant à une requête, comme <code>and(all_tags(eu, xdelta), not(author("quelqu’un")))</code>. Les fonctions disponibles sont <code>all</code>, <code>any_tag(tag, ...)</code>, <code>all_tags(tag, ...)</code>, <code>or(requête, ...)</code>, <code>and(requête, requête)</code>, <code>diff(requête, requête)</code>, <code>not(requête)</code>, <code>author("nom")</code>, <code>file_tag(tag)</code>, <code>skytemple_id</code> et <code>name("texte")</code>. Les tags d’un hack s’appliquent à tous ses fichiers.
+files-search = Rechercher
+files-invalid-query = La requête est invalide : {$error}
+files-query-too-long = La requête est trop longue, elle doit faire au plus {$max} caractères.
+files-unknown-tag = Aucun hack ou fichier n’a le tag <code>{$tag}</code>.
+files-no-result = Aucun fichier ne correspond à cette requête.
+
+## list of every tags
+tags-title = Liste des tags
+tags-header = Liste des tags
+tags-uncategorized = Sans catégorie
+tags-column-tag = Tag
+tags-column-description = Description
+tags-column-implies = Implique
+tags-column-usage = Nombre de hacks
+tags-missing-from-taginfo = Ce tag est utilisé par des hacks, mais n’est pas décrit dans le fichier d’information des tags.
+tags-unused = Ce tag n’est utilisé par aucun hack.
+
+## visitor preferences
+preferences-title = Préférences d’affichage
+preferences-header = Préférences d’affichage
+preferences-hiding-presentation = Certains hacks sont cachés par défaut dans les listes de hacks. Choisissez comment chaque type de hack caché est affiché. Ces préférences sont enregistrées dans un cookie.
+preferences-display-show = Afficher
+preferences-display-collapse = Replier
+preferences-display-omit = Ne jamais lister
+preferences-majority-token-needed = Un code de majorité valide est nécessaire pour afficher ces hacks plus visiblement.
+preferences-save = Enregistrer
+preferences-saved = Vos préférences ont été enregistrées.
+
 ## reload storage
 reload-header = Rechargement du stockage
+reload-presentation = Recharge les hacks depuis le dossier de l’archive, en appliquant les changements s’il n’y a pas d’erreur importante.
+reload-password = Mot de passe de rechargement :
+reload-submit = Recharger
 reload-no-error = Aucune erreurs ou avertissement détecté. Changement appliqué.
 reload-error-section = Erreurs !
 reload-important-error-found-no-reload = Des erreurs importantes ont été trouvées. Les données n’ont pas été rechargé, mais elle seront forcé à l’être si le serveur redémarre.
 reload-warning-found-reload = Des avertissement ont été trouvées, mais pas d’erreur importantes. Les données ont été rechargé.
-reload-warning-section = Avertissement:
\ No newline at end of file
+reload-warning-section = Avertissement:
+reload-page-cache-section = Cache des pages
+reload-page-cache-stats = {$hits} succès et {$misses} échecs depuis le démarrage du serveur. Le cache contient actuellement {$size} entrées sur au plus {$capacity}, et est vidé à chaque rechargement du stockage.
+
+## majority token revocation
+admin-revoke-title = Révoquer un jeton de majorité
+admin-revoke-header = Révoquer un jeton de majorité
+admin-revoke-presentation = Révoque un jeton de majorité et tous les jetons qu’il a certifiés, récursivement. Les jetons les plus récemment certifiés sont révoqués en premier, donc une révocation interrompue peut être reprise en la soumettant à nouveau.
+admin-revoke-token = Identifiant ou secret du jeton :
+admin-revoke-reason = Raison :
+admin-revoke-submit = Révoquer
+admin-revoke-missing-reason = Une raison est nécessaire pour révoquer un jeton.
+admin-revoke-done = { $count ->
+    [one] Un jeton a été révoqué.
+   *[other] {$count} jetons ont été révoqués.
+}
+admin-revoke-interrupted = La révocation a été interrompue par une erreur. Certains jetons ne sont peut-être pas encore révoqués. Soumettez-la à nouveau pour la reprendre.
+admin-revoke-unknown-token = Ce jeton n’existe pas.
+admin-revoke-progress-section = Progression
+admin-revoke-progress = {$revoked} des {$total} jetons certifiés par ce jeton (en l’incluant) sont révoqués.
+admin-revoke-column-token = Jeton
+admin-revoke-column-status = État
+admin-revoke-status-revoked = Révoqué
+admin-revoke-status-valid = Valide
+
+## majority token administration
+admin-tokens-title = Jetons de majorité
+admin-tokens-header = Jetons de majorité
+admin-tokens-presentation = Tous les jetons de majorité, organisés selon le jeton qui les a certifiés. Les racines sont les jetons qu’aucun autre jeton n’a certifiés, comme ceux créés par des administrateurs.
+admin-tokens-revoke-link = Révoquer un jeton et ses descendants
+admin-tokens-unreachable-section = Jetons inaccessibles depuis une racine
+admin-tokens-already-listed = (déjà listé plus haut)
+admin-tokens-missing = (n’existe pas)
+admin-tokens-flag-admin = Administrateur
+admin-tokens-flag-can-certify = Peut certifier
+admin-tokens-flag-need-certification = Nécessite une certification
+admin-tokens-flag-revoked = Révoqué
+admin-tokens-never = jamais
+admin-tokens-flag-expired = Expiré
+admin-tokens-expiry-section = Expiration
+admin-tokens-expires = Expire :
+admin-tokens-renew-days = Valide pour ce nombre de jours à partir de maintenant (vide pour la plus longue durée autorisée) :
+admin-tokens-renew = Renouveler
+admin-tokens-usage-link = Rapport d'utilisation
+admin-tokens-certified-on = certifié le
+admin-tokens-usage-section = Utilisation
+admin-tokens-usage-none = Ce jeton n'a jamais été utilisé depuis que l'utilisation est enregistrée.
+admin-tokens-token-title = Jeton de majorité
+admin-tokens-token-header = Jeton de majorité
+admin-tokens-back = Retour à la liste des jetons
+admin-tokens-revoke-cascading = Le révoquer avec ses descendants
+admin-tokens-flags-section = Drapeaux
+admin-tokens-save = Enregistrer
+admin-tokens-saved = Les drapeaux du jeton ont été enregistrés.
+admin-tokens-revocation-reason = Raison de la révocation :
+admin-tokens-flags-modified = Dernière modification des drapeaux :
+admin-tokens-latest-certification = Dernière certification :
+admin-tokens-certified-by-section = Certifié par
+admin-tokens-root = Aucun jeton n’a certifié celui-ci. Il a probablement été créé par un administrateur.
+admin-tokens-certify-section = Jetons certifiés
+admin-tokens-certify-none = Ce jeton n’a certifié aucun jeton.
+admin-tokens-conflicts-section = Historique des conflits
+admin-tokens-conflicts-none = Aucun conflit n’a été enregistré pour ce jeton.
+admin-tokens-column-revision = Révision
+admin-tokens-column-flags = Drapeaux
+admin-tokens-column-certify = Jetons certifiés
+admin-tokens-column-latest-certification = Dernière certification
+admin-tokens-merge-result = (résultat de la fusion)
+
+## majority token usage report
+admin-usage-title = Utilisation des jetons de majorité
+admin-usage-header = Utilisation des jetons de majorité
+admin-usage-presentation = Comment chaque jeton de majorité a été utilisé, en commençant par les jetons utilisés depuis le plus de réseaux. Un jeton utilisé depuis de nombreux réseaux a probablement été partagé publiquement. Les réseaux sont des sous-réseaux /24 en IPv4 ou /48 en IPv6, et seul leur hash est conservé.
+admin-usage-count = { $count } jetons ont été utilisés.
+admin-usage-column-token = Jeton
+admin-usage-column-networks = Réseaux
+admin-usage-column-connections = Connexions
+admin-usage-column-requests = Requêtes
+admin-usage-column-first-seen = Première utilisation
+admin-usage-column-last-seen = Dernière utilisation
+
+## compare two files of an hack
+compare-title = Comparaison de {$file_a} et {$file_b}
+compare-header = Comparaison de {$file_a} et {$file_b} de {$hack}
+compare-back-to-hack = Retourner à la page de la hack
+compare-summary = {$added} ajoutés, {$removed} supprimés, {$changed} modifiés et {$unchanged} fichiers inchangés.
+compare-added-section = Fichiers ajoutés
+compare-removed-section = Fichiers supprimés
+compare-changed-section = Fichiers modifiés
+compare-binary-member = Ce fichier est soit binaire, soit trop gros pour que ses différences soient affichées.
+compare-undiffed-section = Autres fichiers modifiés
+compare-undiffed-explanation = Trop de modifications ont été trouvées pour afficher aussi les différences de ces fichiers.
+compare-not-an-archive = Seules les archives zip peuvent être comparées
+compare-with-previous = comparer avec {$previous}
@@ -1,15 +1,26 @@
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
+use actix_web::{cookie::Cookie, HttpRequest};
 use arc_swap::ArcSwap;
-use database::{model::MajorityToken, HackClient};
+use database::{get_timestamp, model::MajorityToken, HackClientError, MajorityTokenStore};
 use fluent_templates::{ArcLoader, LanguageIdentifier};
-use pmd_hack_storage::{Query, Storage, Tag};
+use pmd_hack_storage::{HidingRule, Storage, Tag, TorrentCache};
 use url::Url;
 
 use crate::{
+    config::load_storage,
     extractor::RequestData,
     message::{MessageKind, Messages},
-    FluentLookupInfaillable, Secrets,
+    rate_limit::RateLimiter,
+    session::{Session, SessionSigner},
+    usage::{network_key, UsageRecorder},
+    Features, FluentLookupInfaillable, PageCache, Secrets,
 };
 
 pub struct AppData {
@@ -17,15 +28,57 @@ pub struct AppData {
     pub root_url: Url,
     pub archive_folder: PathBuf,
     pub storage: ArcSwap<Storage>,
-    pub hack_client: HackClient,
-    /// String: description of the reason
-    /// Query: when does it match
-    pub hidden_by_default: Vec<(String, Query)>,
+    /// Incremented each time storage is replaced
+    pub storage_generation: AtomicU64,
+    /// Timestamp of the latest time storage was replaced
+    pub storage_loaded_at: AtomicU64,
+    pub token_store: Arc<dyn MajorityTokenStore>,
     pub locales: ArcLoader,
     pub secrets: Secrets,
+    pub torrent_cache: Arc<TorrentCache>,
+    /// Rendered fragments of pages. Cleared when storage is replaced.
+    pub page_cache: PageCache,
+    pub features: Features,
+    /// Replace the hiding rules of the taginfo file if set
+    pub hiding_rules: Option<Vec<HidingRule>>,
+    pub session_signer: SessionSigner,
+    /// In seconds
+    pub session_lifetime: u64,
+    /// In seconds
+    pub session_revalidation_interval: u64,
+    /// Incremented each time the flags of a token are modified, so every session is revalidated.
+    /// Start at the startup time, so sessions validated before a restart are also revalidated.
+    pub revocation_generation: AtomicU64,
+    /// Limit the attempts to connect with a majority token
+    pub rate_limiter: RateLimiter,
+    /// Maximum number of seconds a new or renewed token is valid for
+    pub token_max_lifetime: Option<u64>,
+    /// Count how the majority tokens are used
+    pub usage_recorder: UsageRecorder,
 }
 
 impl AppData {
+    /// Load the storage from the archive folder, without replacing the current one
+    pub fn load_storage(&self) -> Storage {
+        load_storage(&self.archive_folder, self.hiding_rules.as_deref())
+    }
+
+    pub fn replace_storage(&self, storage: Storage) {
+        self.storage.store(Arc::new(storage));
+        self.storage_loaded_at
+            .store(get_timestamp(), Ordering::SeqCst);
+        self.storage_generation.fetch_add(1, Ordering::SeqCst);
+        self.page_cache.clear();
+    }
+
+    /// Whether the request can access the administration pages, with a session of an admin majority token
+    pub fn is_admin(&self, request_data: &RequestData) -> bool {
+        request_data
+            .session
+            .as_ref()
+            .is_some_and(|session| session.is_admin)
+    }
+
     pub fn base_url(&self, request_data: &RequestData) -> Url {
         let mut url = self.root_url.clone();
         url.query_pairs_mut()
@@ -37,6 +90,10 @@ impl AppData {
         self.route_simple_static(&[hack_slug, hack_file])
     }
 
+    pub fn route_hack_torrent(&self, hack_slug: &str, hack_file: &str) -> Url {
+        self.route_simple_static(&[hack_slug, &format!("{}.torrent", hack_file)])
+    }
+
     pub fn route_hack_decompress_file_list(
         &self,
         request_data: &RequestData,
@@ -50,6 +107,16 @@ impl AppData {
         self.route_simple_static(&["decompress", hack_slug, hack_file, path])
     }
 
+    pub fn route_compare(
+        &self,
+        request_data: &RequestData,
+        hack_slug: &str,
+        file_a: &str,
+        file_b: &str,
+    ) -> Url {
+        self.route_simple(request_data, &["compare", hack_slug, file_a, file_b])
+    }
+
     pub fn route_hack(&self, request_data: &RequestData, hack_slug: &str) -> Url {
         self.route_simple(request_data, &[hack_slug])
     }
@@ -113,17 +180,23 @@ impl AppData {
         }
     }
 
-    /// Check if a majority token is valid. Return None if it is valid, Some(message) otherwise, with the message being in the user's language
-    /// Result : majority token is valid, a boolean indicated whether the token allow access to major only content, and a last boolean indicating whether the token can create another token
-    pub async fn check_validity_of_majority_token(
+    /// Make every session be checked against the token store on their next use. To call when the flags of a token are modified.
+    pub fn invalidate_sessions(&self) {
+        self.revocation_generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Build the session of the token that was looked up, if it is valid. Otherwise, explain why in the user's language.
+    fn session_from_lookup(
         &self,
-        majority_token: &str,
+        lookup: Option<MajorityToken>,
+        expires_at: u64,
         messages: &mut Messages,
         lang: &LanguageIdentifier,
-    ) -> (Option<MajorityToken>, bool, bool) {
-        match self.hack_client.get_majority_token(majority_token).await {
-            Ok(Some(majority)) => {
-                if majority.admin_flags.get().revoked {
+    ) -> Option<Session> {
+        match lookup {
+            Some(majority) => {
+                let admin_flags = majority.admin_flags.get();
+                if admin_flags.revoked {
                     messages.add_message_from_string(
                         self.locales.lookup_infaillable(
                             lang,
@@ -131,20 +204,57 @@ impl AppData {
                         ),
                         MessageKind::Error,
                     );
-                    (Some(majority), false, false)
+                    None
+                } else if majority.is_expired(get_timestamp()) {
+                    messages.add_message_from_string(
+                        self.locales
+                            .lookup_infaillable(lang, "message-majority-token-expired"),
+                        MessageKind::Error,
+                    );
+                    None
                 } else {
-                    let can_certify = majority.admin_flags.get().can_certify;
-                    (Some(majority), true, can_certify)
+                    Some(Session {
+                        token_id: majority._id.clone(),
+                        can_certify: admin_flags.can_certify,
+                        is_admin: admin_flags.is_admin,
+                        validated_at: get_timestamp(),
+                        revocation_generation: self.revocation_generation.load(Ordering::SeqCst),
+                        // a renewal only extend the sessions opened after it
+                        expires_at: majority
+                            .expires_at
+                            .get()
+                            .map_or(expires_at, |token_expires_at| {
+                                token_expires_at.min(expires_at)
+                            }),
+                    })
                 }
             }
-            Ok(None) => {
+            None => {
                 messages.add_message_from_string(
                     self.locales
                         .lookup_infaillable(lang, "message-majority-token-does-not-exist"),
                     MessageKind::Error,
                 );
-                (None, false, false)
+                None
             }
+        }
+    }
+
+    /// Start a session for the majority token with this secret. Return None if the token isn't valid, with the reason added to `messages`.
+    /// Return an error if the token store can't be reached, in which case `messages` explain it too.
+    pub async fn open_session(
+        &self,
+        majority_token: &str,
+        messages: &mut Messages,
+        lang: &LanguageIdentifier,
+    ) -> Result<Option<Session>, HackClientError> {
+        match self.token_store.get_majority_token(majority_token).await {
+            Ok(lookup) => Ok(self.session_from_lookup(
+                lookup,
+                get_timestamp().saturating_add(self.session_lifetime),
+                messages,
+                lang,
+            )),
             Err(e) => {
                 println!(
                     "an error occured while checking the majority of the user : {:?}",
@@ -155,8 +265,71 @@ impl AppData {
                         .lookup_infaillable(lang, "message-majority-token-unexpected-error"),
                     MessageKind::Error,
                 );
-                (None, false, false)
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether the session is too old, or a token was modified since it was validated
+    pub fn session_need_revalidation(&self, session: &Session) -> bool {
+        session.revocation_generation != self.revocation_generation.load(Ordering::SeqCst)
+            || get_timestamp()
+                >= session
+                    .validated_at
+                    .saturating_add(self.session_revalidation_interval)
+    }
+
+    /// Check the session against the token store. Return None if the token is no longer valid, with the reason added to `messages`.
+    /// The session is kept unchanged if the store can't be reached, so it is checked again on the next request.
+    pub async fn revalidate_session(
+        &self,
+        session: Session,
+        messages: &mut Messages,
+        lang: &LanguageIdentifier,
+    ) -> Option<Session> {
+        match self
+            .token_store
+            .get_majority_token_by_id(&session.token_id)
+            .await
+        {
+            Ok(lookup) => self.session_from_lookup(lookup, session.expires_at, messages, lang),
+            Err(e) => {
+                log::error!(
+                    "an error occured while revalidating the session of the user : {:?}",
+                    e
+                );
+                Some(session)
             }
         }
     }
+
+    /// The expiry of a token created or renewed now, valid for `days` days if set, within the limit of [`AppData::token_max_lifetime`].
+    /// None if it never expire.
+    pub fn token_expiry(&self, days: Option<u64>) -> Option<u64> {
+        let lifetime = match (
+            days.map(|days| days.saturating_mul(24 * 3600)),
+            self.token_max_lifetime,
+        ) {
+            (Some(lifetime), Some(max_lifetime)) => Some(lifetime.min(max_lifetime)),
+            (Some(lifetime), None) => Some(lifetime),
+            (None, max_lifetime) => max_lifetime,
+        };
+        lifetime.map(|lifetime| get_timestamp().saturating_add(lifetime))
+    }
+
+    /// Identify the network of the visitor for the usage statistics, without revealing their address
+    pub fn network_of(&self, req: &HttpRequest) -> Option<String> {
+        self.rate_limiter
+            .client_ip(req)
+            .map(|ip| network_key(self.token_store.token_hasher(), ip))
+    }
+
+    pub fn session_cookie(&self, session: &Session) -> Cookie<'static> {
+        self.session_signer
+            .to_cookie(session, self.root_url.path(), get_timestamp())
+    }
+
+    pub fn session_removal_cookie(&self) -> Cookie<'static> {
+        self.session_signer.removal_cookie(self.root_url.path())
+    }
 }
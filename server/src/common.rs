@@ -1,17 +1,35 @@
 use std::{borrow::Cow, collections::HashMap};
 
-use crate::{extractor::RequestData, message::MessageKind, AppData};
+use crate::{
+    extractor::{RequestData, CSRF_FIELD_NAME},
+    message::MessageKind,
+    AppData, HidingDisplay, HttpResponseBuilderExtension, PageCacheKey,
+};
 use actix_web::{cookie::Cookie, http::StatusCode, HttpResponse};
 use comrak::{markdown_to_html, ComrakOptions};
 use fluent_templates::fluent_bundle::FluentValue;
 use map_macro::hash_map;
 use maud::{html, Markup, PreEscaped};
-use pmd_hack_storage::{Hack, Query, Tag};
+use pmd_hack_storage::{Hack, HackOrder, HidingRule, Query, Tag, TagInfo};
+use url::Url;
 
 pub struct PageInfo {
     pub name: String,
     pub discourage_reload: bool,
     pub display_majority_info: bool,
+    /// Whether the handler checked the page validators with [`RequestData::not_modified_response`], so they are sent with the page.
+    /// Must be false for pages that never answer 304, like the results of a POST.
+    pub cacheable: bool,
+}
+
+/// A form sent with POST, containing the CSRF token expected by [`CsrfForm`](crate::extractor::CsrfForm)
+pub fn post_form(action: &Url, request_data: &RequestData, content: Markup) -> Markup {
+    html!(
+        form action=(action.as_str()) method="post" {
+            input type="hidden" name=(CSRF_FIELD_NAME) value=(request_data.csrf_token) {}
+            (content)
+        }
+    )
 }
 
 pub fn wrap_page(
@@ -26,6 +44,11 @@ pub fn wrap_page(
         "<a href=\"https://hacks.skytemple.org\">".into(),
     );
     credit_args.insert("skytemple_hack_link_end", "</a>".into());
+    let validators = if page_info.cacheable {
+        request_data.page_validators()
+    } else {
+        None
+    };
     let markup = html!(
         html {
             head {
@@ -58,23 +81,15 @@ pub fn wrap_page(
                     p {
                         (PreEscaped(request_data.lookup_with_args("footer-credit", &credit_args)))
                     }
-                    @if page_info.display_majority_info || request_data.majority_token.is_some() {
-                        @if let Some(majority_check) = request_data.majority_token.as_ref() {
-                            form action=(app_data.route_simple(&request_data, &["disconnect_majority_token"]).as_str()) method="post" {
+                    @if page_info.display_majority_info || request_data.session.is_some() {
+                        @if request_data.session.is_some() {
+                            (post_form(&app_data.route_simple(&request_data, &["disconnect_majority_token"]), &request_data, html!(
                                 label for="disconnect_majority_token" {
-                                    @if request_data.have_access_to_major_only_content {
-                                        (format!("You are connected with the valid majority token {}. ", majority_check._id))
-                                    } @else {
-                                        "You are connected with the "
-                                        b { "invalid" }
-                                        " majority token "
-                                        (majority_check._id)
-                                        "."
-                                    }
+                                    "You are connected with a valid majority token. "
                                 }
                                 input type="hidden" id="redirect_url" name="redirect_url" value=(app_data.route_this_page(&request_data).as_str()) {}
                                 input type="submit" value="Disconnect" {}
-                            }
+                            )))
                             @if request_data.can_certify {
                                 p {
                                     "You can create a token for another user on the "
@@ -86,7 +101,7 @@ pub fn wrap_page(
                         @if page_info.discourage_reload {
                             p { "Go to a non-interactive page to enter a majority token or disconnect it (it would reload the page)."}
                         } @else {
-                            form action=(app_data.route_simple(&request_data, &["connect_majority_token"]).as_str()) method="post" {
+                            (post_form(&app_data.route_simple(&request_data, &["connect_majority_token"]), &request_data, html!(
                                 label for="majority_token" {
                                     "Majority code ("
                                     a href=(app_data.route_simple(&request_data, &["majority"]).as_str()) { "more info" }
@@ -95,7 +110,7 @@ pub fn wrap_page(
                                 input type="hidden" id="redirect_url" name="redirect_url" value=(app_data.route_this_page(&request_data).as_str()) {}
                                 input type="text" id="majority_token" name="majority_token" {}
                                 input type="submit" value="Submit" {}
-                            }
+                            )))
                         }
                     }
                     p {
@@ -117,23 +132,20 @@ pub fn wrap_page(
     let mut response_builder = HttpResponse::build(StatusCode::OK);
     response_builder.content_type(mime::TEXT_HTML_UTF_8);
     response_builder.cookie(Cookie::build("messages", "").finish());
+    response_builder.with_validators(validators.as_ref());
 
     response_builder.body(markup.into_boxed_str().into_string())
 }
 
+/// Display the hacks in the given order
 pub fn make_hack_list(
     hacks: &[(String, &Hack)],
     request_data: &RequestData,
     app_data: &AppData,
 ) -> Markup {
-    let mut hacks_sorted = hacks
-        .iter()
-        .map(|(s, hack_ref)| (s, *hack_ref))
-        .collect::<Vec<_>>();
-    hacks_sorted.sort_by_key(|x| &x.1.data.name);
     html! {
         ul {
-            @for (hack_id, hack) in hacks_sorted.iter() {
+            @for (hack_id, hack) in hacks {
                 li {
                     a href=(app_data.route_hack(request_data, hack_id).as_str()) {
                         (hack.data.name)
@@ -144,6 +156,17 @@ pub fn make_hack_list(
     }
 }
 
+/// The hacks that aren't listed with the others for this visitor, due to the hiding rules and their preferences
+pub fn hidden_hacks_query(hiding_rules: &[HidingRule], request_data: &RequestData) -> Query {
+    Query::Or(
+        hiding_rules
+            .iter()
+            .filter(|rule| request_data.get_hiding_display(rule) != HidingDisplay::Show)
+            .map(|rule| rule.query.clone())
+            .collect(),
+    )
+}
+
 pub fn make_hack_list_hidden(
     query: Query,
     request_data: &RequestData,
@@ -152,25 +175,22 @@ pub fn make_hack_list_hidden(
     let storage = app_data.storage.load();
     let unfiltered_hacks = (Query::Difference(
         Box::new(query.clone()),
-        Box::new(Query::Or(
-            app_data
-                .hidden_by_default
-                .iter()
-                .map(|(_t, q)| q.clone())
-                .collect(),
+        Box::new(hidden_hacks_query(
+            &storage.taginfo.hiding_rules,
+            request_data,
         )),
     ))
-    .get_matching(&storage)
+    .get_matching_ordered(&storage, HackOrder::Name)
     .0;
 
     html! {
         (make_hack_list(&unfiltered_hacks, request_data, app_data))
-        @for (hidden_string, hidden_query) in &app_data.hidden_by_default {
-            @let hidden_hacks = Query::Intersection(Box::new(query.clone()), Box::new(hidden_query.clone())).get_matching(&storage).0;
+        @for rule in storage.taginfo.hiding_rules.iter().filter(|rule| request_data.get_hiding_display(rule) == HidingDisplay::Collapse) {
+            @let hidden_hacks = Query::Intersection(Box::new(query.clone()), Box::new(rule.query.clone())).get_matching_ordered(&storage, HackOrder::Name).0;
             @if !hidden_hacks.is_empty() {
                 details {
                     summary {
-                        (hidden_string) " (" (request_data.lookup("hidden-click-to-reveal")) ")"
+                        (request_data.lookup(&rule.reason)) " (" (request_data.lookup("hidden-click-to-reveal")) ")"
                     }
                     (make_hack_list(&hidden_hacks, request_data, app_data))
                 }
@@ -179,6 +199,20 @@ pub fn make_hack_list_hidden(
     }
 }
 
+/// Same as [`make_hack_list_hidden`], but reuse the render from the page cache when possible. `route` should uniquely identify the query.
+pub fn make_hack_list_hidden_cached(
+    route: &str,
+    query: Query,
+    request_data: &RequestData,
+    app_data: &AppData,
+) -> Markup {
+    app_data
+        .page_cache
+        .get_or_render(PageCacheKey::new(route, request_data), || {
+            make_hack_list_hidden(query, request_data, app_data)
+        })
+}
+
 pub fn render_markdown(text: &str) -> PreEscaped<String> {
     let text_initial = markdown_to_html(text, &ComrakOptions::default());
     // Add spoiler as a post-processing filter
@@ -201,22 +235,44 @@ pub fn render_markdown(text: &str) -> PreEscaped<String> {
     PreEscaped(result)
 }
 
+/// Render the label of the tag, coloured as per its category
+pub fn render_tag_label(tag: &Tag, taginfo: &TagInfo) -> Markup {
+    html! {
+        @if let Some(single_tag_info) = taginfo.get_tag(tag) {
+            @let label = single_tag_info.label.as_ref().unwrap_or(&tag.0);
+            @if let Some(category_data) = taginfo.get_category_for_single_tag_info(single_tag_info, tag) {
+                span class="tag" style=(format!("border-color:{};background-color:{}", category_data.border_color, category_data.background_color)) { (label) }
+            } @else {
+                span class="tag" { (label) }
+            }
+        } @else {
+            span class="tag" { (tag.0) }
+        }
+    }
+}
+
 pub fn render_tag(tag: &Tag, request_data: &RequestData, app_data: &AppData) -> Markup {
     let storage = app_data.storage.load();
     html! {
         a href=(app_data.route_hack_list_by_tag(request_data, tag).as_str()) {
-            @if let Some(single_tag_info) = storage.taginfo.get_tag(tag) {
-                @let label = single_tag_info.label.as_ref().unwrap_or(&tag.0);
-                @if let Some(category_data) = storage.taginfo.get_category_for_single_tag_info(single_tag_info, tag) {
-                    span class="tag" style=(format!("border-color:{};background-color:{}", category_data.border_color, category_data.background_color)) { (label) }
-                } @else {
-                    span class="tag" { (label) }
-                }
-            } @else {
-                span class="tag" { (tag.0) }
-            }
+            (render_tag_label(tag, &storage.taginfo))
+        }
+    }
+}
+
+/// Group the tags by category, in the order of [`TagInfo::orders_tags`]. Tags without category are grouped under None.
+pub fn group_tags_by_category(taginfo: &TagInfo, tags: Vec<Tag>) -> Vec<(Option<&str>, Vec<Tag>)> {
+    let mut groups: Vec<(Option<&str>, Vec<Tag>)> = Vec::new();
+    for tag in taginfo.orders_tags(tags) {
+        let category = taginfo
+            .get_tag(&tag)
+            .and_then(|tag_info| tag_info.category.as_deref());
+        match groups.iter_mut().find(|(c, _)| *c == category) {
+            Some((_, group)) => group.push(tag),
+            None => groups.push((category, vec![tag])),
         }
     }
+    groups
 }
 
 pub fn render_many_tags(tags: Vec<Tag>, request_data: &RequestData, app_data: &AppData) -> Markup {
@@ -237,3 +293,32 @@ pub fn render_many_tags(tags: Vec<Tag>, request_data: &RequestData, app_data: &A
         }
     }
 }
+
+/// Format an unix timestamp as an UTC date, like `2024-01-31 13:05 UTC`
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds_in_day = timestamp % 86400;
+    // civil_from_days, from http://howardhinnant.github.io/date_algorithms.html
+    let shifted_days = days + 719468;
+    let era = shifted_days.div_euclid(146097);
+    let day_of_era = shifted_days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds_in_day / 3600,
+        seconds_in_day % 3600 / 60
+    )
+}
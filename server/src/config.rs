@@ -0,0 +1,700 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use pmd_hack_storage::{HidingRule, Storage};
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use crate::Secrets;
+
+/// Prefix of the environment variables that override the configuration file
+pub const ENV_PREFIX: &str = "HACK_ARCHIVE_";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Can't read the configuration file at {0:?}")]
+    CantReadFile(PathBuf, #[source] io::Error),
+    #[error("The configuration file at {0:?} is invalid")]
+    InvalidFile(PathBuf, #[source] toml::de::Error),
+    #[error("The environment variable {name} has an invalid value: {message}")]
+    InvalidEnvironmentVariable { name: String, message: String },
+    #[error("The setting {key} is missing. Set it in the configuration file or with the environment variable {env_name}")]
+    MissingSetting { key: &'static str, env_name: String },
+    #[error("The root url {0:?} is invalid")]
+    InvalidRootUrl(String, #[source] url::ParseError),
+    #[error("The root url {0:?} can't be used as a base url")]
+    RootUrlCannotBeABase(String),
+    #[error("The root url {0:?} shouldn't end with a /")]
+    RootUrlEndWithSlash(String),
+    #[error("The scope {0:?} should either be empty or start with a /")]
+    InvalidScope(String),
+    #[error("The folder {1:?} (for {0}) doesn't exist")]
+    FolderNotFound(&'static str, PathBuf),
+    #[error("Can't read the secret {0} from the file {1:?}")]
+    CantReadSecret(&'static str, PathBuf, #[source] io::Error),
+    #[error("The file {1:?} containing the secret {0} is empty")]
+    EmptySecret(&'static str, PathBuf),
+    #[error("Multiple hiding rules have the id {0:?}")]
+    DuplicateHidingRule(String),
+}
+
+/// The content of the configuration file. Every setting can be overridden by an environment variable, see [`ConfigFile::apply_env`].
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigFile {
+    /// Path to the archive, should contain a hacks subfolder
+    pub archive_folder: Option<PathBuf>,
+    pub locales_folder: Option<PathBuf>,
+    pub bind_address: Option<String>,
+    /// base url, shouldn't end with /
+    pub root_url: Option<String>,
+    pub scope: Option<String>,
+    /// File containing the password of the reload page
+    pub reload_password_file: Option<PathBuf>,
+    #[serde(default)]
+    pub token_store: TokenStoreSection,
+    /// Only used when the tokens or the failed attempts are stored in CouchDB
+    #[serde(default)]
+    pub couchdb: CouchDbSection,
+    #[serde(default)]
+    pub session: SessionSection,
+    #[serde(default)]
+    pub rate_limit: RateLimitSection,
+    #[serde(default)]
+    pub usage: UsageSection,
+    #[serde(default)]
+    pub cache: CacheSection,
+    #[serde(default)]
+    pub features: Features,
+    /// Replace the hiding rules of the taginfo file if set
+    pub hiding_rules: Option<Vec<HidingRule>>,
+}
+
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenStoreBackend {
+    #[default]
+    #[serde(rename = "couchdb")]
+    CouchDb,
+    File,
+    Memory,
+}
+
+impl FromStr for TokenStoreBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "couchdb" => Ok(Self::CouchDb),
+            "file" => Ok(Self::File),
+            "memory" => Ok(Self::Memory),
+            _ => Err(format!(
+                "unknown backend {:?}, expected couchdb, file or memory",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TokenStoreSection {
+    #[serde(default)]
+    pub backend: TokenStoreBackend,
+    /// The file used by the file backend
+    pub path: Option<PathBuf>,
+    /// File containing the key used to hash the secret of the tokens. Changing it make every token invalid.
+    pub hash_key_file: Option<PathBuf>,
+    /// Maximum number of seconds a new or renewed token is valid for. Tokens never expire if unset, unless their certifier choose so.
+    pub max_lifetime: Option<u64>,
+}
+
+/// Where the failed attempts to connect with a majority token are counted
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RateLimitBackend {
+    /// Each server count the attempts it receives, and forget them when stopped
+    #[default]
+    Memory,
+    /// Shared between every server using the same CouchDB
+    #[serde(rename = "couchdb")]
+    CouchDb,
+}
+
+impl FromStr for RateLimitBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(Self::Memory),
+            "couchdb" => Ok(Self::CouchDb),
+            _ => Err(format!(
+                "unknown backend {:?}, expected memory or couchdb",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields, default, rename_all = "kebab-case")]
+pub struct RateLimitSection {
+    pub backend: RateLimitBackend,
+    /// Number of reverse proxies in front of the server, each adding the address it received the request from to the
+    /// Forwarded or X-Forwarded-For headers. The address of the visitor is the one this many entries from the end.
+    /// 0 to use the address of the connection, as the headers can be forged by the visitors otherwise.
+    pub trusted_proxies: usize,
+}
+
+/// The statistics of use of the majority tokens. They are stored in CouchDB if the tokens are, in memory otherwise.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields, default, rename_all = "kebab-case")]
+pub struct UsageSection {
+    /// Number of seconds between two saves of the usage counted since the previous one
+    pub flush_interval: u64,
+}
+
+impl Default for UsageSection {
+    fn default() -> Self {
+        Self { flush_interval: 60 }
+    }
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CouchDbSection {
+    pub uri: Option<String>,
+    pub username: Option<String>,
+    /// File containing the password, so it doesn't appear on the command line
+    pub password_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields, default, rename_all = "kebab-case")]
+pub struct SessionSection {
+    /// File containing the key used to sign the session cookies. Changing it end every session.
+    pub key_file: Option<PathBuf>,
+    /// Number of seconds after which a majority token must be entered again
+    pub lifetime: u64,
+    /// Number of seconds after which a session is checked again against the token store
+    pub revalidation_interval: u64,
+}
+
+impl Default for SessionSection {
+    fn default() -> Self {
+        Self {
+            key_file: None,
+            lifetime: 30 * 24 * 3600,
+            revalidation_interval: 600,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields, default, rename_all = "kebab-case")]
+pub struct CacheSection {
+    /// Maximum number of rendered page fragments to keep in memory
+    pub page_capacity: usize,
+    /// Folder where the hashes used to generate .torrent files are cached. Default to a subfolder of the temporary folder.
+    pub torrent_folder: Option<PathBuf>,
+}
+
+impl CouchDbSection {
+    fn validate(self, errors: &mut Vec<ConfigError>) -> Option<CouchDbConfig> {
+        let uri = required(self.uri, "couchdb.uri", "COUCHDB_URI", errors);
+        let username = required(
+            self.username,
+            "couchdb.username",
+            "COUCHDB_USERNAME",
+            errors,
+        );
+        let password = read_secret(
+            required(
+                self.password_file,
+                "couchdb.password-file",
+                "COUCHDB_PASSWORD_FILE",
+                errors,
+            ),
+            "couchdb.password-file",
+            errors,
+        );
+        Some(CouchDbConfig {
+            uri: uri?,
+            username: username?,
+            password: password?,
+        })
+    }
+}
+
+impl Default for CacheSection {
+    fn default() -> Self {
+        Self {
+            page_capacity: 256,
+            torrent_folder: None,
+        }
+    }
+}
+
+/// Optional parts of the website. Everything is enabled by default.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields, default)]
+pub struct Features {
+    /// Generate .torrent files for the hack files
+    pub torrent: bool,
+    /// Allow to browse the content of zip files
+    pub decompress: bool,
+    /// Allow to compare two zip files of an hack
+    pub compare: bool,
+    /// The /files search page
+    pub files: bool,
+    /// The tag filter sidebar of the index
+    pub facets: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            torrent: true,
+            decompress: true,
+            compare: true,
+            files: true,
+            facets: true,
+        }
+    }
+}
+
+fn env_value<T: FromStr>(
+    env: &dyn Fn(&str) -> Option<String>,
+    name: &str,
+    errors: &mut Vec<ConfigError>,
+) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    let full_name = format!("{}{}", ENV_PREFIX, name);
+    match env(&full_name)?.parse() {
+        Ok(value) => Some(value),
+        Err(err) => {
+            errors.push(ConfigError::InvalidEnvironmentVariable {
+                name: full_name,
+                message: err.to_string(),
+            });
+            None
+        }
+    }
+}
+
+fn required<T>(
+    value: Option<T>,
+    key: &'static str,
+    env_name: &str,
+    errors: &mut Vec<ConfigError>,
+) -> Option<T> {
+    if value.is_none() {
+        errors.push(ConfigError::MissingSetting {
+            key,
+            env_name: format!("{}{}", ENV_PREFIX, env_name),
+        });
+    }
+    value
+}
+
+fn check_folder(
+    folder: Option<PathBuf>,
+    key: &'static str,
+    errors: &mut Vec<ConfigError>,
+) -> Option<PathBuf> {
+    let folder = folder?;
+    if folder.is_dir() {
+        Some(folder)
+    } else {
+        errors.push(ConfigError::FolderNotFound(key, folder));
+        None
+    }
+}
+
+/// Read a secret from a file. A trailing line break is ignored.
+fn read_secret(
+    path: Option<PathBuf>,
+    key: &'static str,
+    errors: &mut Vec<ConfigError>,
+) -> Option<String> {
+    let path = path?;
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            let secret = content.trim_end_matches(['\n', '\r']);
+            if secret.is_empty() {
+                errors.push(ConfigError::EmptySecret(key, path));
+                None
+            } else {
+                Some(secret.to_string())
+            }
+        }
+        Err(err) => {
+            errors.push(ConfigError::CantReadSecret(key, path, err));
+            None
+        }
+    }
+}
+
+impl ConfigFile {
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| ConfigError::CantReadFile(path.to_path_buf(), err))?;
+        toml::from_str(&content).map_err(|err| ConfigError::InvalidFile(path.to_path_buf(), err))
+    }
+
+    /// Override the settings with the `HACK_ARCHIVE_*` environment variables. `env` return the value of a variable, if set.
+    pub fn apply_env(
+        &mut self,
+        env: &dyn Fn(&str) -> Option<String>,
+        errors: &mut Vec<ConfigError>,
+    ) {
+        macro_rules! set_from_env {
+            (Some($target:expr), $name:expr) => {
+                if let Some(value) = env_value(env, $name, errors) {
+                    $target = Some(value);
+                }
+            };
+            ($target:expr, $name:expr) => {
+                if let Some(value) = env_value(env, $name, errors) {
+                    $target = value;
+                }
+            };
+        }
+
+        set_from_env!(Some(self.archive_folder), "ARCHIVE_FOLDER");
+        set_from_env!(Some(self.locales_folder), "LOCALES_FOLDER");
+        set_from_env!(Some(self.bind_address), "BIND_ADDRESS");
+        set_from_env!(Some(self.root_url), "ROOT_URL");
+        set_from_env!(Some(self.scope), "SCOPE");
+        set_from_env!(Some(self.reload_password_file), "RELOAD_PASSWORD_FILE");
+        set_from_env!(self.token_store.backend, "TOKEN_STORE_BACKEND");
+        set_from_env!(Some(self.token_store.path), "TOKEN_STORE_PATH");
+        set_from_env!(
+            Some(self.token_store.hash_key_file),
+            "TOKEN_STORE_HASH_KEY_FILE"
+        );
+        set_from_env!(
+            Some(self.token_store.max_lifetime),
+            "TOKEN_STORE_MAX_LIFETIME"
+        );
+        set_from_env!(Some(self.couchdb.uri), "COUCHDB_URI");
+        set_from_env!(Some(self.couchdb.username), "COUCHDB_USERNAME");
+        set_from_env!(Some(self.couchdb.password_file), "COUCHDB_PASSWORD_FILE");
+        set_from_env!(Some(self.session.key_file), "SESSION_KEY_FILE");
+        set_from_env!(self.session.lifetime, "SESSION_LIFETIME");
+        set_from_env!(
+            self.session.revalidation_interval,
+            "SESSION_REVALIDATION_INTERVAL"
+        );
+        set_from_env!(self.rate_limit.backend, "RATE_LIMIT_BACKEND");
+        set_from_env!(
+            self.rate_limit.trusted_proxies,
+            "RATE_LIMIT_TRUSTED_PROXIES"
+        );
+        set_from_env!(self.usage.flush_interval, "USAGE_FLUSH_INTERVAL");
+        set_from_env!(self.cache.page_capacity, "CACHE_PAGE_CAPACITY");
+        set_from_env!(Some(self.cache.torrent_folder), "CACHE_TORRENT_FOLDER");
+        set_from_env!(self.features.torrent, "FEATURES_TORRENT");
+        set_from_env!(self.features.decompress, "FEATURES_DECOMPRESS");
+        set_from_env!(self.features.compare, "FEATURES_COMPARE");
+        set_from_env!(self.features.files, "FEATURES_FILES");
+        set_from_env!(self.features.facets, "FEATURES_FACETS");
+    }
+
+    /// Check the settings and read the secrets. Every problem found is added to `errors`.
+    pub fn validate(self, errors: &mut Vec<ConfigError>) -> Option<Config> {
+        let archive_folder = check_folder(
+            required(
+                self.archive_folder,
+                "archive-folder",
+                "ARCHIVE_FOLDER",
+                errors,
+            ),
+            "archive-folder",
+            errors,
+        );
+        let locales_folder = check_folder(
+            required(
+                self.locales_folder,
+                "locales-folder",
+                "LOCALES_FOLDER",
+                errors,
+            ),
+            "locales-folder",
+            errors,
+        );
+        let bind_address = required(self.bind_address, "bind-address", "BIND_ADDRESS", errors);
+
+        let root_url =
+            required(self.root_url, "root-url", "ROOT_URL", errors).and_then(|root_url_text| {
+                match Url::parse(&root_url_text) {
+                    Ok(root_url) if root_url.cannot_be_a_base() => {
+                        errors.push(ConfigError::RootUrlCannotBeABase(root_url_text));
+                        None
+                    }
+                    Ok(root_url) if root_url.path() != "/" && root_url.path().ends_with('/') => {
+                        errors.push(ConfigError::RootUrlEndWithSlash(root_url_text));
+                        None
+                    }
+                    Ok(root_url) => Some(root_url),
+                    Err(err) => {
+                        errors.push(ConfigError::InvalidRootUrl(root_url_text, err));
+                        None
+                    }
+                }
+            });
+
+        let scope = self.scope.unwrap_or_default();
+        if !scope.is_empty() && !scope.starts_with('/') {
+            errors.push(ConfigError::InvalidScope(scope.clone()));
+        }
+
+        let reload_page_password = read_secret(
+            required(
+                self.reload_password_file,
+                "reload-password-file",
+                "RELOAD_PASSWORD_FILE",
+                errors,
+            ),
+            "reload-password-file",
+            errors,
+        );
+        let token_hash_key = read_secret(
+            required(
+                self.token_store.hash_key_file,
+                "token-store.hash-key-file",
+                "TOKEN_STORE_HASH_KEY_FILE",
+                errors,
+            ),
+            "token-store.hash-key-file",
+            errors,
+        );
+        let session_key = read_secret(
+            required(
+                self.session.key_file,
+                "session.key-file",
+                "SESSION_KEY_FILE",
+                errors,
+            ),
+            "session.key-file",
+            errors,
+        );
+
+        let couchdb = if self.token_store.backend == TokenStoreBackend::CouchDb
+            || self.rate_limit.backend == RateLimitBackend::CouchDb
+        {
+            self.couchdb.validate(errors)
+        } else {
+            None
+        };
+
+        let token_store = match self.token_store.backend {
+            TokenStoreBackend::CouchDb => Some(TokenStoreConfig::CouchDb),
+            TokenStoreBackend::File => required(
+                self.token_store.path,
+                "token-store.path",
+                "TOKEN_STORE_PATH",
+                errors,
+            )
+            .map(TokenStoreConfig::File),
+            TokenStoreBackend::Memory => Some(TokenStoreConfig::Memory),
+        };
+
+        if let Some(hiding_rules) = &self.hiding_rules {
+            let mut hiding_rule_ids = HashSet::new();
+            for hiding_rule in hiding_rules {
+                if !hiding_rule_ids.insert(&hiding_rule.id) {
+                    errors.push(ConfigError::DuplicateHidingRule(hiding_rule.id.clone()));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return None;
+        }
+
+        Some(Config {
+            archive_folder: archive_folder?,
+            locales_folder: locales_folder?,
+            bind_address: bind_address?,
+            root_url: root_url?,
+            scope,
+            couchdb,
+            token_store: token_store?,
+            token_max_lifetime: self.token_store.max_lifetime,
+            rate_limit_backend: self.rate_limit.backend,
+            trusted_proxies: self.rate_limit.trusted_proxies,
+            usage_flush_interval: self.usage.flush_interval,
+            secrets: Secrets {
+                reload_page_password: reload_page_password?,
+                token_hash_key: token_hash_key?,
+                session_key: session_key?,
+            },
+            session_lifetime: self.session.lifetime,
+            session_revalidation_interval: self.session.revalidation_interval,
+            page_cache_capacity: self.cache.page_capacity,
+            torrent_cache_folder: self
+                .cache
+                .torrent_folder
+                .unwrap_or_else(|| std::env::temp_dir().join("pmd_hack_archive_torrent_cache")),
+            features: self.features,
+            hiding_rules: self.hiding_rules,
+        })
+    }
+}
+
+pub struct CouchDbConfig {
+    pub uri: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Where the majority tokens are stored
+pub enum TokenStoreConfig {
+    /// In the database of [`Config::couchdb`]
+    CouchDb,
+    /// A file with one token per line
+    File(PathBuf),
+    /// Lost when the server stops
+    Memory,
+}
+
+/// The validated configuration of the server
+pub struct Config {
+    pub archive_folder: PathBuf,
+    pub locales_folder: PathBuf,
+    pub bind_address: String,
+    /// Can be used as a base
+    pub root_url: Url,
+    pub scope: String,
+    /// Set if the tokens or the failed attempts are stored in CouchDB
+    pub couchdb: Option<CouchDbConfig>,
+    pub token_store: TokenStoreConfig,
+    /// In seconds
+    pub token_max_lifetime: Option<u64>,
+    pub rate_limit_backend: RateLimitBackend,
+    /// Number of reverse proxies whose headers give the address of the visitor
+    pub trusted_proxies: usize,
+    /// In seconds
+    pub usage_flush_interval: u64,
+    pub secrets: Secrets,
+    /// In seconds
+    pub session_lifetime: u64,
+    /// In seconds
+    pub session_revalidation_interval: u64,
+    pub page_cache_capacity: usize,
+    pub torrent_cache_folder: PathBuf,
+    pub features: Features,
+    pub hiding_rules: Option<Vec<HidingRule>>,
+}
+
+impl Config {
+    /// Read the configuration file (if any), apply the environment variables and validate the result.
+    /// Return every problem found on failure.
+    pub fn load(
+        path: Option<&Path>,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Self, Vec<ConfigError>> {
+        let mut config_file = match path {
+            Some(path) => ConfigFile::read(path).map_err(|err| vec![err])?,
+            None => ConfigFile::default(),
+        };
+        let mut errors = Vec::new();
+        config_file.apply_env(env, &mut errors);
+        match config_file.validate(&mut errors) {
+            Some(config) if errors.is_empty() => Ok(config),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// Load the archive, replacing its hiding rules by the configured ones if any
+pub fn load_storage(archive_folder: &Path, hiding_rules: Option<&[HidingRule]>) -> Storage {
+    let mut storage = Storage::load_from_folder(archive_folder);
+    if let Some(hiding_rules) = hiding_rules {
+        storage.taginfo.hiding_rules = hiding_rules.to_vec();
+    }
+    storage
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::config::{ConfigError, ConfigFile, RateLimitBackend};
+
+    #[test]
+    pub fn test_config_validation() {
+        let mut config_file: ConfigFile = toml::from_str(
+            r#"
+            root-url = "https://example.com/archive/"
+            scope = "archive"
+
+            [couchdb]
+            uri = "http://localhost:5984"
+
+            [features]
+            torrent = false
+            "#,
+        )
+        .unwrap();
+        assert!(!config_file.features.torrent);
+        assert!(config_file.features.compare);
+
+        let env = HashMap::from([
+            ("HACK_ARCHIVE_BIND_ADDRESS", "127.0.0.1:8080"),
+            ("HACK_ARCHIVE_CACHE_PAGE_CAPACITY", "many"),
+            ("HACK_ARCHIVE_FEATURES_COMPARE", "false"),
+            ("HACK_ARCHIVE_RATE_LIMIT_BACKEND", "couchdb"),
+            ("HACK_ARCHIVE_RATE_LIMIT_TRUSTED_PROXIES", "2"),
+        ]);
+        let mut errors = Vec::new();
+        config_file.apply_env(
+            &|name| env.get(name).map(|value| value.to_string()),
+            &mut errors,
+        );
+        assert_eq!(config_file.bind_address.as_deref(), Some("127.0.0.1:8080"));
+        assert!(!config_file.features.compare);
+        assert_eq!(config_file.cache.page_capacity, 256);
+        assert_eq!(config_file.rate_limit.backend, RateLimitBackend::CouchDb);
+        assert_eq!(config_file.rate_limit.trusted_proxies, 2);
+
+        assert!(config_file.validate(&mut errors).is_none());
+        // every problem is reported, not just the first one
+        let missing_settings = errors
+            .iter()
+            .filter_map(|error| match error {
+                ConfigError::MissingSetting { key, .. } => Some(*key),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            missing_settings,
+            vec![
+                "archive-folder",
+                "locales-folder",
+                "reload-password-file",
+                "token-store.hash-key-file",
+                "session.key-file",
+                "couchdb.username",
+                "couchdb.password-file"
+            ]
+        );
+        assert!(errors
+            .iter()
+            .any(|error| matches!(error, ConfigError::InvalidEnvironmentVariable { .. })));
+        assert!(errors
+            .iter()
+            .any(|error| matches!(error, ConfigError::RootUrlEndWithSlash(_))));
+        assert!(errors
+            .iter()
+            .any(|error| matches!(error, ConfigError::InvalidScope(_))));
+    }
+}
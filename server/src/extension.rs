@@ -6,10 +6,12 @@ use fluent_templates::{ArcLoader, Loader};
 use log::warn;
 use unic_langid::LanguageIdentifier;
 
-use crate::message::Messages;
+use crate::{message::Messages, CacheValidators};
 
 pub trait HttpResponseBuilderExtension {
     fn with_messages(&mut self, messages: Messages) -> &mut Self;
+
+    fn with_validators(&mut self, validators: Option<&CacheValidators>) -> &mut Self;
 }
 
 impl HttpResponseBuilderExtension for HttpResponseBuilder {
@@ -17,6 +19,13 @@ impl HttpResponseBuilderExtension for HttpResponseBuilder {
         //unwrap: Messages should never fail to serialize
         self.cookie(Cookie::build("messages", serde_json::to_string(&messages).unwrap()).finish())
     }
+
+    fn with_validators(&mut self, validators: Option<&CacheValidators>) -> &mut Self {
+        if let Some(validators) = validators {
+            validators.add_to_response(self);
+        }
+        self
+    }
 }
 
 //TODO: use a fallack (a.k.a english) language?
@@ -0,0 +1,70 @@
+use std::{future::Future, ops::Deref, pin::Pin};
+
+use actix_web::{
+    error::{ErrorBadRequest, ErrorForbidden},
+    web::{Bytes, Data},
+    Error, FromRequest,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{session::SessionSigner, AppData};
+
+/// The name of the form field containing the CSRF token
+pub const CSRF_FIELD_NAME: &str = "csrf_token";
+
+#[derive(Deserialize)]
+struct CsrfField {
+    csrf_token: String,
+}
+
+/// A form with no field other than the CSRF token, for actions that need no parameter
+#[derive(Deserialize)]
+pub struct EmptyForm {}
+
+/// An url-encoded form, only accepted if it contain the CSRF token of the visitor, proving it was sent from a page of this site.
+/// Forms rendered with [`post_form`](crate::post_form) contain it. Must be used by every route that modify something.
+pub struct CsrfForm<T>(pub T);
+
+impl<T> Deref for CsrfForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for CsrfForm<T> {
+    type Error = Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let app_data = req.app_data::<Data<AppData>>().unwrap().clone();
+        let csrf_nonce = req.cookie(SessionSigner::CSRF_COOKIE_NAME);
+        let body = Bytes::from_request(req, payload);
+
+        Box::pin(async move {
+            let body = body.await?;
+
+            let csrf_field = serde_urlencoded::from_bytes::<CsrfField>(&body).ok();
+            let is_valid = match (csrf_nonce, csrf_field) {
+                (Some(csrf_nonce), Some(csrf_field)) => app_data
+                    .session_signer
+                    .check_csrf_token(csrf_nonce.value(), &csrf_field.csrf_token),
+                _ => false,
+            };
+            if !is_valid {
+                return Err(ErrorForbidden(
+                    "This form has expired or was sent from another website. Go back, reload the page and submit it again.",
+                ));
+            }
+
+            serde_urlencoded::from_bytes(&body)
+                .map(CsrfForm)
+                .map_err(ErrorBadRequest)
+        })
+    }
+}
@@ -1,2 +1,5 @@
 mod request_data;
 pub use request_data::RequestData;
+
+mod csrf_form;
+pub use csrf_form::{CsrfForm, EmptyForm, CSRF_FIELD_NAME};
@@ -1,25 +1,50 @@
-use std::{collections::HashMap, convert::Infallible, future::Future, pin::Pin, sync::Arc};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    convert::Infallible,
+    future::Future,
+    hash::{Hash, Hasher},
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+    time::SystemTime,
+};
 
-use actix_web::{web::Data, FromRequest};
-use database::model::MajorityToken;
+use actix_web::{
+    http::header::{Header, IfModifiedSince, IfNoneMatch, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+    web::Data,
+    FromRequest, HttpMessage, HttpResponse,
+};
+use database::get_timestamp;
 use fluent_templates::{fluent_bundle::FluentValue, LanguageIdentifier};
+use pmd_hack_storage::HidingRule;
 use qstring::QString;
 use unic_langid::langid;
 
 use crate::{
     message::{MessageKind, Messages},
-    AppData, FluentLookupInfaillable,
+    session::{
+        legacy_majority_token_removal_cookie, CookieUpdates, Session, SessionSigner,
+        LEGACY_MAJORITY_TOKEN_COOKIE_NAME,
+    },
+    AppData, CacheValidators, FluentLookupInfaillable, HidingDisplay, Preferences,
 };
 
 pub struct RequestData {
-    pub majority_token: Option<MajorityToken>,
+    /// The validated state of the majority token of the user, if any
+    pub session: Option<Session>,
     pub have_access_to_major_only_content: bool,
     pub can_certify: bool,
     pub messages: Messages,
     pub language: LanguageIdentifier,
     pub path: String,
+    pub query_string: String,
     pub app_data: Arc<AppData>,
-    pub reload_secret: Option<String>,
+    pub if_none_match: Option<IfNoneMatch>,
+    pub if_modified_since: Option<SystemTime>,
+    pub preferences: Preferences,
+    /// Must be included in every form that modify something, see [`CsrfForm`](crate::extractor::CsrfForm)
+    pub csrf_token: String,
+    /// Whether the CSRF nonce was created for this request, in which case the pages the client has contain another token
+    pub csrf_nonce_is_new: bool,
 }
 
 impl FromRequest for RequestData {
@@ -39,7 +64,8 @@ impl FromRequest for RequestData {
         }
         let app_data = req.app_data::<Data<AppData>>().unwrap().clone();
 
-        let query_string = QString::from(req.query_string());
+        let raw_query_string = req.query_string().to_string();
+        let query_string = QString::from(raw_query_string.as_str());
         let language = query_string
             .get("lang")
             .unwrap_or("en")
@@ -69,34 +95,115 @@ impl FromRequest for RequestData {
             )
         }
 
-        let majority_token_cookie = req.cookie("majority_token");
+        let session_cookie = req.cookie(SessionSigner::COOKIE_NAME);
+        // from before sessions were introduced, contain the secret of the token
+        let legacy_majority_token_cookie = req.cookie(LEGACY_MAJORITY_TOKEN_COOKIE_NAME);
+
+        let preferences = req
+            .cookie(Preferences::COOKIE_NAME)
+            .map(|cookie| Preferences::from_cookie(&cookie))
+            .unwrap_or_default();
+
+        let if_none_match = if req.headers().contains_key(IF_NONE_MATCH) {
+            IfNoneMatch::parse(req).ok()
+        } else {
+            None
+        };
+        let if_modified_since = if req.headers().contains_key(IF_MODIFIED_SINCE) {
+            IfModifiedSince::parse(req)
+                .ok()
+                .map(|date| SystemTime::from(date.0))
+        } else {
+            None
+        };
+
+        let mut cookie_updates = Vec::new();
+
+        let (csrf_nonce, csrf_nonce_is_new) = match req
+            .cookie(SessionSigner::CSRF_COOKIE_NAME)
+            .filter(|cookie| !cookie.value().is_empty())
+        {
+            Some(cookie) => (cookie.value().to_string(), false),
+            None => {
+                let cookie = app_data
+                    .session_signer
+                    .new_csrf_cookie(app_data.root_url.path());
+                let nonce = cookie.value().to_string();
+                cookie_updates.push(cookie);
+                (nonce, true)
+            }
+        };
+        let csrf_token = app_data.session_signer.csrf_token(&csrf_nonce);
 
+        let req = req.clone();
         Box::pin(async move {
-            let mut majority_token: Option<String> =
-                majority_token_cookie.map(|cookie| cookie.value().to_string());
+            let mut session = None;
+            if let Some(cookie) = session_cookie.filter(|cookie| !cookie.value().is_empty()) {
+                match app_data
+                    .session_signer
+                    .from_cookie(&cookie, get_timestamp())
+                {
+                    Some(previous) if app_data.session_need_revalidation(&previous) => {
+                        session = app_data
+                            .revalidate_session(previous.clone(), &mut messages, &language)
+                            .await;
+                        match &session {
+                            Some(session) if *session == previous => (),
+                            Some(session) => cookie_updates.push(app_data.session_cookie(session)),
+                            None => cookie_updates.push(app_data.session_removal_cookie()),
+                        }
+                    }
+                    Some(valid) => session = Some(valid),
+                    None => {
+                        messages.add_message_from_string(
+                            app_data
+                                .locales
+                                .lookup_infaillable(&language, "message-session-expired"),
+                            MessageKind::Error,
+                        );
+                        cookie_updates.push(app_data.session_removal_cookie());
+                    }
+                }
+            } else if let Some(cookie) =
+                legacy_majority_token_cookie.filter(|cookie| !cookie.value().is_empty())
+            {
+                session = app_data
+                    .open_session(cookie.value(), &mut messages, &language)
+                    .await
+                    .ok()
+                    .flatten();
+                if let Some(session) = &session {
+                    cookie_updates.push(app_data.session_cookie(session));
+                }
+                cookie_updates.push(legacy_majority_token_removal_cookie(
+                    app_data.root_url.path(),
+                ));
+            }
 
-            if majority_token.as_deref() == Some("") {
-                majority_token = None;
-            };
+            if !cookie_updates.is_empty() {
+                req.extensions_mut().insert(CookieUpdates(cookie_updates));
+            }
 
-            let (majority_token, have_access_to_major_only_content, can_certify) =
-                if let Some(token) = majority_token {
-                    app_data
-                        .check_validity_of_majority_token(&token, &mut messages, &language)
-                        .await
-                } else {
-                    (None, false, false)
-                };
+            if let Some(session) = &session {
+                app_data
+                    .usage_recorder
+                    .record_request(&session.token_id, app_data.network_of(&req).as_deref());
+            }
 
             Ok(Self {
-                majority_token,
-                have_access_to_major_only_content,
-                can_certify,
+                have_access_to_major_only_content: session.is_some(),
+                can_certify: session.as_ref().is_some_and(|session| session.can_certify),
+                session,
                 messages,
                 language,
                 path,
+                query_string: raw_query_string,
                 app_data: app_data.into_inner(),
-                reload_secret: query_string.get("reload_secret").map(|x| x.to_string()),
+                if_none_match,
+                if_modified_since,
+                preferences,
+                csrf_token,
+                csrf_nonce_is_new,
             })
         })
     }
@@ -114,4 +221,54 @@ impl RequestData {
             .locales
             .lookup_with_args_infaillable(&self.language, text_id, args)
     }
+
+    /// How the hacks matching this rule should be displayed, taking the preferences of the visitor into account
+    pub fn get_hiding_display(&self, rule: &HidingRule) -> HidingDisplay {
+        self.preferences
+            .get_display(rule, self.have_access_to_major_only_content)
+    }
+
+    /// Validators for a page rendered for this request, derived from the storage generation and the state of the user.
+    /// Return None if the page display one-shot messages, and thus shouldn't be cached, or if the forms of the pages
+    /// the client has contain an outdated CSRF token.
+    pub fn page_validators(&self) -> Option<CacheValidators> {
+        if !self.messages.is_empty() || self.csrf_nonce_is_new {
+            return None;
+        }
+        let mut hasher = DefaultHasher::new();
+        self.app_data
+            .storage_generation
+            .load(Ordering::SeqCst)
+            .hash(&mut hasher);
+        // the generation restart from 0 with the server
+        self.app_data
+            .storage_loaded_at
+            .load(Ordering::SeqCst)
+            .hash(&mut hasher);
+        self.path.hash(&mut hasher);
+        self.query_string.hash(&mut hasher);
+        self.language.to_string().hash(&mut hasher);
+        self.have_access_to_major_only_content.hash(&mut hasher);
+        self.can_certify.hash(&mut hasher);
+        self.preferences.hash(&mut hasher);
+        self.session
+            .as_ref()
+            .map(|session| &session.token_id)
+            .hash(&mut hasher);
+        Some(CacheValidators::weak_from_hash(hasher.finish()))
+    }
+
+    /// Return a 304 response if the client already has an up-to-date response with these validators
+    pub fn check_not_modified(&self, validators: &CacheValidators) -> Option<HttpResponse> {
+        if validators.is_not_modified(self.if_none_match.as_ref(), self.if_modified_since) {
+            Some(validators.not_modified_response())
+        } else {
+            None
+        }
+    }
+
+    /// Return a 304 response if the client already has an up-to-date version of the page rendered for this request
+    pub fn not_modified_response(&self) -> Option<HttpResponse> {
+        self.check_not_modified(&self.page_validators()?)
+    }
 }
@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, HashMap};
+
+use fluent_templates::fluent_bundle::FluentValue;
+use maud::{html, Markup};
+use pmd_hack_storage::{Query, Storage, Tag};
+use qstring::QString;
+use url::Url;
+
+use crate::{
+    extractor::RequestData, group_tags_by_category, hidden_hacks_query, render_tag_label, AppData,
+};
+
+/// The tags selected in the filter sidebar of the index. Encoded in the URL as `with=a,b&without=c`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct TagSelection {
+    /// every one of these tags should be present. Sorted.
+    pub with: Vec<Tag>,
+    /// none of these tags should be present. Sorted.
+    pub without: Vec<Tag>,
+}
+
+fn parse_tag_list(value: Option<&str>) -> Vec<Tag> {
+    let mut tags = value
+        .unwrap_or("")
+        .split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| Tag(tag.to_string()))
+        .collect::<Vec<_>>();
+    tags.sort_unstable();
+    tags.dedup();
+    tags
+}
+
+fn join_tags(tags: &[Tag]) -> String {
+    tags.iter()
+        .map(|tag| tag.0.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl TagSelection {
+    pub fn from_query_string(query_string: &str) -> Self {
+        let query_string = QString::from(query_string);
+        let with = parse_tag_list(query_string.get("with"));
+        let without = parse_tag_list(query_string.get("without"))
+            .into_iter()
+            .filter(|tag| !with.contains(tag))
+            .collect();
+        Self { with, without }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.with.is_empty() && self.without.is_empty()
+    }
+
+    pub fn to_query(&self) -> Query {
+        let base = if self.with.is_empty() {
+            Query::All
+        } else {
+            Query::AllOfTags(self.with.clone())
+        };
+        if self.without.is_empty() {
+            base
+        } else {
+            Query::Difference(
+                Box::new(base),
+                Box::new(Query::AtLeastOneOfTag(self.without.clone())),
+            )
+        }
+    }
+
+    /// Add the parameters of this selection to the url
+    pub fn add_to_url(&self, url: &mut Url) {
+        if !self.with.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("with", &join_tags(&self.with));
+        }
+        if !self.without.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("without", &join_tags(&self.without));
+        }
+    }
+
+    /// An unique string for this selection, to be used as part of a cache key
+    pub fn cache_key(&self) -> String {
+        format!(
+            "with={}&without={}",
+            join_tags(&self.with),
+            join_tags(&self.without)
+        )
+    }
+
+    fn removing(&self, tag: &Tag) -> Self {
+        Self {
+            with: self.with.iter().filter(|t| *t != tag).cloned().collect(),
+            without: self.without.iter().filter(|t| *t != tag).cloned().collect(),
+        }
+    }
+
+    fn including(&self, tag: &Tag) -> Self {
+        let mut result = self.removing(tag);
+        result.with.push(tag.clone());
+        result.with.sort_unstable();
+        result
+    }
+
+    fn excluding(&self, tag: &Tag) -> Self {
+        let mut result = self.removing(tag);
+        result.without.push(tag.clone());
+        result.without.sort_unstable();
+        result
+    }
+}
+
+/// Count the hacks matching the selection but not `hidden`, and how many of them have each tag. Tags none of them
+/// have are left out.
+fn count_facets(
+    storage: &Storage,
+    selection: &TagSelection,
+    hidden: Query,
+) -> (usize, BTreeMap<Tag, usize>) {
+    let selected_set = Query::Difference(Box::new(selection.to_query()), Box::new(hidden))
+        .get_matching_set(storage)
+        .0;
+    let tag_counts = storage
+        .tags
+        .tag_list
+        .keys()
+        .filter_map(|tag| {
+            let count = storage
+                .index
+                .get_hacks_with_tag(tag)
+                .map_or(0, |set| set.intersection_count(&selected_set));
+            (count > 0).then(|| (tag.clone(), count))
+        })
+        .collect();
+    (selected_set.count(), tag_counts)
+}
+
+/// Render the filter sidebar, with the number of hacks in the current selection that have each tag. The hacks
+/// [`crate::make_hack_list_hidden`] doesn't list with the others aren't counted.
+pub fn render_facets(
+    selection: &TagSelection,
+    request_data: &RequestData,
+    app_data: &AppData,
+) -> Markup {
+    let storage = app_data.storage.load();
+    let (selected_count, tag_counts) = count_facets(
+        &storage,
+        selection,
+        hidden_hacks_query(&storage.taginfo.hiding_rules, request_data),
+    );
+    let route = |selection: &TagSelection| {
+        let mut url = app_data.base_url(request_data);
+        selection.add_to_url(&mut url);
+        url
+    };
+
+    html! {
+        aside class="facets" {
+            h2 { (request_data.lookup("facets-header")) }
+            @if !selection.is_empty() {
+                p {
+                    (request_data.lookup_with_args("facets-matching-count", &HashMap::from([
+                        ("count", FluentValue::from(selected_count))
+                    ])))
+                    " "
+                    a href=(route(&TagSelection::default()).as_str()) { (request_data.lookup("facets-clear")) }
+                }
+                ul class="facetselection" {
+                    @for (tags, selected_class) in [(&selection.with, "facetincluded"), (&selection.without, "facetexcluded")] {
+                        @for tag in tags {
+                            li class=(selected_class) {
+                                (render_tag_label(tag, &storage.taginfo))
+                                " "
+                                a href=(route(&selection.removing(tag)).as_str()) title=(request_data.lookup("facets-remove")) { "×" }
+                            }
+                        }
+                    }
+                }
+            }
+            @for (category, tags) in group_tags_by_category(&storage.taginfo, tag_counts.keys().cloned().collect()) {
+                details open {
+                    summary {
+                        @match category {
+                            Some(category) => (category),
+                            None => (request_data.lookup("facets-uncategorized")),
+                        }
+                    }
+                    ul {
+                        @for tag in tags.iter().filter(|tag| !selection.with.contains(tag) && !selection.without.contains(tag)) {
+                            li class="facet" {
+                                (render_tag_label(tag, &storage.taginfo))
+                                " (" (tag_counts[tag]) ") "
+                                a href=(route(&selection.including(tag)).as_str()) title=(request_data.lookup("facets-include")) { "+" }
+                                " "
+                                a href=(route(&selection.excluding(tag)).as_str()) title=(request_data.lookup("facets-exclude")) { "−" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{count_facets, TagSelection};
+    use pmd_hack_storage::{Hack, HackData, Query, Storage, Tag};
+    use std::collections::HashSet;
+
+    fn make_hack(tags: &str) -> Hack {
+        Hack {
+            data: serde_json::from_str::<HackData>(&format!(
+                r#"{{ "name": "hack", "tags": {}, "files": [] }}"#,
+                tags
+            ))
+            .unwrap(),
+            implied_tags: HashSet::new(),
+            folder: Default::default(),
+        }
+    }
+
+    #[test]
+    pub fn test_hidden_hacks_not_counted() {
+        let mut storage = Storage::default();
+        storage.add_hack("a".into(), make_hack(r#"["romhack", "translation"]"#));
+        storage.add_hack("b".into(), make_hack(r#"["romhack", "explicit"]"#));
+        storage.add_hack("c".into(), make_hack(r#"["translation"]"#));
+        storage.rebuild_index();
+        let hidden = Query::AtLeastOneOfTag(vec![Tag("explicit".into())]);
+
+        let (count, tag_counts) = count_facets(&storage, &TagSelection::default(), hidden.clone());
+        assert_eq!(count, 2);
+        assert_eq!(tag_counts.get(&Tag("romhack".into())), Some(&1));
+        assert_eq!(tag_counts.get(&Tag("translation".into())), Some(&2));
+        // only present on the hidden hack
+        assert_eq!(tag_counts.get(&Tag("explicit".into())), None);
+
+        let selection = TagSelection::from_query_string("with=romhack");
+        let (count, tag_counts) = count_facets(&storage, &selection, hidden);
+        assert_eq!(count, 1);
+        assert_eq!(tag_counts.get(&Tag("translation".into())), Some(&1));
+        assert_eq!(tag_counts.get(&Tag("explicit".into())), None);
+
+        let (count, tag_counts) = count_facets(&storage, &selection, Query::Or(Vec::new()));
+        assert_eq!(count, 2);
+        assert_eq!(tag_counts.get(&Tag("explicit".into())), Some(&1));
+    }
+}
@@ -1,6 +1,8 @@
 use std::{
+    fs::File,
     io::{Cursor, Read, Seek},
     path::PathBuf,
+    time::UNIX_EPOCH,
 };
 
 use actix_files::NamedFile;
@@ -11,7 +13,7 @@ use actix_web::{
 use pmd_hack_storage::{Hack, Storage};
 use zip::ZipArchive;
 
-use crate::extractor::RequestData;
+use crate::{extractor::RequestData, CacheValidators};
 
 pub type FileRefGetFileType = Either<NamedFile, Vec<u8>>;
 use safe_join::SafeJoin;
@@ -35,12 +37,13 @@ impl FileRef {
         storage.hacks.get(hack_id)
     }
 
-    pub fn get_file(
+    /// Return the hack, but only if it exist and the user has access to it
+    fn get_accessible_hack<'a>(
         &self,
-        storage: &Storage,
+        storage: &'a Storage,
         request_data: &RequestData,
-    ) -> Result<FileRefGetFileType> {
-        let hack = if let Some(hack) = self.get_hack(&storage) {
+    ) -> Result<&'a Hack> {
+        let hack = if let Some(hack) = self.get_hack(storage) {
             hack
         } else {
             return Err(ErrorNotFound(request_data.lookup("hack-does-not-exist")));
@@ -54,24 +57,30 @@ impl FileRef {
             ));
         }
 
-        let filename = match self {
-            Self::HackFile(_, filename) => filename,
-            Self::Zipped(_, filename) => filename,
-        };
+        Ok(hack)
+    }
+
+    fn get_hack_file_path(
+        hack: &Hack,
+        filename: &str,
+        request_data: &RequestData,
+    ) -> Result<PathBuf> {
+        hack.folder
+            .safe_join(filename)
+            .map_err(|_| ErrorBadRequest(request_data.lookup("path-traversal-detected")))
+    }
+
+    pub fn get_file(
+        &self,
+        storage: &Storage,
+        request_data: &RequestData,
+    ) -> Result<FileRefGetFileType> {
+        let hack = self.get_accessible_hack(storage, request_data)?;
 
         Ok(match self {
-            Self::HackFile(_, _) => {
-                let path: PathBuf = match hack.folder.safe_join(filename) {
-                    Ok(v) => v,
-                    Err(_) => {
-                        return Err(ErrorBadRequest(
-                            request_data.lookup("path-traversal-detected"),
-                        ))
-                    }
-                };
-
-                Either::Left(NamedFile::open(path)?)
-            }
+            Self::HackFile(_, filename) => Either::Left(NamedFile::open(
+                Self::get_hack_file_path(hack, filename, request_data)?,
+            )?),
             Self::Zipped(source_hack, inner_path) => {
                 let hack_file = source_hack.get_reader(&storage, &request_data)?;
 
@@ -88,6 +97,41 @@ impl FileRef {
         })
     }
 
+    /// Return strong validators for the content of a file inside a zip file of an hack, based on the modification time of the zip file and the CRC of the inner file.
+    /// Return None for other files. (NamedFile already handle caching itself)
+    pub fn get_validators(
+        &self,
+        storage: &Storage,
+        request_data: &RequestData,
+    ) -> Result<Option<CacheValidators>> {
+        let hack = self.get_accessible_hack(storage, request_data)?;
+
+        let (zip_filename, inner_path) = match self {
+            Self::Zipped(source, inner_path) => match source.as_ref() {
+                Self::HackFile(_, zip_filename) => (zip_filename, inner_path),
+                Self::Zipped(_, _) => return Ok(None),
+            },
+            Self::HackFile(_, _) => return Ok(None),
+        };
+
+        let zip_path = Self::get_hack_file_path(hack, zip_filename, request_data)?;
+        let zip_file = File::open(&zip_path).map_err(ErrorNotFound)?;
+        let last_modified = zip_file
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map_err(ErrorInternalServerError)?;
+        let mut zip = ZipArchive::new(zip_file).map_err(ErrorInternalServerError)?;
+        let inner_file = zip.by_name(inner_path).map_err(ErrorNotFound)?;
+
+        let mtime = last_modified
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Ok(Some(CacheValidators::strong(
+            format!("{}-{}-{:08x}", mtime, inner_file.size(), inner_file.crc32()),
+            last_modified,
+        )))
+    }
+
     pub fn get_reader(
         &self,
         storage: &Storage,
@@ -0,0 +1,96 @@
+use std::time::{Duration, SystemTime};
+
+use actix_web::{
+    http::header::{
+        CacheControl, CacheDirective, ETag, EntityTag, HttpDate, IfNoneMatch, LastModified,
+    },
+    HttpResponse, HttpResponseBuilder,
+};
+
+/// The values used to check if the client already has an up-to-date version of a response
+pub struct CacheValidators {
+    pub etag: EntityTag,
+    /// None if the content may change without a newer modification date, so only the ETag can be used
+    pub last_modified: Option<SystemTime>,
+}
+
+impl CacheValidators {
+    /// Validators for a page that depend on the state of the storage and of the user. There is no modification date, as
+    /// the page also changes when the user logs in or changes their language or preferences.
+    pub fn weak_from_hash(hash: u64) -> Self {
+        Self {
+            etag: EntityTag::new_weak(format!("{:016x}", hash)),
+            last_modified: None,
+        }
+    }
+
+    /// Validators for a content that is byte-for-byte identical as long as `tag` is the same
+    pub fn strong(tag: String, last_modified: SystemTime) -> Self {
+        Self {
+            etag: EntityTag::new_strong(tag),
+            last_modified: Some(last_modified),
+        }
+    }
+
+    /// Check whether a response with these validators would be the same as the one the client has, as per RFC 9110 section 13.2.2
+    pub fn is_not_modified(
+        &self,
+        if_none_match: Option<&IfNoneMatch>,
+        if_modified_since: Option<SystemTime>,
+    ) -> bool {
+        match if_none_match {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(etags)) => etags.iter().any(|etag| etag.weak_eq(&self.etag)),
+            None => match (self.last_modified, if_modified_since) {
+                // HTTP dates have a precision of a second
+                (Some(last_modified), Some(if_modified_since)) => {
+                    last_modified <= if_modified_since + Duration::from_millis(999)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    pub fn add_to_response(&self, builder: &mut HttpResponseBuilder) {
+        builder
+            .insert_header(ETag(self.etag.clone()))
+            // pages depend on cookies, so they should only be cached by the browser, and always revalidated
+            .insert_header(CacheControl(vec![
+                CacheDirective::Private,
+                CacheDirective::NoCache,
+            ]));
+        if let Some(last_modified) = self.last_modified {
+            builder.insert_header(LastModified(HttpDate::from(last_modified)));
+        }
+    }
+
+    pub fn not_modified_response(&self) -> HttpResponse {
+        let mut builder = HttpResponse::NotModified();
+        self.add_to_response(&mut builder);
+        builder.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CacheValidators;
+    use actix_web::http::header::{EntityTag, IfNoneMatch};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    pub fn test_is_not_modified() {
+        let now = SystemTime::now();
+        let page = CacheValidators::weak_from_hash(1);
+        let matching = IfNoneMatch::Items(vec![EntityTag::new_weak("0000000000000001".into())]);
+        let other = IfNoneMatch::Items(vec![EntityTag::new_weak("0000000000000002".into())]);
+        assert!(page.is_not_modified(Some(&matching), None));
+        assert!(!page.is_not_modified(Some(&other), Some(now)));
+        // the date can't tell whether the page changed
+        assert!(!page.is_not_modified(None, Some(now)));
+
+        let file = CacheValidators::strong("file".into(), now - Duration::from_secs(10));
+        assert!(file.is_not_modified(None, Some(now)));
+        assert!(!file.is_not_modified(None, Some(now - Duration::from_secs(20))));
+        assert!(!file.is_not_modified(None, None));
+    }
+}
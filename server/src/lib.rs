@@ -1,5 +1,4 @@
 mod common;
-use serde::Deserialize;
 
 pub use common::*;
 
@@ -15,6 +14,27 @@ pub use extension::*;
 mod app_data;
 pub use app_data::AppData;
 
+mod http_cache;
+pub use http_cache::CacheValidators;
+
+mod page_cache;
+pub use page_cache::{PageCache, PageCacheKey};
+
+mod facets;
+pub use facets::{render_facets, TagSelection};
+
+mod preferences;
+pub use preferences::{HidingDisplay, Preferences};
+
+pub mod config;
+pub use config::{Config, Features};
+
+pub mod session;
+
+pub mod rate_limit;
+
+pub mod usage;
+
 mod fileref;
 pub use fileref::{FileRef, FileRefGetFileType};
 
@@ -34,8 +54,14 @@ pub fn is_illegal_hack_slug(name: &str) -> bool {
             "create_majority_token"|
             "disconnect_majority_token"|
             "connect_majority_token"|
+            "renew_majority_token"|
             "index"|
             "reload"|
+            "compare"|
+            "files"|
+            "tags"|
+            "preferences"|
+            "admin"|
 
             //likely to be used
             "faq"|
@@ -55,9 +81,12 @@ pub fn is_illegal_hack_slug(name: &str) -> bool {
     }
 }
 
-#[derive(Deserialize)]
 pub struct Secrets {
-    reload_page_password: String,
+    pub reload_page_password: String,
+    /// Key of the hash majority tokens are stored under
+    pub token_hash_key: String,
+    /// Key signing the session cookies
+    pub session_key: String,
 }
 
 #[cfg(test)]
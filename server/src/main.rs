@@ -1,36 +1,42 @@
+use actix_web::dev::Service;
 use actix_web::web::Data;
 use actix_web::{web, App, HttpServer};
 use arc_swap::ArcSwap;
 use clap::Parser;
-use database::HackClient;
+use database::{
+    get_timestamp, AttemptStore, FileTokenStore, HackClient, HackClientError, MajorityTokenStore,
+    MemoryAttemptStore, MemoryTokenStore, MemoryUsageStore, TokenHasher, UsageStore,
+};
 use display_error_chain::DisplayErrorChain;
 use fluent_templates::ArcLoader;
-use pmd_hack_storage::{Query, Storage, Tag};
+use pmd_hack_storage::TorrentCache;
 use server::pages::{
-    connect_majority_token, create_majority_token, css, decompress, disconnect_majority_token,
-    file, hack, hackindex, index, majority, oswald, reload_storage, tagged,
+    admin, compare, connect_majority_token, create_majority_token, css, decompress,
+    disconnect_majority_token, file, files, hack, hackindex, index, majority, oswald, preferences,
+    reload_storage, renew_majority_token, tagged, tags, torrent,
+};
+use server::{
+    config::{load_storage, RateLimitBackend, TokenStoreConfig},
+    rate_limit::RateLimiter,
+    session::{apply_cookie_updates, SessionSigner},
+    usage::UsageRecorder,
+    AppData, Config, PageCache,
 };
-use server::AppData;
-use std::fs::File;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{atomic::AtomicU64, Arc};
+use std::time::Duration;
 use unic_langid::langid;
-use url::Url;
 
 #[derive(Parser, Debug)]
 #[clap()]
 pub struct Opts {
-    /// Path to the archive, should contain a hacks subfolder
-    archive_folder: PathBuf,
-    locales_folder: PathBuf,
-    bind_address: String,
-    /// base url, shouldn't end with /
-    root_url: String,
-    scope: String,
-    couch_uri: String,
-    couch_username: String,
-    couch_password: String,
-    secret_file: PathBuf,
+    /// Path to the TOML configuration file. Settings can also be provided with HACK_ARCHIVE_* environment variables.
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// Store the majority tokens still stored under their secret under their hash instead, remove the conflict history
+    /// containing secrets, then exit. Can be run again if interrupted.
+    #[clap(long)]
+    migrate_hashed_token_ids: bool,
 }
 
 #[tokio::main]
@@ -39,20 +45,23 @@ async fn main() {
 
     let opts = Opts::parse();
 
-    let root_url = Url::parse(&opts.root_url).unwrap();
-    if root_url.cannot_be_a_base() {
-        panic!("The provided url ({:?}) cannot be use a base url", root_url);
-    }
+    let config = match Config::load(opts.config.as_deref(), &|name| std::env::var(name).ok()) {
+        Ok(config) => config,
+        Err(errors) => {
+            println!("The configuration is invalid:");
+            for error in &errors {
+                println!("{}", DisplayErrorChain::new(error).to_string());
+            }
+            std::process::exit(1);
+        }
+    };
 
-    let locales = ArcLoader::builder(&opts.locales_folder, langid!("en"))
-        .shared_resources(Some(&[opts.locales_folder.join("core.ftl")]))
+    let locales = ArcLoader::builder(&config.locales_folder, langid!("en"))
+        .shared_resources(Some(&[config.locales_folder.join("core.ftl")]))
         .build()
         .unwrap();
 
-    let secret_file = File::open(&opts.secret_file).unwrap();
-    let secrets = serde_json::from_reader(secret_file).unwrap();
-
-    let storage = Storage::load_from_folder(&opts.archive_folder);
+    let storage = load_storage(&config.archive_folder, config.hiding_rules.as_deref());
 
     if !storage.errors.is_empty() {
         println!("There are errors that occured during the loading of the datas! :");
@@ -63,70 +72,207 @@ async fn main() {
 
     println!("hacks loaded");
 
-    //TODO: rework how hacks are hidden
-    let hidden_by_default = vec![
-        (
-            "Hacks marked being considered as being likely to be perceived as offensive".into(),
-            Query::AtLeastOneOfTag(vec![Tag("likely-offensive".into())])
-        ),
-        (
-            "Hacks marked as being explicitly refused in the SkyTemple hack list for moderation reason".into(),
-            Query::AtLeastOneOfTag(vec![Tag("refused-skytemple".into())]),
-        ),
-        (
-            "Merged hack".into(),
-            Query::AtLeastOneOfTag(vec![Tag("deprecated".into())]),
-        ),
-        (
-            "Hacks marked as being reserved to major person (contain pornography)".into(),
-            Query::AtLeastOneOfTag(vec![Tag("pornographic".into())]),
-        )
-    ];
-
-    let hack_client = HackClient::new_from_connection_info(
-        &opts.couch_uri,
-        &opts.couch_username,
-        &opts.couch_password,
-    )
-    .await
-    .unwrap();
+    let token_hasher = TokenHasher::new(config.secrets.token_hash_key.as_bytes());
+    let couchdb_client = match &config.couchdb {
+        Some(couchdb) => {
+            let client = HackClient::new_from_connection_info(
+                &couchdb.uri,
+                &couchdb.username,
+                &couchdb.password,
+                token_hasher.clone(),
+            )
+            .await;
+            match client {
+                Ok(client) => {
+                    println!("connected to couchdb");
+                    Some(Arc::new(client))
+                }
+                Err(error) => {
+                    println!(
+                        "Can't connect to couchdb: {}",
+                        DisplayErrorChain::new(&error)
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let token_store: Arc<dyn MajorityTokenStore> = match &config.token_store {
+        //unwrap: the couchdb settings are required by this backend
+        TokenStoreConfig::CouchDb => couchdb_client.clone().unwrap(),
+        TokenStoreConfig::File(path) => match FileTokenStore::open(path, token_hasher) {
+            Ok(store) => Arc::new(store),
+            Err(error) => {
+                println!(
+                    "Can't open the majority token file: {}",
+                    DisplayErrorChain::new(&error)
+                );
+                std::process::exit(1);
+            }
+        },
+        TokenStoreConfig::Memory => {
+            println!("majority tokens are only stored in memory, and will be lost on exit");
+            Arc::new(MemoryTokenStore::new(token_hasher))
+        }
+    };
+
+    if opts.migrate_hashed_token_ids {
+        let migration = async {
+            let migrated_count = token_store.migrate_to_hashed_ids().await?;
+            println!("{} majority tokens migrated", migrated_count);
+            let purged_count = token_store.purge_plaintext_conflict_history().await?;
+            println!("{} conflict log entries removed", purged_count);
+            Ok::<(), HackClientError>(())
+        };
+        if let Err(error) = migration.await {
+            println!(
+                "The migration of the majority tokens failed: {}",
+                DisplayErrorChain::new(&error)
+            );
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // tokens stored under their secret can't be found by their hash, so their owners would be unable to connect
+    match token_store.count_unmigrated_tokens().await {
+        Ok(0) => (),
+        Ok(unmigrated_count) => {
+            println!(
+                "{} majority tokens are still stored under their secret. Run the server once with --migrate-hashed-token-ids to migrate them.",
+                unmigrated_count
+            );
+            std::process::exit(1);
+        }
+        Err(error) => {
+            println!(
+                "Can't check whether the majority tokens are migrated: {}",
+                DisplayErrorChain::new(&error)
+            );
+            std::process::exit(1);
+        }
+    }
+
+    // usage is stored along the tokens, but the file store is only meant for tokens
+    let usage_store: Arc<dyn UsageStore> = match &config.token_store {
+        //unwrap: the couchdb settings are required by this backend
+        TokenStoreConfig::CouchDb => couchdb_client.clone().unwrap(),
+        TokenStoreConfig::File(_) | TokenStoreConfig::Memory => {
+            Arc::new(MemoryUsageStore::default())
+        }
+    };
+
+    let attempt_store: Arc<dyn AttemptStore> = match config.rate_limit_backend {
+        //unwrap: the couchdb settings are required by this backend
+        RateLimitBackend::CouchDb => couchdb_client.unwrap(),
+        RateLimitBackend::Memory => Arc::new(MemoryAttemptStore::default()),
+    };
+    let rate_limiter = RateLimiter::new(attempt_store, config.trusted_proxies);
+
+    let torrent_cache = TorrentCache::new(config.torrent_cache_folder);
+
+    let session_signer = SessionSigner::new(
+        config.secrets.session_key.as_bytes(),
+        // browsers refuse secure cookies on plain http, except for localhost
+        config.root_url.scheme() == "https",
+    );
 
     let app_data = Data::new(AppData {
-        root_url,
-        archive_folder: opts.archive_folder,
+        root_url: config.root_url,
+        archive_folder: config.archive_folder,
         storage: ArcSwap::new(Arc::new(storage)),
-        hack_client,
-        hidden_by_default,
+        storage_generation: AtomicU64::new(0),
+        storage_loaded_at: AtomicU64::new(get_timestamp()),
+        token_store,
+        torrent_cache: Arc::new(torrent_cache),
+        page_cache: PageCache::new(config.page_cache_capacity),
         locales,
-        secrets,
+        secrets: config.secrets,
+        features: config.features,
+        hiding_rules: config.hiding_rules,
+        session_signer,
+        session_lifetime: config.session_lifetime,
+        session_revalidation_interval: config.session_revalidation_interval,
+        revocation_generation: AtomicU64::new(get_timestamp()),
+        rate_limiter,
+        token_max_lifetime: config.token_max_lifetime,
+        usage_recorder: UsageRecorder::new(usage_store),
     });
 
-    println!("connected to couchdb");
+    let usage_app_data = app_data.clone();
+    // tokio refuse an interval of 0
+    let usage_flush_interval = Duration::from_secs(config.usage_flush_interval.max(1));
+    tokio::spawn(async move {
+        usage_app_data
+            .usage_recorder
+            .flush_periodically(usage_flush_interval)
+            .await
+    });
+    let flushed_app_data = app_data.clone();
+
+    let scope_path = config.scope;
+    let features = config.features;
 
     HttpServer::new(move || {
-        App::new().app_data(app_data.clone()).service(
-            web::scope(&opts.scope)
-                .service(reload_storage::reload)
-                .service(oswald)
-                .service(css::css)
-                .service(index::index)
-                .service(hackindex::index_root::index_root)
-                .service(hackindex::index_taginfo::index_taginfo)
-                .service(hackindex::index_hacks::index_hacks)
-                .service(hackindex::index_hack::index_hack)
-                .service(majority::majority)
-                .service(create_majority_token::create_majority_token)
-                .service(tagged::tagged)
-                .service(disconnect_majority_token::disconnect_majority_token)
-                .service(connect_majority_token::connect_majority_token)
-                .service(hack::hack)
-                .service(file::file)
-                .service(decompress::decompress),
-        )
+        let mut scope = web::scope(&scope_path)
+            .service(reload_storage::reload_form)
+            .service(reload_storage::reload)
+            .service(admin::revoke::revoke)
+            .service(admin::revoke::do_revoke)
+            .service(admin::tokens::tokens)
+            .service(admin::tokens::token)
+            .service(admin::tokens::save_token)
+            .service(admin::usage::usage)
+            .service(oswald)
+            .service(css::css)
+            .service(index::index)
+            .service(hackindex::index_root::index_root)
+            .service(hackindex::index_taginfo::index_taginfo)
+            .service(hackindex::index_hacks::index_hacks)
+            .service(hackindex::index_hack::index_hack)
+            .service(majority::majority)
+            .service(create_majority_token::create_majority_token)
+            .service(tagged::tagged)
+            .service(tags::tags)
+            .service(preferences::preferences)
+            .service(preferences::save_preferences)
+            .service(disconnect_majority_token::disconnect_majority_token)
+            .service(connect_majority_token::connect_majority_token)
+            .service(renew_majority_token::renew_majority_token);
+        if features.files {
+            scope = scope.service(files::files);
+        }
+        if features.decompress {
+            scope = scope.service(decompress::decompress);
+        }
+        if features.compare {
+            scope = scope.service(compare::compare);
+        }
+        // must be registered before file, whose route also match .torrent files
+        if features.torrent {
+            scope = scope.service(torrent::torrent);
+        }
+        scope = scope.service(hack::hack).service(file::file);
+        App::new()
+            .app_data(app_data.clone())
+            .wrap_fn(|request, service| {
+                let response = service.call(request);
+                async move {
+                    let mut response = response.await?;
+                    apply_cookie_updates(&mut response)?;
+                    Ok(response)
+                }
+            })
+            .service(scope)
     })
-    .bind(&opts.bind_address)
+    .bind(&config.bind_address)
     .unwrap()
     .run()
     .await
     .unwrap();
+
+    // save the usage counted since the latest flush before exiting
+    flushed_app_data.usage_recorder.flush().await;
 }
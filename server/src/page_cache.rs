@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use maud::Markup;
+
+use crate::{extractor::RequestData, Preferences};
+
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+pub struct PageCacheKey {
+    /// identify the rendered fragment, including everything that may change its content outside of the other fields
+    pub route: String,
+    pub language: String,
+    pub major_access: bool,
+    pub storage_generation: u64,
+    pub preferences: Preferences,
+}
+
+impl PageCacheKey {
+    pub fn new(route: &str, request_data: &RequestData) -> Self {
+        Self {
+            route: route.to_string(),
+            language: request_data.language.to_string(),
+            major_access: request_data.have_access_to_major_only_content,
+            storage_generation: request_data
+                .app_data
+                .storage_generation
+                .load(Ordering::SeqCst),
+            preferences: request_data.preferences.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PageCacheEntries {
+    /// the u64 is the value of `tick` when the entry was last used
+    entries: HashMap<PageCacheKey, (u64, Markup)>,
+    tick: u64,
+}
+
+/// A bounded cache of rendered HTML fragments. When full, the least recently used entry is evicted.
+pub struct PageCache {
+    capacity: usize,
+    entries: Mutex<PageCacheEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl PageCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(PageCacheEntries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Return the cached fragment for `key`, or render and cache it
+    pub fn get_or_render<F: FnOnce() -> Markup>(&self, key: PageCacheKey, render: F) -> Markup {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            entries.tick += 1;
+            let tick = entries.tick;
+            if let Some((last_used, markup)) = entries.entries.get_mut(&key) {
+                *last_used = tick;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return markup.clone();
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // rendering is done without the lock held, so multiple thread may render the same fragment at once
+        let markup = render();
+
+        if self.capacity > 0 {
+            let mut entries = self.entries.lock().unwrap();
+            if entries.entries.len() >= self.capacity && !entries.entries.contains_key(&key) {
+                if let Some(oldest_key) = entries
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, (last_used, _))| *last_used)
+                    .map(|(oldest_key, _)| oldest_key.clone())
+                {
+                    entries.entries.remove(&oldest_key);
+                }
+            }
+            let tick = entries.tick;
+            entries.entries.insert(key, (tick, markup.clone()));
+        }
+
+        markup
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PageCache, PageCacheKey};
+    use crate::{HidingDisplay, Preferences};
+    use maud::{html, Markup};
+    use std::cell::Cell;
+
+    fn make_key(route: &str) -> PageCacheKey {
+        PageCacheKey {
+            route: route.to_string(),
+            language: "en".to_string(),
+            major_access: false,
+            storage_generation: 0,
+            preferences: Preferences::default(),
+        }
+    }
+
+    /// Return whether the fragment had to be rendered
+    fn is_rendered(cache: &PageCache, key: PageCacheKey) -> bool {
+        let rendered = Cell::new(false);
+        cache.get_or_render(key, || -> Markup {
+            rendered.set(true);
+            html!("fragment")
+        });
+        rendered.get()
+    }
+
+    #[test]
+    pub fn test_page_cache_eviction() {
+        let cache = PageCache::new(2);
+        assert!(is_rendered(&cache, make_key("a")));
+        assert!(is_rendered(&cache, make_key("b")));
+        assert!(!is_rendered(&cache, make_key("a")));
+        // b is the least recently used
+        assert!(is_rendered(&cache, make_key("c")));
+        assert_eq!(cache.len(), 2);
+        assert!(!is_rendered(&cache, make_key("a")));
+        assert!(!is_rendered(&cache, make_key("c")));
+        assert!(is_rendered(&cache, make_key("b")));
+        assert_eq!((cache.hits(), cache.misses()), (3, 4));
+
+        let disabled_cache = PageCache::new(0);
+        assert!(is_rendered(&disabled_cache, make_key("a")));
+        assert!(is_rendered(&disabled_cache, make_key("a")));
+        assert!(disabled_cache.is_empty());
+    }
+
+    #[test]
+    pub fn test_page_cache_key_fields() {
+        let cache = PageCache::new(10);
+        assert!(is_rendered(&cache, make_key("a")));
+
+        let mut reloaded_key = make_key("a");
+        reloaded_key.storage_generation = 1;
+        assert!(is_rendered(&cache, reloaded_key.clone()));
+        assert!(!is_rendered(&cache, reloaded_key));
+
+        let mut preferences_key = make_key("a");
+        preferences_key
+            .preferences
+            .hiding
+            .insert("explicit".to_string(), HidingDisplay::Show);
+        assert!(is_rendered(&cache, preferences_key.clone()));
+        assert!(!is_rendered(&cache, preferences_key));
+
+        assert!(!is_rendered(&cache, make_key("a")));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    pub fn test_page_cache_clear() {
+        let cache = PageCache::new(10);
+        assert!(is_rendered(&cache, make_key("a")));
+        assert!(is_rendered(&cache, make_key("b")));
+        cache.clear();
+        assert!(cache.is_empty());
+        assert!(is_rendered(&cache, make_key("a")));
+        assert!(is_rendered(&cache, make_key("b")));
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod revoke;
+pub mod tokens;
+pub mod usage;
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use actix_web::{error::ErrorForbidden, get, post, web::Data, Error, HttpResponse};
+use database::MajorityTokenStore;
+use fluent_templates::fluent_bundle::FluentValue;
+use maud::html;
+use qstring::QString;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    extractor::{CsrfForm, RequestData},
+    message::{MessageKind, Messages},
+    post_form, wrap_page, AppData, HttpResponseBuilderExtension, PageInfo,
+};
+
+/// The url of the revocation page, showing the progress of the revocation of `token` if any
+pub fn route_revoke(app_data: &AppData, request_data: &RequestData, token: Option<&str>) -> Url {
+    let mut url = app_data.route_simple(request_data, &["admin", "revoke"]);
+    if let Some(token) = token {
+        url.query_pairs_mut().append_pair("token", token);
+    }
+    url
+}
+
+#[get("/admin/revoke")]
+pub async fn revoke(
+    app_data: Data<AppData>,
+    request_data: RequestData,
+) -> Result<HttpResponse, Error> {
+    if !app_data.is_admin(&request_data) {
+        return Err(ErrorForbidden("No secret provided or invalid"));
+    }
+
+    let query_string = QString::from(request_data.query_string.as_str());
+    let token = query_string
+        .get("token")
+        .filter(|token| !token.is_empty())
+        .map(|token| app_data.token_store.token_hasher().to_id(token));
+
+    let progress = match &token {
+        Some(token) => match app_data
+            .token_store
+            .get_descendants_deepest_first(token)
+            .await
+        {
+            Ok(descendants) if descendants.is_empty() => {
+                html!(p { (request_data.lookup("admin-revoke-unknown-token")) })
+            }
+            Ok(descendants) => {
+                let revoked_count = descendants
+                    .iter()
+                    .filter(|token| token.admin_flags.get().revoked)
+                    .count();
+                html!(
+                    h2 { (request_data.lookup("admin-revoke-progress-section")) }
+                    p {
+                        (request_data.lookup_with_args("admin-revoke-progress", &HashMap::from([
+                            ("revoked", FluentValue::from(revoked_count)),
+                            ("total", FluentValue::from(descendants.len())),
+                        ])))
+                    }
+                    table class="taglist" {
+                        tr {
+                            th { (request_data.lookup("admin-revoke-column-token")) }
+                            th { (request_data.lookup("admin-revoke-column-status")) }
+                        }
+                        @for descendant in &descendants {
+                            @let admin_flags = descendant.admin_flags.get();
+                            tr {
+                                td { code { (descendant._id) } }
+                                td {
+                                    @if admin_flags.revoked {
+                                        (request_data.lookup("admin-revoke-status-revoked"))
+                                        @if let Some(reason) = &admin_flags.revocation_reason {
+                                            " (" (reason) ")"
+                                        }
+                                    } @else {
+                                        (request_data.lookup("admin-revoke-status-valid"))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                )
+            }
+            Err(e) => {
+                log::error!(
+                    "An error occured while listing the descendants of a majority token : {:?}",
+                    e
+                );
+                html!(p { (e.end_user_error_message()) })
+            }
+        },
+        None => html!(),
+    };
+
+    Ok(wrap_page(
+        html!(
+            h1 { (request_data.lookup("admin-revoke-header")) }
+            p { (request_data.lookup("admin-revoke-presentation")) }
+            (post_form(&route_revoke(&app_data, &request_data, None), &request_data, html!(
+                label for="token" { (request_data.lookup("admin-revoke-token")) }
+                " "
+                input type="text" id="token" name="token" value=(token.as_deref().unwrap_or("")) required {}
+                br {}
+                label for="reason" { (request_data.lookup("admin-revoke-reason")) }
+                " "
+                input type="text" id="reason" name="reason" required {}
+                br {}
+                input type="submit" value=(request_data.lookup("admin-revoke-submit")) {}
+            )))
+            (progress)
+        ),
+        PageInfo {
+            name: request_data.lookup("admin-revoke-title"),
+            discourage_reload: false,
+            display_majority_info: false,
+            cacheable: false,
+        },
+        &app_data,
+        request_data,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeForm {
+    token: String,
+    reason: String,
+}
+
+#[post("/admin/revoke")]
+pub async fn do_revoke(
+    app_data: Data<AppData>,
+    request_data: RequestData,
+    form: CsrfForm<RevokeForm>,
+) -> Result<HttpResponse, Error> {
+    if !app_data.is_admin(&request_data) {
+        return Err(ErrorForbidden("No secret provided or invalid"));
+    }
+
+    // the secret of the token may have been entered, but only its id should be kept
+    let root = app_data.token_store.token_hasher().to_id(form.token.trim());
+    let message = if form.reason.trim().is_empty() {
+        Messages::create_with_message(
+            request_data.lookup("admin-revoke-missing-reason"),
+            MessageKind::Error,
+        )
+    } else {
+        let result = app_data
+            .token_store
+            .revoke_majority_token_cascading(&root, form.reason.trim())
+            .await;
+        // even when interrupted, some tokens may have been revoked
+        app_data.invalidate_sessions();
+        match result {
+            Ok(revoked_count) => {
+                log::info!(
+                    "{} majority tokens revoked, starting from {}, for the reason: {}",
+                    revoked_count,
+                    root,
+                    form.reason
+                );
+                Messages::create_with_message(
+                    request_data.lookup_with_args(
+                        "admin-revoke-done",
+                        &HashMap::from([("count", FluentValue::from(revoked_count))]),
+                    ),
+                    MessageKind::Success,
+                )
+            }
+            Err(e) => {
+                log::error!("An error occured while revoking a majority token : {:?}", e);
+                Messages::create_with_message(
+                    request_data.lookup("admin-revoke-interrupted"),
+                    MessageKind::Error,
+                )
+            }
+        }
+    };
+
+    Ok(HttpResponse::SeeOther()
+        .append_header((
+            "location",
+            route_revoke(&app_data, &request_data, Some(&root)).as_str(),
+        ))
+        .with_messages(message)
+        .finish())
+}
@@ -0,0 +1,419 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use actix_web::{
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
+    get, post,
+    web::{Data, Path},
+    Error, HttpResponse,
+};
+use database::{get_timestamp, model::MajorityToken, HackClientError, MajorityTokenStore};
+use maud::{html, Markup};
+use serde::Deserialize;
+
+use crate::{
+    extractor::{CsrfForm, RequestData},
+    format_timestamp,
+    message::{MessageKind, Messages},
+    pages::admin::{revoke::route_revoke, usage::render_network_count},
+    post_form, wrap_page, AppData, HttpResponseBuilderExtension, PageInfo,
+};
+
+fn database_error(error: HackClientError) -> Error {
+    log::error!(
+        "An error occured while reading majority tokens for the admin console : {:?}",
+        error
+    );
+    ErrorInternalServerError(error.end_user_error_message())
+}
+
+fn render_flags(token: &MajorityToken, request_data: &RequestData) -> Markup {
+    let admin_flags = token.admin_flags.get();
+    html!(
+        @for (enabled, flag) in [
+            (admin_flags.is_admin, "admin-tokens-flag-admin"),
+            (admin_flags.can_certify, "admin-tokens-flag-can-certify"),
+            (admin_flags.need_certification, "admin-tokens-flag-need-certification"),
+            (admin_flags.revoked, "admin-tokens-flag-revoked"),
+        ] {
+            @if enabled {
+                " "
+                span class="tokenflag" { (request_data.lookup(flag)) }
+            }
+        }
+        @if token.is_expired(get_timestamp()) {
+            " "
+            span class="tokenflag" { (request_data.lookup("admin-tokens-flag-expired")) }
+        }
+    )
+}
+
+fn render_latest_certification(token: &MajorityToken, request_data: &RequestData) -> String {
+    if token.latest_certification_timestamp == 0 {
+        request_data.lookup("admin-tokens-never")
+    } else {
+        format_timestamp(token.latest_certification_timestamp)
+    }
+}
+
+/// Render a token and, the first time it is encountered, the tokens it certified
+fn render_token_tree(
+    id: &str,
+    tokens: &BTreeMap<String, MajorityToken>,
+    visited: &mut HashSet<String>,
+    request_data: &RequestData,
+    app_data: &AppData,
+) -> Markup {
+    let first_visit = visited.insert(id.to_string());
+    html!(
+        li {
+            a href=(app_data.route_simple(request_data, &["admin", "tokens", id]).as_str()) { code { (id) } }
+            @match tokens.get(id) {
+                Some(token) => {
+                    (render_flags(token, request_data))
+                    @if !first_visit {
+                        " " (request_data.lookup("admin-tokens-already-listed"))
+                    } @else if !token.certify.is_empty() {
+                        ul {
+                            @for certified in &token.certify {
+                                (render_token_tree(certified, tokens, visited, request_data, app_data))
+                            }
+                        }
+                    }
+                }
+                None => {
+                    " " (request_data.lookup("admin-tokens-missing"))
+                }
+            }
+        }
+    )
+}
+
+#[get("/admin/tokens")]
+pub async fn tokens(
+    app_data: Data<AppData>,
+    request_data: RequestData,
+) -> Result<HttpResponse, Error> {
+    if !app_data.is_admin(&request_data) {
+        return Err(ErrorForbidden("Only admins can access this page"));
+    }
+
+    let tokens = app_data
+        .token_store
+        .list_majority_tokens()
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|token| (token._id.clone(), token))
+        .collect::<BTreeMap<_, _>>();
+    let certified = tokens
+        .values()
+        .flat_map(|token| token.certify.iter())
+        .collect::<HashSet<_>>();
+
+    // the roots are the tokens not certified by any other token, like the ones created by an admin or with the admin tool
+    let mut visited = HashSet::new();
+    let mut forest = Vec::new();
+    for id in tokens.keys().filter(|id| !certified.contains(id)) {
+        forest.push(render_token_tree(
+            id,
+            &tokens,
+            &mut visited,
+            &request_data,
+            &app_data,
+        ));
+    }
+    // only possible with a certification cycle
+    let mut unreachable = Vec::new();
+    for id in tokens.keys() {
+        if !visited.contains(id) {
+            unreachable.push(render_token_tree(
+                id,
+                &tokens,
+                &mut visited,
+                &request_data,
+                &app_data,
+            ));
+        }
+    }
+
+    Ok(wrap_page(
+        html!(
+            h1 { (request_data.lookup("admin-tokens-header")) }
+            p { (request_data.lookup("admin-tokens-presentation")) }
+            p {
+                a href=(route_revoke(&app_data, &request_data, None).as_str()) { (request_data.lookup("admin-tokens-revoke-link")) }
+                " · "
+                a href=(app_data.route_simple(&request_data, &["admin", "usage"]).as_str()) { (request_data.lookup("admin-tokens-usage-link")) }
+            }
+            ul class="tokentree" {
+                @for tree in forest {
+                    (tree)
+                }
+            }
+            @if !unreachable.is_empty() {
+                h2 { (request_data.lookup("admin-tokens-unreachable-section")) }
+                ul class="tokentree" {
+                    @for tree in unreachable {
+                        (tree)
+                    }
+                }
+            }
+        ),
+        PageInfo {
+            name: request_data.lookup("admin-tokens-title"),
+            discourage_reload: false,
+            display_majority_info: false,
+            cacheable: false,
+        },
+        &app_data,
+        request_data,
+    ))
+}
+
+#[get("/admin/tokens/{token}")]
+pub async fn token(
+    app_data: Data<AppData>,
+    request_data: RequestData,
+    path: Path<String>,
+) -> Result<HttpResponse, Error> {
+    if !app_data.is_admin(&request_data) {
+        return Err(ErrorForbidden("Only admins can access this page"));
+    }
+    let id = path.into_inner();
+
+    let token = app_data
+        .token_store
+        .get_majority_token_by_id(&id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| ErrorNotFound("This majority token doesn't exist"))?;
+    let certified_by = app_data
+        .token_store
+        .list_majority_tokens()
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .filter(|other| other.certify.contains(&id))
+        .map(|other| other._id)
+        .collect::<BTreeSet<_>>();
+    let conflict_history = app_data
+        .token_store
+        .get_conflict_history(&id)
+        .await
+        .map_err(database_error)?;
+    app_data.usage_recorder.flush().await;
+    let usage = app_data
+        .usage_recorder
+        .store()
+        .get_token_usage(&id)
+        .await
+        .map_err(database_error)?
+        .map(|usage| usage.total());
+
+    let admin_flags = token.admin_flags.get();
+    let route_token = |id: &str| app_data.route_simple(&request_data, &["admin", "tokens", id]);
+
+    Ok(wrap_page(
+        html!(
+            h1 { (request_data.lookup("admin-tokens-token-header")) " " code { (id) } }
+            p {
+                a href=(app_data.route_simple(&request_data, &["admin", "tokens"]).as_str()) { (request_data.lookup("admin-tokens-back")) }
+                " · "
+                a href=(route_revoke(&app_data, &request_data, Some(&id)).as_str()) { (request_data.lookup("admin-tokens-revoke-cascading")) }
+            }
+            h2 { (request_data.lookup("admin-tokens-flags-section")) }
+            (post_form(&route_token(&id), &request_data, html!(
+                @for (name, enabled, flag) in [
+                    ("is_admin", admin_flags.is_admin, "admin-tokens-flag-admin"),
+                    ("can_certify", admin_flags.can_certify, "admin-tokens-flag-can-certify"),
+                    ("need_certification", admin_flags.need_certification, "admin-tokens-flag-need-certification"),
+                    ("revoked", admin_flags.revoked, "admin-tokens-flag-revoked"),
+                ] {
+                    input type="checkbox" id=(name) name=(name) checked[enabled] {}
+                    label for=(name) { (request_data.lookup(flag)) }
+                    br {}
+                }
+                input type="submit" value=(request_data.lookup("admin-tokens-save")) {}
+            )))
+            @if let Some(reason) = &admin_flags.revocation_reason {
+                p { (request_data.lookup("admin-tokens-revocation-reason")) " " (reason) }
+            }
+            p { (request_data.lookup("admin-tokens-flags-modified")) " " (format_timestamp(token.admin_flags.1)) }
+            p { (request_data.lookup("admin-tokens-latest-certification")) " " (render_latest_certification(&token, &request_data)) }
+
+            h2 { (request_data.lookup("admin-tokens-expiry-section")) }
+            p {
+                (request_data.lookup("admin-tokens-expires")) " "
+                @match token.expires_at.get() {
+                    Some(expires_at) => (format_timestamp(*expires_at)),
+                    None => (request_data.lookup("admin-tokens-never")),
+                }
+            }
+            (post_form(&app_data.route_simple(&request_data, &["renew_majority_token"]), &request_data, html!(
+                input type="hidden" name="redirect_url" value=(route_token(&id).as_str()) {}
+                input type="hidden" name="token" value=(id) {}
+                label for="days" { (request_data.lookup("admin-tokens-renew-days")) }
+                " "
+                input type="number" id="days" name="days" min="1" {}
+                " "
+                input type="submit" value=(request_data.lookup("admin-tokens-renew")) {}
+            )))
+
+            h2 { (request_data.lookup("admin-tokens-certified-by-section")) }
+            @if certified_by.is_empty() {
+                p { (request_data.lookup("admin-tokens-root")) }
+            } @else {
+                ul {
+                    @for other in &certified_by {
+                        li { a href=(route_token(other).as_str()) { code { (other) } } }
+                    }
+                }
+            }
+
+            h2 { (request_data.lookup("admin-tokens-certify-section")) }
+            @if token.certify.is_empty() {
+                p { (request_data.lookup("admin-tokens-certify-none")) }
+            } @else {
+                ul {
+                    @for other in &token.certify {
+                        li {
+                            a href=(route_token(other).as_str()) { code { (other) } }
+                            @if let Some(certified_at) = token.certified_at.get(other) {
+                                " " (request_data.lookup("admin-tokens-certified-on")) " " (format_timestamp(*certified_at))
+                            }
+                        }
+                    }
+                }
+            }
+
+            h2 { (request_data.lookup("admin-tokens-usage-section")) }
+            @match &usage {
+                Some(usage) => {
+                    table class="taglist" {
+                        tr {
+                            th { (request_data.lookup("admin-usage-column-networks")) }
+                            th { (request_data.lookup("admin-usage-column-connections")) }
+                            th { (request_data.lookup("admin-usage-column-requests")) }
+                            th { (request_data.lookup("admin-usage-column-first-seen")) }
+                            th { (request_data.lookup("admin-usage-column-last-seen")) }
+                        }
+                        tr {
+                            td { (render_network_count(usage)) }
+                            td { (usage.connections) }
+                            td { (usage.requests) }
+                            td { (format_timestamp(usage.first_seen)) }
+                            td { (format_timestamp(usage.last_seen)) }
+                        }
+                    }
+                }
+                None => {
+                    p { (request_data.lookup("admin-tokens-usage-none")) }
+                }
+            }
+
+            h2 { (request_data.lookup("admin-tokens-conflicts-section")) }
+            @if conflict_history.is_empty() {
+                p { (request_data.lookup("admin-tokens-conflicts-none")) }
+            }
+            @for entry in &conflict_history {
+                h3 { (format_timestamp(entry.timestamp)) }
+                table class="taglist" {
+                    tr {
+                        th { (request_data.lookup("admin-tokens-column-revision")) }
+                        th { (request_data.lookup("admin-tokens-column-flags")) }
+                        th { (request_data.lookup("admin-tokens-column-certify")) }
+                        th { (request_data.lookup("admin-tokens-column-latest-certification")) }
+                    }
+                    @for (position, version) in entry.conflicting_docs.iter().enumerate() {
+                        tr {
+                            td {
+                                code { (version._rev) }
+                                @if position + 1 == entry.conflicting_docs.len() {
+                                    " " (request_data.lookup("admin-tokens-merge-result"))
+                                }
+                            }
+                            td { (render_flags(version, &request_data)) }
+                            td { (version.certify.len()) }
+                            td { (render_latest_certification(version, &request_data)) }
+                        }
+                    }
+                }
+            }
+        ),
+        PageInfo {
+            name: request_data.lookup("admin-tokens-token-title"),
+            discourage_reload: false,
+            display_majority_info: false,
+            cacheable: false,
+        },
+        &app_data,
+        request_data,
+    ))
+}
+
+/// Checkboxes are only sent when checked
+#[derive(Deserialize)]
+pub struct TokenFlagsForm {
+    is_admin: Option<String>,
+    can_certify: Option<String>,
+    need_certification: Option<String>,
+    revoked: Option<String>,
+}
+
+#[post("/admin/tokens/{token}")]
+pub async fn save_token(
+    app_data: Data<AppData>,
+    request_data: RequestData,
+    path: Path<String>,
+    form: CsrfForm<TokenFlagsForm>,
+) -> Result<HttpResponse, Error> {
+    if !app_data.is_admin(&request_data) {
+        return Err(ErrorForbidden("Only admins can access this page"));
+    }
+    let id = path.into_inner();
+
+    let mut token = app_data
+        .token_store
+        .get_majority_token_by_id(&id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| ErrorNotFound("This majority token doesn't exist"))?;
+
+    let mut admin_flags = token.admin_flags.get().clone();
+    admin_flags.is_admin = form.is_admin.is_some();
+    admin_flags.can_certify = form.can_certify.is_some();
+    admin_flags.need_certification = form.need_certification.is_some();
+    admin_flags.revoked = form.revoked.is_some();
+    if !admin_flags.revoked {
+        admin_flags.revocation_reason = None;
+    }
+    token.admin_flags.update(admin_flags);
+
+    let message = match app_data.token_store.save_majority_token(token).await {
+        Ok(()) => {
+            log::info!("admin flags of the majority token {} modified", id);
+            app_data.invalidate_sessions();
+            Messages::create_with_message(
+                request_data.lookup("admin-tokens-saved"),
+                MessageKind::Success,
+            )
+        }
+        Err(e) => {
+            log::error!(
+                "An error occured while saving the flags of a majority token : {:?}",
+                e
+            );
+            Messages::create_with_message(e.end_user_error_message(), MessageKind::Error)
+        }
+    };
+
+    Ok(HttpResponse::SeeOther()
+        .append_header((
+            "location",
+            app_data
+                .route_simple(&request_data, &["admin", "tokens", &id])
+                .as_str(),
+        ))
+        .with_messages(message)
+        .finish())
+}
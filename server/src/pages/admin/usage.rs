@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use actix_web::{
+    error::{ErrorForbidden, ErrorInternalServerError},
+    get,
+    web::Data,
+    Error, HttpResponse,
+};
+use database::model::NodeUsage;
+use fluent_templates::fluent_bundle::FluentValue;
+use maud::{html, Markup};
+
+use crate::{extractor::RequestData, format_timestamp, wrap_page, AppData, PageInfo};
+
+/// The number of networks a token was used from, marked as a lower bound when it reached the limit
+pub fn render_network_count(usage: &NodeUsage) -> Markup {
+    html!(
+        (usage.networks.len())
+        @if usage.networks.len() >= NodeUsage::MAX_NETWORKS {
+            "+"
+        }
+    )
+}
+
+#[get("/admin/usage")]
+pub async fn usage(
+    app_data: Data<AppData>,
+    request_data: RequestData,
+) -> Result<HttpResponse, Error> {
+    if !app_data.is_admin(&request_data) {
+        return Err(ErrorForbidden("Only admins can access this page"));
+    }
+
+    // the usage counted since the latest flush isn't included otherwise
+    app_data.usage_recorder.flush().await;
+    let mut usage = app_data
+        .usage_recorder
+        .store()
+        .list_token_usage()
+        .await
+        .map_err(|error| {
+            log::error!(
+                "An error occured while reading the usage of majority tokens for the admin console : {:?}",
+                error
+            );
+            ErrorInternalServerError(error.end_user_error_message())
+        })?
+        .into_iter()
+        .map(|usage| (usage._id.clone(), usage.total()))
+        .collect::<Vec<_>>();
+    // a token shared publicly is used from many networks
+    usage.sort_by(|(_, first), (_, second)| {
+        (second.networks.len(), second.connections).cmp(&(first.networks.len(), first.connections))
+    });
+
+    Ok(wrap_page(
+        html!(
+            h1 { (request_data.lookup("admin-usage-header")) }
+            p { (request_data.lookup("admin-usage-presentation")) }
+            p {
+                (request_data.lookup_with_args("admin-usage-count", &HashMap::from([
+                    ("count", FluentValue::from(usage.len())),
+                ])))
+            }
+            table class="taglist" {
+                tr {
+                    th { (request_data.lookup("admin-usage-column-token")) }
+                    th { (request_data.lookup("admin-usage-column-networks")) }
+                    th { (request_data.lookup("admin-usage-column-connections")) }
+                    th { (request_data.lookup("admin-usage-column-requests")) }
+                    th { (request_data.lookup("admin-usage-column-first-seen")) }
+                    th { (request_data.lookup("admin-usage-column-last-seen")) }
+                }
+                @for (id, total) in &usage {
+                    tr {
+                        td { a href=(app_data.route_simple(&request_data, &["admin", "tokens", id]).as_str()) { code { (id) } } }
+                        td { (render_network_count(total)) }
+                        td { (total.connections) }
+                        td { (total.requests) }
+                        td { (format_timestamp(total.first_seen)) }
+                        td { (format_timestamp(total.last_seen)) }
+                    }
+                }
+            }
+        ),
+        PageInfo {
+            name: request_data.lookup("admin-usage-title"),
+            discourage_reload: false,
+            display_majority_info: false,
+            cacheable: false,
+        },
+        &app_data,
+        request_data,
+    ))
+}
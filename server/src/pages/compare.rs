@@ -0,0 +1,256 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    io::Read,
+};
+
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    get,
+    web::{Data, Path},
+    HttpResponse, Result,
+};
+use fluent_templates::fluent_bundle::FluentValue;
+use maud::{html, Markup};
+use similar::TextDiff;
+use zip::ZipArchive;
+
+use crate::{extractor::RequestData, fileref::ReadSeek, wrap_page, AppData, FileRef, PageInfo};
+
+/// Members bigger than this are never diffed, only compared by CRC
+const MAX_DIFFED_MEMBER_SIZE: u64 = 1024 * 1024;
+/// Maximum number of members diffed for a single comparison. The other changed members are only listed.
+const MAX_DIFFED_MEMBERS: usize = 50;
+/// Maximum total uncompressed size of the members read to be diffed for a single comparison, in both archives
+const MAX_TOTAL_DIFFED_SIZE: u64 = 8 * 1024 * 1024;
+
+struct ZipMember {
+    crc32: u32,
+    size: u64,
+}
+
+fn list_members(zip: &mut ZipArchive<Box<dyn ReadSeek>>) -> Result<BTreeMap<String, ZipMember>> {
+    let mut result = BTreeMap::new();
+    for file_id in 0..zip.len() {
+        let file = zip.by_index(file_id).map_err(ErrorInternalServerError)?;
+        if file.is_file() {
+            result.insert(
+                file.name().to_string(),
+                ZipMember {
+                    crc32: file.crc32(),
+                    size: file.size(),
+                },
+            );
+        }
+    }
+    Ok(result)
+}
+
+/// Return the content of the member if it is small enought and look like text
+fn read_text_member(zip: &mut ZipArchive<Box<dyn ReadSeek>>, name: &str) -> Option<String> {
+    let mut file = zip.by_name(name).ok()?;
+    if file.size() > MAX_DIFFED_MEMBER_SIZE {
+        return None;
+    }
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).ok()?;
+    if content.contains(&0) {
+        return None;
+    }
+    String::from_utf8(content).ok()
+}
+
+fn render_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Markup {
+    let diff = TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_name, new_name)
+        .to_string();
+    html! {
+        pre class="diff" {
+            @for line in diff.lines() {
+                @let class = if line.starts_with("+++") || line.starts_with("---") {
+                    "diffheader"
+                } else if line.starts_with("@@") {
+                    "diffhunk"
+                } else if line.starts_with('+') {
+                    "diffadded"
+                } else if line.starts_with('-') {
+                    "diffremoved"
+                } else {
+                    "diffcontext"
+                };
+                span class=(class) { (line) } "\n"
+            }
+        }
+    }
+}
+
+#[get("/compare/{hack_id}/{file_a}/{file_b}")]
+pub async fn compare(
+    app_data: Data<AppData>,
+    path: Path<(String, String, String)>,
+    request_data: RequestData,
+) -> Result<HttpResponse> {
+    let storage = app_data.storage.load();
+    let (hack_id, file_a, file_b) = path.into_inner();
+    let file_ref_a = FileRef::HackFile(hack_id.clone(), file_a.clone());
+    let file_ref_b = FileRef::HackFile(hack_id.clone(), file_b.clone());
+
+    let mut zip_a = ZipArchive::new(file_ref_a.get_reader(&storage, &request_data)?)
+        .map_err(|_| ErrorBadRequest(request_data.lookup("compare-not-an-archive")))?;
+    let mut zip_b = ZipArchive::new(file_ref_b.get_reader(&storage, &request_data)?)
+        .map_err(|_| ErrorBadRequest(request_data.lookup("compare-not-an-archive")))?;
+    // unwrap: get_reader already checked the hack exist
+    let hack = file_ref_a.get_hack(&storage).unwrap();
+
+    let members_a = list_members(&mut zip_a)?;
+    let members_b = list_members(&mut zip_b)?;
+
+    let removed = members_a
+        .keys()
+        .filter(|name| !members_b.contains_key(*name))
+        .collect::<Vec<_>>();
+    let added = members_b
+        .keys()
+        .filter(|name| !members_a.contains_key(*name))
+        .collect::<Vec<_>>();
+    let mut changed = Vec::new();
+    // changed members left over once the diff budget is used up
+    let mut undiffed = Vec::new();
+    let mut diffed_size = 0;
+    let mut unchanged_count = 0;
+    for (name, member_a) in &members_a {
+        if let Some(member_b) = members_b.get(name) {
+            if member_a.crc32 != member_b.crc32 || member_a.size != member_b.size {
+                // members too big to be diffed aren't read, and so don't use the budget
+                let read_size = if member_a.size > MAX_DIFFED_MEMBER_SIZE
+                    || member_b.size > MAX_DIFFED_MEMBER_SIZE
+                {
+                    0
+                } else {
+                    member_a.size + member_b.size
+                };
+                if changed.len() >= MAX_DIFFED_MEMBERS
+                    || diffed_size + read_size > MAX_TOTAL_DIFFED_SIZE
+                {
+                    undiffed.push(name);
+                    continue;
+                }
+                diffed_size += read_size;
+                let diff = match (
+                    read_text_member(&mut zip_a, name),
+                    read_text_member(&mut zip_b, name),
+                ) {
+                    (Some(text_a), Some(text_b)) => Some(render_diff(
+                        &text_a,
+                        &text_b,
+                        &format!("{}/{}", file_a, name),
+                        &format!("{}/{}", file_b, name),
+                    )),
+                    _ => None,
+                };
+                changed.push((name, diff));
+            } else {
+                unchanged_count += 1;
+            }
+        }
+    }
+
+    let mut translation_args = HashMap::new();
+    translation_args.insert(
+        "file_a",
+        FluentValue::String(Cow::Borrowed(file_a.as_str())),
+    );
+    translation_args.insert(
+        "file_b",
+        FluentValue::String(Cow::Borrowed(file_b.as_str())),
+    );
+    translation_args.insert(
+        "hack",
+        FluentValue::String(Cow::Borrowed(hack.data.name.as_str())),
+    );
+    let mut count_args = HashMap::new();
+    count_args.insert("added", FluentValue::from(added.len()));
+    count_args.insert("removed", FluentValue::from(removed.len()));
+    count_args.insert("changed", FluentValue::from(changed.len() + undiffed.len()));
+    count_args.insert("unchanged", FluentValue::from(unchanged_count));
+
+    // the members can only be linked to when zip files can be browsed
+    let member_link = |filename: &str, name: &str, label: &str| {
+        if app_data.features.decompress {
+            html!(a href=(app_data.route_hack_decompress_file(&hack_id, filename, name).as_str()) { (label) })
+        } else {
+            html!((label))
+        }
+    };
+
+    Ok(wrap_page(
+        html!(
+            h1 { (request_data.lookup_with_args("compare-header", &translation_args)) }
+            p {
+                a href=(app_data.route_hack(&request_data, &hack_id).as_str()) { (request_data.lookup("compare-back-to-hack")) }
+            }
+            p { (request_data.lookup_with_args("compare-summary", &count_args)) }
+            @if !added.is_empty() {
+                h2 { (request_data.lookup("compare-added-section")) }
+                ul {
+                    @for name in &added {
+                        li class="diffadded" {
+                            (member_link(&file_b, name, name))
+                        }
+                    }
+                }
+            }
+            @if !removed.is_empty() {
+                h2 { (request_data.lookup("compare-removed-section")) }
+                ul {
+                    @for name in &removed {
+                        li class="diffremoved" {
+                            (member_link(&file_a, name, name))
+                        }
+                    }
+                }
+            }
+            @if !changed.is_empty() {
+                h2 { (request_data.lookup("compare-changed-section")) }
+                @for (name, diff) in &changed {
+                    h3 { (name) }
+                    p {
+                        (member_link(&file_a, name, &file_a))
+                        " → "
+                        (member_link(&file_b, name, &file_b))
+                    }
+                    @if let Some(diff) = diff {
+                        (diff)
+                    } @else {
+                        p { i { (request_data.lookup("compare-binary-member")) } }
+                    }
+                }
+                @if !undiffed.is_empty() {
+                    h3 { (request_data.lookup("compare-undiffed-section")) }
+                    p { (request_data.lookup("compare-undiffed-explanation")) }
+                    ul {
+                        @for name in &undiffed {
+                            li {
+                                (name) " ("
+                                (member_link(&file_a, name, &file_a))
+                                " → "
+                                (member_link(&file_b, name, &file_b))
+                                ")"
+                            }
+                        }
+                    }
+                }
+            }
+        ),
+        PageInfo {
+            name: request_data.lookup_with_args("compare-title", &translation_args),
+            discourage_reload: false,
+            display_majority_info: hack.need_majority_token(&storage.taginfo),
+            cacheable: false,
+        },
+        &app_data,
+        request_data,
+    ))
+}
@@ -1,13 +1,11 @@
-use actix_web::{
-    cookie::Cookie,
-    post,
-    web::{Data, Form},
-    HttpResponse,
-};
+use std::collections::HashMap;
+
+use actix_web::{post, web::Data, HttpRequest, HttpResponse};
+use fluent_templates::fluent_bundle::FluentValue;
 use serde::Deserialize;
 
 use crate::{
-    extractor::RequestData,
+    extractor::{CsrfForm, RequestData},
     message::{MessageKind, Messages},
     AppData, HttpResponseBuilderExtension,
 };
@@ -20,29 +18,60 @@ pub struct FormData {
 
 #[post("/connect_majority_token")]
 pub async fn connect_majority_token(
-    form: Form<FormData>,
+    req: HttpRequest,
+    form: CsrfForm<FormData>,
     request_data: RequestData,
     app_data: Data<AppData>,
 ) -> HttpResponse {
     let mut messages = Messages::default();
     let mut builder = HttpResponse::SeeOther();
-    if let (Some(_), true, _) = app_data
-        .check_validity_of_majority_token(
-            &form.majority_token,
-            &mut messages,
-            &request_data.language,
-        )
-        .await
-    {
+    let client_ip = app_data.rate_limiter.client_ip(&req);
+    // held until the outcome is recorded, so concurrent attempts can't all pass the check before the first failure is counted
+    let _attempt = match client_ip {
+        Some(client_ip) => Some(app_data.rate_limiter.begin_attempt(client_ip).await),
+        None => None,
+    };
+    let remaining_delay = match client_ip {
+        Some(client_ip) => app_data.rate_limiter.remaining_delay(client_ip).await,
+        None => 0,
+    };
+
+    // the token isn't looked up while the visitor has to wait, so guessing stays slow even if it is right
+    if remaining_delay > 0 {
         messages.add_message_from_string(
-            request_data.lookup("message-majority-token-added"),
-            MessageKind::Success,
+            request_data.lookup_with_args(
+                "message-majority-token-rate-limited",
+                &HashMap::from([("seconds", FluentValue::from(remaining_delay))]),
+            ),
+            MessageKind::Error,
         );
-        builder.cookie(Cookie::new(
-            "majority_token",
-            form.majority_token.to_string(),
-        ));
-    };
+    } else {
+        match app_data
+            .open_session(&form.majority_token, &mut messages, &request_data.language)
+            .await
+        {
+            Ok(Some(session)) => {
+                if let Some(client_ip) = client_ip {
+                    app_data.rate_limiter.record_success(client_ip).await;
+                }
+                app_data
+                    .usage_recorder
+                    .record_connection(&session.token_id, app_data.network_of(&req).as_deref());
+                messages.add_message_from_string(
+                    request_data.lookup("message-majority-token-added"),
+                    MessageKind::Success,
+                );
+                builder.cookie(app_data.session_cookie(&session));
+            }
+            Ok(None) => {
+                if let Some(client_ip) = client_ip {
+                    app_data.rate_limiter.record_failure(client_ip).await;
+                }
+            }
+            // the visitor may have sent a valid token, so this isn't counted as a failed guess
+            Err(_) => (),
+        }
+    }
     builder
         .append_header(("location", form.redirect_url.as_str()))
         .with_messages(messages)
@@ -1,23 +1,35 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use actix_web::{get, web::Data, HttpResponse};
+use actix_web::{post, web::Data, HttpResponse};
 use database::{
     get_timestamp,
     model::{MajorityToken, MajorityTokenAdminFlags},
-    FieldWithTime, HackClient, HackClientError,
+    FieldWithTime, HackClientError,
 };
 use maud::html;
 use rand::{distributions::Alphanumeric, Rng};
+use serde::Deserialize;
 
-use crate::{extractor::RequestData, wrap_page, AppData, PageInfo};
+use crate::{
+    extractor::{CsrfForm, RequestData},
+    format_timestamp,
+    pages::renew_majority_token::parse_days,
+    wrap_page, AppData, PageInfo,
+};
 
 const TITLE: &str = "new majority token";
 
-#[get("/create_majority_token")]
+#[derive(Deserialize)]
+pub struct FormData {
+    /// Number of days the new token is valid for. Limited by the configured maximum lifetime, if any.
+    days: Option<String>,
+}
+
+#[post("/create_majority_token")]
 pub async fn create_majority_token(
     app_data: Data<AppData>,
-    hack_client: Data<HackClient>,
-    mut request_data: RequestData,
+    request_data: RequestData,
+    form: CsrfForm<FormData>,
 ) -> HttpResponse {
     fn get_error_response(
         message: &str,
@@ -34,6 +46,7 @@ pub async fn create_majority_token(
                 name: TITLE.to_string(),
                 discourage_reload,
                 display_majority_info: true,
+                cacheable: false,
             },
             app_data,
             request_data,
@@ -58,69 +71,69 @@ pub async fn create_majority_token(
         )
     }
 
-    if let Some(majority_token) = &mut request_data.majority_token {
-        if request_data.can_certify {
-            if get_timestamp() > majority_token.latest_certification_timestamp + 10 {
-                let mut remaining_max_loop: u8 = 100;
-                let new_token_id = loop {
-                    remaining_max_loop = remaining_max_loop.saturating_sub(1);
-                    if remaining_max_loop == 0 {
-                        log::error!("Not able to generate a majority token in less than 100 iteration. Something is certainly wrong !");
-                        return get_error_response(
-                            "Unable to generate a unused token. Somethin on the server is certainly horrible wrong",
-                            false,
-                            &app_data, request_data);
-                    };
-                    let token: String = rand::thread_rng()
-                        .sample_iter(&Alphanumeric)
-                        .take(16)
-                        .map(char::from)
-                        .collect();
-                    match hack_client.get_majority_token(&token).await {
-                        Ok(Some(_)) => continue,
-                        Ok(None) => break token,
-                        Err(e) => {
-                            return get_hack_client_error_response_and_log_error(
-                                e,
-                                &app_data,
-                                request_data,
-                            )
-                        }
-                    };
-                };
+    let days = match parse_days(form.days.as_deref()) {
+        Ok(days) => days,
+        Err(_) => {
+            return get_error_response(
+                "The number of days the token is valid for should be a positive integer",
+                false,
+                &app_data,
+                request_data,
+            )
+        }
+    };
 
-                // 1. add the token to the list of certified token by the current user
-                majority_token.certify.insert(new_token_id.clone());
-                majority_token.latest_certification_timestamp = get_timestamp();
-                match hack_client
-                    .save_majority_token(majority_token.clone())
-                    .await
-                {
-                    Ok(_) => (),
-                    Err(e) => {
-                        return get_hack_client_error_response_and_log_error(
-                            e,
-                            &app_data,
-                            request_data,
-                        )
-                    }
-                };
-                // 2. Create the certified
-                let new_token = MajorityToken {
-                    _id: new_token_id.clone(),
-                    _rev: String::new(),
-                    _deleted: None,
-                    certify: BTreeSet::new(),
-                    admin_flags: FieldWithTime::new(MajorityTokenAdminFlags {
-                        can_certify: true,
-                        need_certification: true,
-                        revoked: false,
-                    }),
-                    latest_certification_timestamp: 0,
-                    _conflicts: Vec::new(),
+    let mut majority_token = match &request_data.session {
+        Some(session) => match app_data
+            .token_store
+            .get_majority_token_by_id(&session.token_id)
+            .await
+        {
+            Ok(Some(majority_token)) => majority_token,
+            Ok(None) => {
+                return get_error_response(
+                    "Your majority token doesn't exist anymore",
+                    false,
+                    &app_data,
+                    request_data,
+                )
+            }
+            Err(e) => {
+                return get_hack_client_error_response_and_log_error(e, &app_data, request_data)
+            }
+        },
+        None => {
+            return get_error_response(
+                "You haven't entered a majority token",
+                false,
+                &app_data,
+                request_data,
+            )
+        }
+    };
+
+    // the session may be slightly outdated, so the flags are checked again
+    let admin_flags = majority_token.admin_flags.get();
+    if admin_flags.can_certify && !admin_flags.revoked {
+        if get_timestamp() > majority_token.latest_certification_timestamp + 10 {
+            let mut remaining_max_loop: u8 = 100;
+            let new_token_secret = loop {
+                remaining_max_loop = remaining_max_loop.saturating_sub(1);
+                if remaining_max_loop == 0 {
+                    log::error!("Not able to generate a majority token in less than 100 iteration. Something is certainly wrong !");
+                    return get_error_response(
+                        "Unable to generate a unused token. Somethin on the server is certainly horrible wrong",
+                        false,
+                        &app_data, request_data);
                 };
-                match hack_client.save_majority_token(new_token).await {
-                    Ok(_) => (),
+                let token: String = rand::thread_rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(16)
+                    .map(char::from)
+                    .collect();
+                match app_data.token_store.get_majority_token(&token).await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) => break token,
                     Err(e) => {
                         return get_hack_client_error_response_and_log_error(
                             e,
@@ -128,57 +141,94 @@ pub async fn create_majority_token(
                             request_data,
                         )
                     }
+                };
+            };
+            // only the hash is stored, the secret is just shown to the user
+            let new_token_id = app_data.token_store.token_hasher().hash(&new_token_secret);
+
+            // 1. add the token to the list of certified token by the current user
+            majority_token.add_certified(&new_token_id, get_timestamp());
+            match app_data
+                .token_store
+                .save_majority_token(majority_token.clone())
+                .await
+            {
+                Ok(_) => (),
+                Err(e) => {
+                    return get_hack_client_error_response_and_log_error(e, &app_data, request_data)
+                }
+            };
+            // 2. Create the certified
+            let new_token = MajorityToken {
+                _id: new_token_id.clone(),
+                _rev: String::new(),
+                _deleted: None,
+                certify: BTreeSet::new(),
+                certified_at: BTreeMap::new(),
+                admin_flags: FieldWithTime::new(MajorityTokenAdminFlags {
+                    can_certify: true,
+                    need_certification: true,
+                    revoked: false,
+                    is_admin: false,
+                    revocation_reason: None,
+                }),
+                latest_certification_timestamp: 0,
+                expires_at: FieldWithTime::new(app_data.token_expiry(days)),
+                _conflicts: Vec::new(),
+            };
+            let expires_at = *new_token.expires_at.get();
+            match app_data.token_store.save_majority_token(new_token).await {
+                Ok(_) => (),
+                Err(e) => {
+                    return get_hack_client_error_response_and_log_error(e, &app_data, request_data)
                 }
+            }
 
-                log::info!(
-                    "new majority token created by {} : {}",
-                    majority_token._id,
-                    new_token_id
-                );
+            log::info!(
+                "new majority token created by {} : {}",
+                majority_token._id,
+                new_token_id
+            );
 
-                wrap_page(
-                    html!(
-                        h1 { (TITLE) }
-                        p { "New majority token created. Share it with the user you certified have more than 18 years, and keep using your own token." }
+            wrap_page(
+                html!(
+                    h1 { (TITLE) }
+                    p { "New majority token created. Share it with the user you certified have more than 18 years, and keep using your own token." }
 
-                        p { "The other person will be able to use it to accept hacks restricting to major user." }
-                        p { "The token is "
-                            b { (new_token_id) }
-                        }
-                    ),
-                    PageInfo {
-                        name: TITLE.to_string(),
-                        discourage_reload: true,
-                        display_majority_info: true,
-                    },
-                    &app_data,
-                    request_data,
-                )
-            } else {
-                get_error_response(
-                    &format!(
-                        "Too soon. You will be able to create a new token in {} seconds",
-                        majority_token
-                            .latest_certification_timestamp
-                            .saturating_add(10)
-                            .saturating_sub(get_timestamp())
-                    ),
-                    true,
-                    &app_data,
-                    request_data,
-                )
-            }
+                    p { "The other person will be able to use it to accept hacks restricting to major user." }
+                    p { "The token is "
+                        b { (new_token_secret) }
+                    }
+                    @if let Some(expires_at) = expires_at {
+                        p { "It will expire on " (format_timestamp(expires_at)) ". You will be able to renew it from the majority page." }
+                    }
+                ),
+                PageInfo {
+                    name: TITLE.to_string(),
+                    discourage_reload: true,
+                    display_majority_info: true,
+                    cacheable: false,
+                },
+                &app_data,
+                request_data,
+            )
         } else {
             get_error_response(
-                "Your token doesn't allow to certify someone",
-                false,
+                &format!(
+                    "Too soon. You will be able to create a new token in {} seconds",
+                    majority_token
+                        .latest_certification_timestamp
+                        .saturating_add(10)
+                        .saturating_sub(get_timestamp())
+                ),
+                true,
                 &app_data,
                 request_data,
             )
         }
     } else {
         get_error_response(
-            "You haven't entered a majority token",
+            "Your token doesn't allow to certify someone",
             false,
             &app_data,
             request_data,
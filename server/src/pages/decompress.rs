@@ -7,7 +7,10 @@ use actix_web::{
 use maud::html;
 use zip::ZipArchive;
 
-use crate::{extractor::RequestData, wrap_page, AppData, FileRef, FileRefGetFileType};
+use crate::{
+    extractor::RequestData, wrap_page, AppData, FileRef, FileRefGetFileType,
+    HttpResponseBuilderExtension,
+};
 
 #[get("/decompress/{hack_id}/{filename}/{tail:.*}")]
 pub async fn decompress(
@@ -20,6 +23,10 @@ pub async fn decompress(
     let file_ref = FileRef::HackFile(hack_id.clone(), filename.clone());
 
     if inner_path.is_empty() {
+        if let Some(response) = request_data.not_modified_response() {
+            return Ok(Either::Left(response));
+        }
+
         let file = file_ref.get_reader(&storage, &request_data)?;
         let mut zip = ZipArchive::new(file).map_err(ErrorInternalServerError)?;
 
@@ -49,12 +56,27 @@ pub async fn decompress(
                 name: format!("browsing {}", filename),
                 discourage_reload: false,
                 display_majority_info: false,
+                cacheable: true,
             },
             &app_data,
             request_data,
         )));
     } else {
         let sub_file = FileRef::Zipped(Box::new(file_ref), inner_path);
-        Ok(Either::Right(sub_file.get_file(&storage, &request_data)?))
+        let validators = sub_file.get_validators(&storage, &request_data)?;
+        if let Some(validators) = &validators {
+            if let Some(response) = request_data.check_not_modified(validators) {
+                return Ok(Either::Left(response));
+            }
+        }
+        match sub_file.get_file(&storage, &request_data)? {
+            Either::Right(content) => Ok(Either::Left(
+                HttpResponse::Ok()
+                    .content_type(mime::APPLICATION_OCTET_STREAM)
+                    .with_validators(validators.as_ref())
+                    .body(content),
+            )),
+            named_file => Ok(Either::Right(named_file)),
+        }
     }
 }
@@ -1,10 +1,11 @@
-use actix_web::{cookie::Cookie, post, web::Form, HttpResponse};
+use actix_web::{post, web::Data, HttpResponse};
 use serde::Deserialize;
 
 use crate::{
-    extractor::RequestData,
+    extractor::{CsrfForm, RequestData},
     message::{MessageKind, Messages},
-    HttpResponseBuilderExtension,
+    session::legacy_majority_token_removal_cookie,
+    AppData, HttpResponseBuilderExtension,
 };
 
 #[derive(Deserialize)]
@@ -14,11 +15,15 @@ pub struct FormData {
 
 #[post("/disconnect_majority_token")]
 pub async fn disconnect_majority_token(
-    form: Form<FormData>,
+    form: CsrfForm<FormData>,
     request_data: RequestData,
+    app_data: Data<AppData>,
 ) -> HttpResponse {
     HttpResponse::SeeOther()
-        .cookie(Cookie::new("majority_token", ""))
+        .cookie(app_data.session_removal_cookie())
+        .cookie(legacy_majority_token_removal_cookie(
+            app_data.root_url.path(),
+        ))
         .append_header(("location", form.redirect_url.as_str()))
         .with_messages(Messages::create_with_message(
             request_data.lookup("message-majority-token-removed"),
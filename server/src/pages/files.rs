@@ -0,0 +1,148 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use actix_web::{get, web::Data, HttpResponse};
+use fluent_templates::fluent_bundle::FluentValue;
+use maud::{html, Markup, PreEscaped};
+use pmd_hack_storage::{Hack, HackFile, Query, QueryIssue};
+use qstring::QString;
+
+use crate::{
+    extractor::RequestData, hidden_hacks_query, wrap_page, AppData, HidingDisplay, PageCacheKey,
+    PageInfo,
+};
+
+/// Longest accepted query text, in bytes, checked before parsing it
+const MAX_QUERY_LENGTH: usize = 2000;
+
+fn make_file_list(
+    files: &[(String, &Hack, &HackFile)],
+    request_data: &RequestData,
+    app_data: &AppData,
+) -> Markup {
+    html! {
+        ul {
+            @for (hack_id, hack, file) in files {
+                li {
+                    a href=(app_data.route_hack(request_data, hack_id).as_str()) { (hack.data.name) }
+                    " : "
+                    a href=(app_data.route_hack_file(hack_id, &file.filename).as_str()) { (file.label) }
+                }
+            }
+        }
+    }
+}
+
+/// Same as [`crate::make_hack_list_hidden`], but for the files matching the query
+fn make_file_list_hidden(query: Query, request_data: &RequestData, app_data: &AppData) -> Markup {
+    let storage = app_data.storage.load();
+    let unfiltered_files = Query::Difference(
+        Box::new(query.clone()),
+        Box::new(hidden_hacks_query(
+            &storage.taginfo.hiding_rules,
+            request_data,
+        )),
+    )
+    .get_matching_files(&storage)
+    .0;
+
+    html! {
+        @if unfiltered_files.is_empty() {
+            p { (request_data.lookup("files-no-result")) }
+        } @else {
+            (make_file_list(&unfiltered_files, request_data, app_data))
+        }
+        @for rule in storage.taginfo.hiding_rules.iter().filter(|rule| request_data.get_hiding_display(rule) == HidingDisplay::Collapse) {
+            @let hidden_files = Query::Intersection(Box::new(query.clone()), Box::new(rule.query.clone())).get_matching_files(&storage).0;
+            @if !hidden_files.is_empty() {
+                details {
+                    summary {
+                        (request_data.lookup(&rule.reason)) " (" (request_data.lookup("hidden-click-to-reveal")) ")"
+                    }
+                    (make_file_list(&hidden_files, request_data, app_data))
+                }
+            }
+        }
+    }
+}
+
+#[get("/files")]
+pub async fn files(app_data: Data<AppData>, request_data: RequestData) -> HttpResponse {
+    if let Some(response) = request_data.not_modified_response() {
+        return response;
+    }
+
+    let query_text = QString::from(request_data.query_string.as_str())
+        .get("q")
+        .unwrap_or("all")
+        .to_string();
+    let query = if query_text.len() > MAX_QUERY_LENGTH {
+        None
+    } else {
+        Some(query_text.parse::<Query>())
+    };
+
+    let content = match &query {
+        Some(Ok(query)) => {
+            let storage = app_data.storage.load();
+            let unknown_tags = query
+                .get_matching_file_set(&storage)
+                .1
+                .into_iter()
+                .filter_map(|issue| match issue {
+                    QueryIssue::UnknownTag(tag) => Some(tag),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            // the query is normalised, so equivalent query text share the same cache entry
+            let cache_route = format!("files?q={}", query);
+            html! {
+                @for tag in &unknown_tags {
+                    p {
+                        (PreEscaped(request_data.lookup_with_args("files-unknown-tag", &HashMap::from([
+                            // the tag come from the user, and the message contain HTML
+                            ("tag", FluentValue::String(Cow::Owned(html! { (tag.0) }.into_string())))
+                        ]))))
+                    }
+                }
+                (app_data.page_cache.get_or_render(PageCacheKey::new(&cache_route, &request_data), || {
+                    make_file_list_hidden(query.clone(), &request_data, &app_data)
+                }))
+            }
+        }
+        Some(Err(error)) => html! {
+            p {
+                (request_data.lookup_with_args("files-invalid-query", &HashMap::from([
+                    ("error", FluentValue::String(Cow::Owned(error.to_string())))
+                ])))
+            }
+        },
+        None => html! {
+            p {
+                (request_data.lookup_with_args("files-query-too-long", &HashMap::from([
+                    ("max", FluentValue::from(MAX_QUERY_LENGTH))
+                ])))
+            }
+        },
+    };
+
+    wrap_page(
+        html!(
+            h1 { (request_data.lookup("files-header")) }
+            p { (PreEscaped(request_data.lookup("files-query-help"))) }
+            form action=(app_data.route_simple(&request_data, &["files"]).as_str()) method="get" {
+                input type="hidden" name="lang" value=(request_data.language.to_string()) {}
+                input type="text" name="q" size="60" value=(query_text) {}
+                input type="submit" value=(request_data.lookup("files-search")) {}
+            }
+            (content)
+        ),
+        PageInfo {
+            name: request_data.lookup("files-title"),
+            discourage_reload: false,
+            display_majority_info: false,
+            cacheable: true,
+        },
+        &app_data,
+        request_data,
+    )
+}
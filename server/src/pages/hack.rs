@@ -1,9 +1,12 @@
+use std::collections::HashMap;
+
 use actix_web::{
     error::ErrorNotFound,
     get,
     web::{Data, Path},
     HttpResponse, Result,
 };
+use fluent_templates::fluent_bundle::FluentValue;
 use maud::{html, PreEscaped};
 
 use crate::{
@@ -17,6 +20,10 @@ pub async fn hack(
     path: Path<String>,
     request_data: RequestData,
 ) -> Result<HttpResponse> {
+    if let Some(response) = request_data.not_modified_response() {
+        return Ok(response);
+    }
+
     let storage = app_data.storage.load();
     let hack_id = path.into_inner();
     let hack = if let Some(hack) = storage.hacks.get(&hack_id) {
@@ -116,14 +123,30 @@ pub async fn hack(
                     p { "no file" }
                 } else {
                     div class="filelist" {
-                        @for file in &hack.data.files {
+                        @for (file_position, file) in hack.data.files.iter().enumerate() {
                             div class="hack" {
                                 h4 { (file.label) }
                                 p {
                                     a href=(app_data.route_hack_file(&hack_id, &file.filename).as_str()) { "download" }
-                                    @if file.filename.ends_with(".zip") {
+                                    @if app_data.features.torrent {
                                         " "
-                                        a href=(app_data.route_hack_decompress_file_list(&request_data, &hack_id, &file.filename).as_str()) { "browse" }
+                                        a href=(app_data.route_hack_torrent(&hack_id, &file.filename).as_str()) { "torrent" }
+                                    }
+                                    @if file.filename.ends_with(".zip") {
+                                        @if app_data.features.decompress {
+                                            " "
+                                            a href=(app_data.route_hack_decompress_file_list(&request_data, &hack_id, &file.filename).as_str()) { "browse" }
+                                        }
+                                        @if app_data.features.compare {
+                                            @if let Some(previous_file) = hack.data.files[..file_position].iter().rev().find(|f| f.filename.ends_with(".zip")) {
+                                                " "
+                                                a href=(app_data.route_compare(&request_data, &hack_id, &previous_file.filename, &file.filename).as_str()) {
+                                                    (request_data.lookup_with_args("compare-with-previous", &HashMap::from([
+                                                        ("previous", FluentValue::from(previous_file.label.as_str()))
+                                                    ])))
+                                                }
+                                            }
+                                        }
                                     }
                                 }
                                 @if let Some(description) = &file.description {
@@ -158,6 +181,7 @@ pub async fn hack(
                 name: format!("Archive of {}", hack.data.name),
                 discourage_reload: false,
                 display_majority_info: major_only_hack,
+                cacheable: true,
             },
             &app_data,
             request_data,
@@ -176,6 +200,7 @@ pub async fn hack(
                 name: format!("Major-only hack {}", hack.data.name),
                 discourage_reload: false,
                 display_majority_info: true,
+                cacheable: true,
             },
             &app_data,
             request_data,
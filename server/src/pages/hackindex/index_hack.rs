@@ -10,7 +10,7 @@ use actix_web::{
 };
 use maud::html;
 
-use crate::{extractor::RequestData, AppData};
+use crate::{extractor::RequestData, AppData, HttpResponseBuilderExtension};
 
 #[get("/index/hacks/{hack_id}")]
 pub async fn index_hack(
@@ -18,6 +18,10 @@ pub async fn index_hack(
     path: Path<String>,
     request_data: RequestData,
 ) -> Result<HttpResponse> {
+    if let Some(response) = request_data.not_modified_response() {
+        return Ok(response);
+    }
+
     let storage = app_data.storage.load();
     let hack_id = path.into_inner();
     if let Some(hack) = storage.hacks.get(&hack_id) {
@@ -57,6 +61,7 @@ pub async fn index_hack(
         let mut response_builder = HttpResponse::build(StatusCode::OK);
         response_builder.content_type(mime::TEXT_HTML_UTF_8);
         response_builder.cookie(Cookie::build("messages", "").finish());
+        response_builder.with_validators(request_data.page_validators().as_ref());
 
         Ok(response_builder.body(body))
     } else {
@@ -1,10 +1,14 @@
 use actix_web::{cookie::Cookie, get, http::StatusCode, web::Data, HttpResponse};
 use maud::html;
 
-use crate::AppData;
+use crate::{extractor::RequestData, AppData, HttpResponseBuilderExtension};
 
 #[get("/index/hacks")]
-pub async fn index_hacks(app_data: Data<AppData>) -> HttpResponse {
+pub async fn index_hacks(app_data: Data<AppData>, request_data: RequestData) -> HttpResponse {
+    if let Some(response) = request_data.not_modified_response() {
+        return response;
+    }
+
     let storage = app_data.storage.load();
     let body = (html! {
         html {
@@ -28,6 +32,7 @@ pub async fn index_hacks(app_data: Data<AppData>) -> HttpResponse {
     let mut response_builder = HttpResponse::build(StatusCode::OK);
     response_builder.content_type(mime::TEXT_HTML_UTF_8);
     response_builder.cookie(Cookie::build("messages", "").finish());
+    response_builder.with_validators(request_data.page_validators().as_ref());
 
     response_builder.body(body.into_boxed_str().into_string())
 }
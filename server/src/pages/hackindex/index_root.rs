@@ -1,10 +1,14 @@
 use actix_web::{cookie::Cookie, get, http::StatusCode, web::Data, HttpResponse};
 use maud::html;
 
-use crate::AppData;
+use crate::{extractor::RequestData, AppData, HttpResponseBuilderExtension};
 
 #[get("/index")]
-pub async fn index_root(app_data: Data<AppData>) -> HttpResponse {
+pub async fn index_root(app_data: Data<AppData>, request_data: RequestData) -> HttpResponse {
+    if let Some(response) = request_data.not_modified_response() {
+        return response;
+    }
+
     let body = (html! {
         html {
             head {
@@ -33,5 +37,6 @@ pub async fn index_root(app_data: Data<AppData>) -> HttpResponse {
     let mut response_builder = HttpResponse::build(StatusCode::OK);
     response_builder.content_type(mime::TEXT_HTML_UTF_8);
     response_builder.cookie(Cookie::build("messages", "").finish());
+    response_builder.with_validators(request_data.page_validators().as_ref());
     response_builder.body(body)
 }
@@ -1,16 +1,27 @@
-use actix_web::{error::ErrorInternalServerError, get, web::Data, Result};
+use actix_web::{error::ErrorInternalServerError, get, web::Data, HttpResponse, Result};
 use log::error;
 
-use crate::AppData;
+use crate::{extractor::RequestData, AppData, HttpResponseBuilderExtension};
 
 #[get("/index/taginfo.json")]
-pub async fn index_taginfo(app_data: Data<AppData>) -> Result<String> {
+pub async fn index_taginfo(
+    app_data: Data<AppData>,
+    request_data: RequestData,
+) -> Result<HttpResponse> {
+    if let Some(response) = request_data.not_modified_response() {
+        return Ok(response);
+    }
+
     let storage = app_data.storage.load();
-    storage.taginfo.to_json().map_err(|e| {
+    let taginfo_json = storage.taginfo.to_json().map_err(|e| {
         error!(
             "An error occured while generating the taginfo json file ! {:?}",
             e
         );
         ErrorInternalServerError("An error occured while generating the JSON file")
-    })
+    })?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::TEXT_PLAIN_UTF_8)
+        .with_validators(request_data.page_validators().as_ref())
+        .body(taginfo_json))
 }
@@ -1,11 +1,31 @@
 use actix_web::{get, web::Data, HttpResponse};
 use maud::{html, PreEscaped};
-use pmd_hack_storage::Query;
 
-use crate::{extractor::RequestData, make_hack_list_hidden, wrap_page, AppData, PageInfo};
+use crate::{
+    extractor::RequestData, make_hack_list_hidden_cached, render_facets, wrap_page, AppData,
+    PageCacheKey, PageInfo, TagSelection,
+};
 
 #[get("/")]
 pub async fn index(app_data: Data<AppData>, request_data: RequestData) -> HttpResponse {
+    if let Some(response) = request_data.not_modified_response() {
+        return response;
+    }
+
+    let selection = TagSelection::from_query_string(&request_data.query_string);
+    let cache_route = format!("index?{}", selection.cache_key());
+    let facets = if app_data.features.facets {
+        Some(app_data.page_cache.get_or_render(
+            PageCacheKey::new(
+                &format!("index-facets?{}", selection.cache_key()),
+                &request_data,
+            ),
+            || render_facets(&selection, &request_data, &app_data),
+        ))
+    } else {
+        None
+    };
+
     // create the main page
     wrap_page(
         html!(
@@ -16,13 +36,30 @@ pub async fn index(app_data: Data<AppData>, request_data: RequestData) -> HttpRe
             p {
                 (PreEscaped(request_data.lookup("landpage-missing")))
             }
-            h2 { (request_data.lookup("landpage-list-of-hacks")) }
-            (make_hack_list_hidden(Query::All, &request_data, &app_data))
+            p {
+                @if app_data.features.files {
+                    a href=(app_data.route_simple(&request_data, &["files"]).as_str()) { (request_data.lookup("landpage-search-files")) }
+                    " · "
+                }
+                a href=(app_data.route_simple(&request_data, &["tags"]).as_str()) { (request_data.lookup("landpage-list-of-tags")) }
+                " · "
+                a href=(app_data.route_simple(&request_data, &["preferences"]).as_str()) { (request_data.lookup("landpage-preferences")) }
+            }
+            div class="withsidebar" {
+                @if let Some(facets) = facets {
+                    (facets)
+                }
+                div class="sidebarcontent" {
+                    h2 { (request_data.lookup("landpage-list-of-hacks")) }
+                    (make_hack_list_hidden_cached(&cache_route, selection.to_query(), &request_data, &app_data))
+                }
+            }
         ),
         PageInfo {
             name: request_data.lookup("landpage-title"),
             discourage_reload: false,
             display_majority_info: false,
+            cacheable: true,
         },
         &app_data,
         request_data,
@@ -1,10 +1,94 @@
 use actix_web::{get, web::Data, HttpResponse};
-use maud::html;
-
-use crate::{extractor::RequestData, wrap_page, AppData, PageInfo};
+use database::{model::MajorityToken, TokenHasher};
+use maud::{html, Markup};
+
+use crate::{extractor::RequestData, format_timestamp, post_form, wrap_page, AppData, PageInfo};
+
+/// The tokens certified by the token of the visitor, each with a form to renew it
+async fn render_certified_tokens(app_data: &AppData, request_data: &RequestData) -> Markup {
+    let session = match &request_data.session {
+        Some(session) => session,
+        None => return html!(),
+    };
+    let certified = match app_data
+        .token_store
+        .get_majority_token_by_id(&session.token_id)
+        .await
+    {
+        Ok(Some(token)) => token.certify,
+        Ok(None) => return html!(),
+        Err(e) => {
+            log::error!(
+                "An error occured while listing the tokens certified by a visitor : {:?}",
+                e
+            );
+            return html!(p { (e.end_user_error_message()) });
+        }
+    };
+    let mut tokens: Vec<MajorityToken> = Vec::new();
+    for id in &certified {
+        match app_data.token_store.get_majority_token_by_id(id).await {
+            Ok(Some(token)) => tokens.push(token),
+            Ok(None) => (),
+            Err(e) => {
+                log::error!(
+                    "An error occured while listing the tokens certified by a visitor : {:?}",
+                    e
+                );
+                return html!(p { (e.end_user_error_message()) });
+            }
+        }
+    }
+    if tokens.is_empty() {
+        return html!();
+    }
+
+    let renew_url = app_data.route_simple(request_data, &["renew_majority_token"]);
+    let this_page = app_data.route_this_page(request_data);
+    html!(
+        h2 { "Tokens you certified" }
+        p { "As their secret isn't kept, tokens are shown by the start of their hash. Renewing a token make it valid for the chosen number of days from now, or as long as this server allow if left empty." }
+        table class="taglist" {
+            tr {
+                th { "Token" }
+                th { "Expire" }
+                th { "Renew" }
+            }
+            @for token in &tokens {
+                tr {
+                    td { code { (token._id.trim_start_matches(TokenHasher::PREFIX).chars().take(8).collect::<String>()) "…" } }
+                    td {
+                        @if token.admin_flags.get().revoked {
+                            "revoked"
+                        } @else {
+                            @match token.expires_at.get() {
+                                Some(expires_at) => (format_timestamp(*expires_at)),
+                                None => "never",
+                            }
+                        }
+                    }
+                    td {
+                        (post_form(&renew_url, request_data, html!(
+                            input type="hidden" name="redirect_url" value=(this_page.as_str()) {}
+                            input type="hidden" name="token" value=(token._id) {}
+                            input type="number" name="days" min="1" placeholder="days" {}
+                            " "
+                            input type="submit" value="Renew" {}
+                        )))
+                    }
+                }
+            }
+        }
+    )
+}
 
 #[get("/majority")]
 pub async fn majority(app_data: Data<AppData>, request_data: RequestData) -> HttpResponse {
+    let certified_tokens = if request_data.can_certify {
+        render_certified_tokens(&app_data, &request_data).await
+    } else {
+        html!()
+    };
     wrap_page(
         html!(
             h1 { "Information about the majority check" }
@@ -51,13 +135,20 @@ pub async fn majority(app_data: Data<AppData>, request_data: RequestData) -> Htt
                 i { "Oh ! And if you know a better solution, I'll be happy to know it too !" }
             }
 
-            @if request_data.majority_token.is_some() {
+            @if request_data.session.is_some() {
                 hr {}
                 p { "You currently have a majority token loaded. More information about it can be found at the bottom of the page." }
                 @if request_data.can_certify {
                     p { "You can create for another user. You should make sure, in your own way, that they are actually major as in the legal definition in France (more than 18 year). They will themselves be able to create other majority tokens." }
 
-                    a href=(app_data.route_simple(&request_data, &["create_majority_token"]).as_str()) { "Create a new majority token" }
+                    (post_form(&app_data.route_simple(&request_data, &["create_majority_token"]), &request_data, html!(
+                        label for="days" { "Valid for (in days, leave empty for as long as this server allow): " }
+                        input type="number" id="days" name="days" min="1" {}
+                        br {}
+                        input type="submit" value="Create a new majority token" {}
+                    )))
+
+                    (certified_tokens)
                 }
             }
         ),
@@ -65,6 +156,7 @@ pub async fn majority(app_data: Data<AppData>, request_data: RequestData) -> Htt
             name: "Info about the majority check".to_string(),
             discourage_reload: false,
             display_majority_info: true,
+            cacheable: false,
         },
         &app_data,
         request_data,
@@ -2,17 +2,24 @@
 
 pub mod majority;
 
+pub mod admin;
+pub mod compare;
 pub mod connect_majority_token;
 pub mod create_majority_token;
 pub mod css;
 pub mod decompress;
 pub mod disconnect_majority_token;
 pub mod file;
+pub mod files;
 pub mod hack;
 pub mod hackindex;
 pub mod index;
+pub mod preferences;
 pub mod reload_storage;
+pub mod renew_majority_token;
 pub mod tagged;
+pub mod tags;
+pub mod torrent;
 
 use actix_web::get;
 
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use actix_web::{get, post, web::Data, HttpResponse};
+use maud::html;
+
+use crate::{
+    extractor::{CsrfForm, RequestData},
+    message::{MessageKind, Messages},
+    post_form, wrap_page, AppData, HidingDisplay, HttpResponseBuilderExtension, PageInfo,
+    Preferences,
+};
+
+#[get("/preferences")]
+pub async fn preferences(app_data: Data<AppData>, request_data: RequestData) -> HttpResponse {
+    if let Some(response) = request_data.not_modified_response() {
+        return response;
+    }
+
+    let storage = app_data.storage.load();
+
+    wrap_page(
+        html!(
+            h1 { (request_data.lookup("preferences-header")) }
+            p { (request_data.lookup("preferences-hiding-presentation")) }
+            (post_form(&app_data.route_simple(&request_data, &["preferences"]), &request_data, html!(
+                @for rule in &storage.taginfo.hiding_rules {
+                    @let current_display = request_data.get_hiding_display(rule);
+                    fieldset {
+                        legend { (request_data.lookup(&rule.reason)) }
+                        @for display in HidingDisplay::ALL {
+                            @let input_id = format!("{}-{}", rule.id, display.id());
+                            @let allowed = Preferences::is_allowed(rule, display, request_data.have_access_to_major_only_content);
+                            input type="radio" id=(input_id) name=(rule.id) value=(display.id()) checked[display == current_display] disabled[!allowed] {}
+                            label for=(input_id) { (request_data.lookup(&format!("preferences-display-{}", display.id()))) }
+                            " "
+                        }
+                        @if rule.requires_majority_token && !request_data.have_access_to_major_only_content {
+                            p class="preferencenote" { (request_data.lookup("preferences-majority-token-needed")) }
+                        }
+                    }
+                }
+                input type="submit" value=(request_data.lookup("preferences-save")) {}
+            )))
+        ),
+        PageInfo {
+            name: request_data.lookup("preferences-title"),
+            discourage_reload: false,
+            display_majority_info: true,
+            cacheable: true,
+        },
+        &app_data,
+        request_data,
+    )
+}
+
+#[post("/preferences")]
+pub async fn save_preferences(
+    app_data: Data<AppData>,
+    request_data: RequestData,
+    form: CsrfForm<HashMap<String, String>>,
+) -> HttpResponse {
+    let storage = app_data.storage.load();
+
+    let mut preferences = request_data.preferences.clone();
+    for rule in &storage.taginfo.hiding_rules {
+        if let Some(display) = form
+            .get(&rule.id)
+            .and_then(|display| HidingDisplay::from_id(display))
+        {
+            // disallowed choices are also ignored when displaying, but they shouldn't replace a previous valid choice
+            if Preferences::is_allowed(
+                rule,
+                display,
+                request_data.have_access_to_major_only_content,
+            ) {
+                preferences.hiding.insert(rule.id.clone(), display);
+            }
+        }
+    }
+
+    HttpResponse::SeeOther()
+        .append_header((
+            "location",
+            app_data
+                .route_simple(&request_data, &["preferences"])
+                .as_str(),
+        ))
+        .cookie(preferences.to_cookie(app_data.root_url.path()))
+        .with_messages(Messages::create_with_message(
+            request_data.lookup("preferences-saved"),
+            MessageKind::Success,
+        ))
+        .finish()
+}
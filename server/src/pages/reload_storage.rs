@@ -1,22 +1,75 @@
-use std::sync::Arc;
+use std::collections::HashMap;
 
-use actix_web::{error::ErrorForbidden, get, web::Data, Error, HttpResponse};
+use actix_web::{error::ErrorForbidden, get, post, web::Data, Error, HttpResponse};
 use display_error_chain::DisplayErrorChain;
+use fluent_templates::fluent_bundle::FluentValue;
 use maud::html;
-use pmd_hack_storage::Storage;
+use serde::Deserialize;
 
-use crate::{extractor::RequestData, wrap_page, AppData, PageInfo};
+use crate::{
+    extractor::{CsrfForm, RequestData},
+    post_form, wrap_page, AppData, PageInfo,
+};
 
 #[get("/reload")]
+pub async fn reload_form(app_data: Data<AppData>, request_data: RequestData) -> HttpResponse {
+    let is_admin = app_data.is_admin(&request_data);
+    wrap_page(
+        html!(
+            h1 { (request_data.lookup("reload-header")) }
+            p { (request_data.lookup("reload-presentation")) }
+            (post_form(&app_data.route_simple(&request_data, &["reload"]), &request_data, html!(
+                @if !is_admin {
+                    label for="password" { (request_data.lookup("reload-password")) }
+                    " "
+                    input type="password" id="password" name="password" required {}
+                    br {}
+                }
+                input type="submit" value=(request_data.lookup("reload-submit")) {}
+            )))
+            // the statistics would be lost by the time the reload result is displayed, as reloading empties the cache
+            @if is_admin {
+                h2 { (request_data.lookup("reload-page-cache-section")) }
+                p {
+                    (request_data.lookup_with_args("reload-page-cache-stats", &HashMap::from([
+                        ("hits", FluentValue::from(app_data.page_cache.hits())),
+                        ("misses", FluentValue::from(app_data.page_cache.misses())),
+                        ("size", FluentValue::from(app_data.page_cache.len())),
+                        ("capacity", FluentValue::from(app_data.page_cache.capacity())),
+                    ])))
+                }
+            }
+        ),
+        PageInfo {
+            name: request_data.lookup("reload-header"),
+            discourage_reload: false,
+            display_majority_info: true,
+            cacheable: false,
+        },
+        &app_data,
+        request_data,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct ReloadForm {
+    /// Not needed for admins
+    password: Option<String>,
+}
+
+#[post("/reload")]
 pub async fn reload(
     app_data: Data<AppData>,
     request_data: RequestData,
+    form: CsrfForm<ReloadForm>,
 ) -> Result<HttpResponse, Error> {
-    if request_data.reload_secret == Some(app_data.secrets.reload_page_password.to_string()) {
-        let new_storage = Storage::load_from_folder(&app_data.archive_folder);
+    if app_data.is_admin(&request_data)
+        || form.password.as_deref() == Some(app_data.secrets.reload_page_password.as_str())
+    {
+        let new_storage = app_data.load_storage();
 
         let error_reporting_status = if new_storage.errors.is_empty() {
-            app_data.storage.store(Arc::new(new_storage));
+            app_data.replace_storage(new_storage);
             html!(p { (request_data.lookup("reload-no-error")) })
         } else {
             let (error_display, status_text, should_reload) = if new_storage
@@ -62,7 +115,7 @@ pub async fn reload(
             };
 
             if should_reload {
-                app_data.storage.store(Arc::new(new_storage));
+                app_data.replace_storage(new_storage);
             }
 
             html! {
@@ -78,6 +131,7 @@ pub async fn reload(
                 name: request_data.lookup("reload-header"),
                 discourage_reload: false,
                 display_majority_info: true,
+                cacheable: false,
             },
             &app_data,
             request_data,
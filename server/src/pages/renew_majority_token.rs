@@ -0,0 +1,105 @@
+use std::num::{NonZeroU64, ParseIntError};
+
+use actix_web::{post, web::Data, HttpResponse};
+use database::HackClientError;
+use serde::Deserialize;
+
+use crate::{
+    extractor::{CsrfForm, RequestData},
+    message::{MessageKind, Messages},
+    AppData, HttpResponseBuilderExtension,
+};
+
+/// The number of days entered in a form, None if left empty. 0 is refused.
+pub fn parse_days(days: Option<&str>) -> Result<Option<u64>, ParseIntError> {
+    match days.map(str::trim) {
+        Some(days) if !days.is_empty() => days.parse::<NonZeroU64>().map(|days| Some(days.get())),
+        _ => Ok(None),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FormData {
+    redirect_url: String,
+    /// The id of the token to renew
+    token: String,
+    /// Number of days the token is valid for, from now. Limited by the configured maximum lifetime, if any.
+    days: Option<String>,
+}
+
+/// Set the expiry of the token of the form. The inner error is the id of the message explaining why it can't be renewed.
+async fn renew(
+    form: &FormData,
+    request_data: &RequestData,
+    app_data: &AppData,
+) -> Result<Result<(), &'static str>, HackClientError> {
+    let days = match parse_days(form.days.as_deref()) {
+        Ok(days) => days,
+        Err(_) => return Ok(Err("message-majority-token-renewal-invalid-days")),
+    };
+    let mut token = match app_data
+        .token_store
+        .get_majority_token_by_id(&form.token)
+        .await?
+    {
+        Some(token) => token,
+        None => return Ok(Err("message-majority-token-does-not-exist")),
+    };
+
+    let session = match &request_data.session {
+        Some(session) => session,
+        None => return Ok(Err("message-majority-token-renewal-forbidden")),
+    };
+    // admins can renew any token, others only the tokens they certified
+    if !app_data.is_admin(request_data) {
+        if !session.can_certify {
+            return Ok(Err("message-majority-token-renewal-forbidden"));
+        }
+        let certifiers = app_data.token_store.get_certifiers(&token._id).await?;
+        if !certifiers
+            .iter()
+            .any(|certifier| certifier._id == session.token_id)
+        {
+            return Ok(Err("message-majority-token-renewal-forbidden"));
+        }
+    }
+
+    token.expires_at.update(app_data.token_expiry(days));
+    let id = token._id.clone();
+    let expires_at = *token.expires_at.get();
+    app_data.token_store.save_majority_token(token).await?;
+    // the renewal may also shorten the token, which the existing sessions must follow
+    app_data.invalidate_sessions();
+    log::info!(
+        "majority token {} renewed by {}, now expiring at {:?}",
+        id,
+        session.token_id,
+        expires_at
+    );
+    Ok(Ok(()))
+}
+
+#[post("/renew_majority_token")]
+pub async fn renew_majority_token(
+    form: CsrfForm<FormData>,
+    request_data: RequestData,
+    app_data: Data<AppData>,
+) -> HttpResponse {
+    let messages = match renew(&form, &request_data, &app_data).await {
+        Ok(Ok(())) => Messages::create_with_message(
+            request_data.lookup("message-majority-token-renewed"),
+            MessageKind::Success,
+        ),
+        Ok(Err(message_id)) => {
+            Messages::create_with_message(request_data.lookup(message_id), MessageKind::Error)
+        }
+        Err(e) => {
+            log::error!("An error occured while renewing a majority token : {:?}", e);
+            Messages::create_with_message(e.end_user_error_message(), MessageKind::Error)
+        }
+    };
+    HttpResponse::SeeOther()
+        .append_header(("location", form.redirect_url.as_str()))
+        .with_messages(messages)
+        .finish()
+}
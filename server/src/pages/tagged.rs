@@ -2,14 +2,30 @@ use std::{borrow::Cow, collections::HashMap};
 
 use actix_web::{
     get,
+    http::header,
     web::{Data, Path},
     HttpResponse,
 };
 use fluent_templates::fluent_bundle::FluentValue;
 use maud::{html, PreEscaped};
-use pmd_hack_storage::{Query, Tag};
+use pmd_hack_storage::{Query, Tag, TagInfo};
+use url::Url;
 
-use crate::{extractor::RequestData, make_hack_list_hidden, wrap_page, AppData, PageInfo};
+use crate::{extractor::RequestData, make_hack_list_hidden_cached, wrap_page, AppData, PageInfo};
+
+/// Redirect aliases and previous names of tags to the page of their canonical tag
+fn redirect_to_canonical_tag(
+    taginfo: &TagInfo,
+    tag: &Tag,
+    route: impl FnOnce(&Tag) -> Url,
+) -> Option<HttpResponse> {
+    let canonical_tag = taginfo.get_canonical_tag(tag)?;
+    Some(
+        HttpResponse::MovedPermanently()
+            .append_header((header::LOCATION, route(canonical_tag).as_str()))
+            .finish(),
+    )
+}
 
 #[get("/tagged/{tag_id}")]
 pub async fn tagged(
@@ -17,11 +33,24 @@ pub async fn tagged(
     path: Path<String>,
     request_data: RequestData,
 ) -> HttpResponse {
+    if let Some(response) = request_data.not_modified_response() {
+        return response;
+    }
+
     let storage = app_data.storage.load();
 
     let tag_id = path.into_inner();
 
+    if let Some(response) =
+        redirect_to_canonical_tag(&storage.taginfo, &Tag(tag_id.clone()), |tag| {
+            app_data.route_hack_list_by_tag(&request_data, tag)
+        })
+    {
+        return response;
+    }
+
     let base_query = Query::AtLeastOneOfTag(vec![Tag(tag_id.clone())]);
+    let cache_route = format!("tagged/{}", tag_id);
 
     let tag_info_single = storage.taginfo.get_tag(&Tag(tag_id.clone()));
 
@@ -39,14 +68,47 @@ pub async fn tagged(
                     }
                 }
             }
-            (make_hack_list_hidden(base_query, &request_data, &app_data))
+            (make_hack_list_hidden_cached(&cache_route, base_query, &request_data, &app_data))
         ),
         PageInfo {
             name: (request_data.lookup_with_args("hack-list-by-tag-title", &translation_args)),
             discourage_reload: false,
             display_majority_info: false, //TODO: enable if the tag is directly or indirectly major-only
+            cacheable: true,
         },
         &app_data,
         request_data,
     )
 }
+
+#[cfg(test)]
+mod test {
+    use super::redirect_to_canonical_tag;
+    use actix_web::{http::header, http::StatusCode};
+    use pmd_hack_storage::{Tag, TagInfo};
+    use url::Url;
+
+    #[test]
+    pub fn test_redirect_to_canonical_tag() {
+        let mut taginfo: TagInfo = serde_json::from_str(
+            r#"{ "tags": { "new": { "renamed-from": ["old"] } }, "categories": {} }"#,
+        )
+        .unwrap();
+        taginfo.rebuild_aliases();
+        let route = |tag: &Tag| {
+            Url::parse("https://example.com/tagged/")
+                .unwrap()
+                .join(&tag.0)
+                .unwrap()
+        };
+
+        let response = redirect_to_canonical_tag(&taginfo, &Tag("old".into()), route).unwrap();
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "https://example.com/tagged/new"
+        );
+        assert!(redirect_to_canonical_tag(&taginfo, &Tag("new".into()), route).is_none());
+        assert!(redirect_to_canonical_tag(&taginfo, &Tag("other".into()), route).is_none());
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+
+use actix_web::{get, web::Data, HttpResponse};
+use maud::html;
+
+use crate::{
+    extractor::RequestData, group_tags_by_category, render_tag, wrap_page, AppData, PageInfo,
+};
+
+#[get("/tags")]
+pub async fn tags(app_data: Data<AppData>, request_data: RequestData) -> HttpResponse {
+    if let Some(response) = request_data.not_modified_response() {
+        return response;
+    }
+
+    let storage = app_data.storage.load();
+
+    let mut all_tags = storage.taginfo.tags.keys().cloned().collect::<HashSet<_>>();
+    all_tags.extend(storage.tags.tag_list.keys().cloned());
+    let groups = group_tags_by_category(&storage.taginfo, all_tags.into_iter().collect());
+
+    wrap_page(
+        html!(
+            h1 { (request_data.lookup("tags-header")) }
+            @for (category, tags) in &groups {
+                h2 {
+                    @match category {
+                        Some(category) => (category),
+                        None => (request_data.lookup("tags-uncategorized")),
+                    }
+                }
+                table class="taglist" {
+                    tr {
+                        th { (request_data.lookup("tags-column-tag")) }
+                        th { (request_data.lookup("tags-column-description")) }
+                        th { (request_data.lookup("tags-column-implies")) }
+                        th { (request_data.lookup("tags-column-usage")) }
+                    }
+                    @for tag in tags {
+                        @let tag_info = storage.taginfo.get_tag(tag);
+                        @let usage = storage.tags.get_hack_for_tag(tag).map_or(0, |hacks| hacks.len());
+                        tr {
+                            td {
+                                (render_tag(tag, &request_data, &app_data))
+                                @if tag_info.is_none() {
+                                    p class="tagwarning" { (request_data.lookup("tags-missing-from-taginfo")) }
+                                }
+                            }
+                            td {
+                                @if let Some(description) = tag_info.and_then(|tag_info| tag_info.description.as_ref()) {
+                                    (description)
+                                }
+                            }
+                            td {
+                                @if let Some(tag_info) = tag_info {
+                                    @for implied_tag in storage.taginfo.orders_tags(tag_info.implies.clone()) {
+                                        (render_tag(&implied_tag, &request_data, &app_data)) " "
+                                    }
+                                }
+                            }
+                            td {
+                                (usage)
+                                @if usage == 0 {
+                                    p class="tagwarning" { (request_data.lookup("tags-unused")) }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        ),
+        PageInfo {
+            name: request_data.lookup("tags-title"),
+            discourage_reload: false,
+            display_majority_info: false,
+            cacheable: true,
+        },
+        &app_data,
+        request_data,
+    )
+}
@@ -0,0 +1,79 @@
+use actix_web::{
+    error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
+    get,
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web::{self, Data, Path},
+    Either, HttpResponse, Result,
+};
+use log::error;
+use safe_join::SafeJoin;
+
+use crate::{extractor::RequestData, AppData, FileRef, FileRefGetFileType};
+
+#[get("/{hack_id}/{filename:.+\\.torrent}")]
+pub async fn torrent(
+    app_data: Data<AppData>,
+    path: Path<(String, String)>,
+    request_data: RequestData,
+) -> Result<Either<HttpResponse, FileRefGetFileType>> {
+    let storage = app_data.storage.load();
+    let (hack_id, torrent_filename) = path.into_inner();
+    let hack = if let Some(hack) = storage.hacks.get(&hack_id) {
+        hack
+    } else {
+        return Err(ErrorNotFound(request_data.lookup("hack-does-not-exist")));
+    };
+
+    // An hack may contain an actual .torrent file
+    if hack
+        .data
+        .files
+        .iter()
+        .any(|file| file.filename == torrent_filename)
+    {
+        let file_ref = FileRef::HackFile(hack_id, torrent_filename);
+        return Ok(Either::Right(file_ref.get_file(&storage, &request_data)?));
+    }
+
+    if hack.need_majority_token(&storage.taginfo) && !request_data.have_access_to_major_only_content
+    {
+        return Err(ErrorForbidden(
+            request_data.lookup("valid-majority-token-needed-to-access-file"),
+        ));
+    }
+
+    // unwrap: the route only match path ending with .torrent
+    let filename = torrent_filename
+        .strip_suffix(".torrent")
+        .unwrap()
+        .to_string();
+    if !hack.data.files.iter().any(|file| file.filename == filename) {
+        return Err(ErrorNotFound(request_data.lookup("file-does-not-exist")));
+    }
+    let file_path = hack
+        .folder
+        .safe_join(&filename)
+        .map_err(|_| ErrorBadRequest(request_data.lookup("path-traversal-detected")))?;
+    let web_seed = app_data.route_hack_file(&hack_id, &filename);
+    drop(storage);
+
+    let torrent_cache = app_data.torrent_cache.clone();
+    let hashes = web::block(move || torrent_cache.get_or_compute(&hack_id, &filename, &file_path))
+        .await
+        .map_err(ErrorInternalServerError)?
+        .map_err(|e| {
+            error!("Can't compute the torrent hashes: {:?}", e);
+            ErrorInternalServerError(request_data.lookup("message-error-file-open"))
+        })?;
+
+    let name = torrent_filename.strip_suffix(".torrent").unwrap();
+    Ok(Either::Left(
+        HttpResponse::Ok()
+            .content_type("application/x-bittorrent")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(torrent_filename.clone())],
+            })
+            .body(hashes.to_torrent_file(name, web_seed.as_str())),
+    ))
+}
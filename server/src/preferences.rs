@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use actix_web::cookie::Cookie;
+use pmd_hack_storage::{HidingMode, HidingRule};
+use serde::{Deserialize, Serialize};
+
+/// How a visitor want the hacks matching an hiding rule to be displayed. Ordered from the least to the most strict.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum HidingDisplay {
+    /// listed with the other hacks
+    Show,
+    /// listed in a collapsed section
+    Collapse,
+    /// not listed
+    Omit,
+}
+
+impl HidingDisplay {
+    pub const ALL: [Self; 3] = [Self::Show, Self::Collapse, Self::Omit];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Show => "show",
+            Self::Collapse => "collapse",
+            Self::Omit => "omit",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|display| display.id() == id)
+    }
+}
+
+impl From<HidingMode> for HidingDisplay {
+    fn from(mode: HidingMode) -> Self {
+        match mode {
+            HidingMode::Collapsed => Self::Collapse,
+            HidingMode::NeverListed => Self::Omit,
+        }
+    }
+}
+
+/// The preferences of a visitor, stored in the `preferences` cookie
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Preferences {
+    /// id of the hiding rule -> how to display the matching hacks
+    #[serde(default)]
+    pub hiding: BTreeMap<String, HidingDisplay>,
+}
+
+impl Preferences {
+    pub const COOKIE_NAME: &'static str = "preferences";
+
+    /// Invalid cookies are ignored, as preferences aren't important enought to warn about them
+    pub fn from_cookie(cookie: &Cookie) -> Self {
+        serde_json::from_str(cookie.value()).unwrap_or_default()
+    }
+
+    pub fn to_cookie(&self, path: &str) -> Cookie<'static> {
+        //unwrap: Preferences should never fail to serialize
+        Cookie::build(Self::COOKIE_NAME, serde_json::to_string(self).unwrap())
+            .path(path.to_string())
+            .permanent()
+            .finish()
+    }
+
+    /// Return whether the visitor can choose `display` for this rule
+    pub fn is_allowed(
+        rule: &HidingRule,
+        display: HidingDisplay,
+        have_access_to_major_only_content: bool,
+    ) -> bool {
+        // a rule can always be made stricter
+        !rule.requires_majority_token
+            || have_access_to_major_only_content
+            || display >= HidingDisplay::from(rule.mode)
+    }
+
+    /// How the hacks matching this rule should be displayed for this visitor
+    pub fn get_display(
+        &self,
+        rule: &HidingRule,
+        have_access_to_major_only_content: bool,
+    ) -> HidingDisplay {
+        match self.hiding.get(&rule.id) {
+            Some(display)
+                if Self::is_allowed(rule, *display, have_access_to_major_only_content) =>
+            {
+                *display
+            }
+            _ => rule.mode.into(),
+        }
+    }
+}
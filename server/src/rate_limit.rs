@@ -0,0 +1,394 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::{
+    http::header::{self, HeaderMap},
+    HttpRequest,
+};
+use database::{get_timestamp, model::FailedAttempts, AttemptStore};
+use tokio::sync::OwnedMutexGuard;
+
+/// How many failures are tolerated from a source before it has to wait between attempts
+pub struct RateLimitPolicy {
+    /// Number of failures allowed without delay
+    pub free_attempts: u32,
+    /// Delay after the first failure over `free_attempts`, in seconds. Doubled for each following failure.
+    pub base_delay: u64,
+    /// Maximum delay between two attempts, in seconds
+    pub max_delay: u64,
+}
+
+impl RateLimitPolicy {
+    /// For a single IP address. A legitimate visitor may mistype their token a few times.
+    pub const PER_IP: Self = Self {
+        free_attempts: 5,
+        base_delay: 2,
+        max_delay: 3600,
+    };
+
+    /// For a whole subnet, to slow down an attacker with many addresses. Shared between the visitors of a network.
+    pub const PER_SUBNET: Self = Self {
+        free_attempts: 20,
+        base_delay: 2,
+        max_delay: 3600,
+    };
+
+    /// The number of seconds before a new attempt is allowed, 0 if it is allowed now
+    pub fn remaining_delay(&self, failed: &FailedAttempts, now: u64) -> u64 {
+        if failed.failures <= self.free_attempts {
+            return 0;
+        }
+        let exponent = (failed.failures - self.free_attempts - 1).min(63);
+        let delay = self
+            .base_delay
+            .saturating_mul(1u64.checked_shl(exponent).unwrap_or(u64::MAX))
+            .min(self.max_delay);
+        failed
+            .latest_failure
+            .saturating_add(delay)
+            .saturating_sub(now)
+    }
+}
+
+/// The keys the failures of an address are counted under: the address itself, then its subnet.
+/// IPv6 addresses are counted per /64, as a single host usually has a whole /64, and their subnet is the /48.
+/// IPv4-mapped IPv6 addresses are counted as the IPv4 address they map, as with a dual-stack socket.
+pub fn rate_limit_keys(ip: IpAddr) -> [(String, &'static RateLimitPolicy); 2] {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            [
+                (format!("ip:{}", ip), &RateLimitPolicy::PER_IP),
+                (
+                    format!("subnet:{}/24", Ipv4Addr::new(a, b, c, 0)),
+                    &RateLimitPolicy::PER_SUBNET,
+                ),
+            ]
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            let prefix = |length: usize| {
+                let mut masked = [0; 8];
+                masked[..length].copy_from_slice(&segments[..length]);
+                Ipv6Addr::from(masked)
+            };
+            [
+                (format!("ip:{}/64", prefix(4)), &RateLimitPolicy::PER_IP),
+                (
+                    format!("subnet:{}/48", prefix(3)),
+                    &RateLimitPolicy::PER_SUBNET,
+                ),
+            ]
+        }
+    }
+}
+
+/// Parse an address of a forwarding header. It may be quoted, followed by a port, and between brackets for IPv6.
+fn parse_forwarded_address(address: &str) -> Option<IpAddr> {
+    let address = address.trim().trim_matches('"');
+    IpAddr::from_str(address)
+        .or_else(|_| SocketAddr::from_str(address).map(|address| address.ip()))
+        .ok()
+        .or_else(|| {
+            address
+                .strip_prefix('[')?
+                .strip_suffix(']')?
+                .parse::<Ipv6Addr>()
+                .ok()
+                .map(IpAddr::V6)
+        })
+}
+
+/// The entries of the `Forwarded` headers, or of the `X-Forwarded-For` ones without them, from the first proxy to the latest.
+/// An element of `Forwarded` without a `for` parameter gives an empty entry.
+fn forwarded_entries(headers: &HeaderMap) -> Vec<&str> {
+    let header_values = |name| {
+        headers
+            .get_all(name)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+    };
+    let forwarded = header_values(header::FORWARDED)
+        .map(|element| {
+            element
+                .split(';')
+                .find_map(|pair| {
+                    let (name, value) = pair.trim().split_once('=')?;
+                    name.eq_ignore_ascii_case("for").then_some(value)
+                })
+                .unwrap_or("")
+        })
+        .collect::<Vec<_>>();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+    header_values(header::HeaderName::from_static("x-forwarded-for")).collect()
+}
+
+/// The address of the visitor in the forwarding headers, `trusted_proxies` entries from the end.
+/// The entries before it are added by the visitor or by proxies that aren't trusted, and may be forged.
+fn forwarded_client_ip(headers: &HeaderMap, trusted_proxies: usize) -> Option<IpAddr> {
+    let entries = forwarded_entries(headers);
+    let index = entries.len().checked_sub(trusted_proxies)?;
+    parse_forwarded_address(entries[index])
+}
+
+/// Slow down the enumeration of majority tokens, by delaying new attempts from addresses and subnets with too many failures.
+/// If the store can't be reached, the attempts are allowed, so an outage of a shared store doesn't lock everyone out.
+pub struct RateLimiter {
+    store: Arc<dyn AttemptStore>,
+    /// A lock for each key with an attempt in progress on this server. Removed once no attempt use it.
+    in_progress: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Number of reverse proxies adding to the `Forwarded` or `X-Forwarded-For` headers. If 0, those are ignored,
+    /// as they can be forged by the visitor when the server is reached directly.
+    trusted_proxies: usize,
+}
+
+impl RateLimiter {
+    /// Failures are forgotten after a day without new ones
+    pub const FORGET_AFTER: u64 = 24 * 3600;
+
+    pub fn new(store: Arc<dyn AttemptStore>, trusted_proxies: usize) -> Self {
+        Self {
+            store,
+            in_progress: Mutex::new(HashMap::new()),
+            trusted_proxies,
+        }
+    }
+
+    /// Wait for the other attempts from the address and its subnet on this server to be over, so the delay is only checked
+    /// once the previous attempts are recorded. The attempt lasts until the returned guard is dropped.
+    /// Servers sharing a store still check their attempts concurrently, so a source can make one attempt per server at once.
+    pub async fn begin_attempt(&self, ip: IpAddr) -> AttemptGuard<'_> {
+        // created first, so the locks already acquired are released if the attempt is cancelled while waiting for the next one
+        let mut attempt = AttemptGuard {
+            rate_limiter: self,
+            guards: Vec::new(),
+        };
+        // always locked in the same order, the address then its subnet, so attempts can't wait for each other
+        for (key, _) in rate_limit_keys(ip) {
+            let lock = self
+                .in_progress
+                .lock()
+                .unwrap()
+                .entry(key.clone())
+                .or_default()
+                .clone();
+            attempt.guards.push((key, lock.lock_owned().await));
+        }
+        attempt
+    }
+
+    /// The address of the visitor, if known. Behind proxies, if their headers don't have the expected number of entries,
+    /// the address of the connection is used, so the attempts are still limited, if only per proxy.
+    pub fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer_ip = req.peer_addr().map(|address| address.ip());
+        if self.trusted_proxies == 0 {
+            return peer_ip;
+        }
+        forwarded_client_ip(req.headers(), self.trusted_proxies).or_else(|| {
+            log::warn!(
+                "no valid address of the visitor {} entries from the end of the forwarding headers, the address of the connection is used",
+                self.trusted_proxies
+            );
+            peer_ip
+        })
+    }
+
+    /// The number of seconds the visitor has to wait before trying another token, 0 if they can try now
+    pub async fn remaining_delay(&self, ip: IpAddr) -> u64 {
+        let now = get_timestamp();
+        let mut remaining_delay = 0;
+        for (key, policy) in rate_limit_keys(ip) {
+            match self.store.get_failed_attempts(&key).await {
+                Ok(Some(failed)) => {
+                    remaining_delay = remaining_delay.max(policy.remaining_delay(&failed, now));
+                }
+                Ok(None) => (),
+                Err(e) => log::error!(
+                    "an error occured while reading the failed attempts of {} : {:?}",
+                    key,
+                    e
+                ),
+            }
+        }
+        if remaining_delay > 0 {
+            log::warn!(
+                "majority token attempt from {} refused, {} seconds remaining before the next allowed attempt",
+                ip,
+                remaining_delay
+            );
+        }
+        remaining_delay
+    }
+
+    pub async fn record_failure(&self, ip: IpAddr) {
+        let now = get_timestamp();
+        for (key, policy) in rate_limit_keys(ip) {
+            match self
+                .store
+                .record_failed_attempt(&key, now, Self::FORGET_AFTER)
+                .await
+            {
+                Ok(failed) if failed.failures > policy.free_attempts => log::warn!(
+                    "{} failed majority token attempts from {}, now delayed by {} seconds",
+                    failed.failures,
+                    key,
+                    policy.remaining_delay(&failed, now)
+                ),
+                Ok(failed) => log::info!(
+                    "{} failed majority token attempts from {}",
+                    failed.failures,
+                    key
+                ),
+                Err(e) => log::error!(
+                    "an error occured while recording a failed attempt of {} : {:?}",
+                    key,
+                    e
+                ),
+            }
+        }
+    }
+
+    /// Only the counter of the address is reset, not the one of its subnet, so an attacker can't reset it with their own token
+    pub async fn record_success(&self, ip: IpAddr) {
+        let (key, _) = &rate_limit_keys(ip)[0];
+        if let Err(e) = self.store.clear_failed_attempts(key).await {
+            log::error!(
+                "an error occured while clearing the failed attempts of {} : {:?}",
+                key,
+                e
+            );
+        }
+    }
+}
+
+/// An attempt in progress, see [`RateLimiter::begin_attempt`]
+pub struct AttemptGuard<'a> {
+    rate_limiter: &'a RateLimiter,
+    guards: Vec<(String, OwnedMutexGuard<()>)>,
+}
+
+impl Drop for AttemptGuard<'_> {
+    fn drop(&mut self) {
+        let mut in_progress = self.rate_limiter.in_progress.lock().unwrap();
+        for (key, guard) in self.guards.drain(..) {
+            drop(guard);
+            // no other attempt holds or waits for it, and none can start while `in_progress` is locked
+            if in_progress
+                .get(&key)
+                .is_some_and(|lock| Arc::strong_count(lock) == 1)
+            {
+                in_progress.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::IpAddr, sync::Arc, time::Duration};
+
+    use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+    use database::{model::FailedAttempts, MemoryAttemptStore};
+
+    use crate::rate_limit::{forwarded_client_ip, rate_limit_keys, RateLimitPolicy, RateLimiter};
+
+    #[test]
+    fn test_rate_limit_keys() {
+        let keys = rate_limit_keys("192.0.2.17".parse::<IpAddr>().unwrap());
+        assert_eq!(keys[0].0, "ip:192.0.2.17");
+        assert_eq!(keys[1].0, "subnet:192.0.2.0/24");
+        let mapped_keys = rate_limit_keys("::ffff:192.0.2.17".parse::<IpAddr>().unwrap());
+        assert_eq!(mapped_keys[0].0, keys[0].0);
+        assert_eq!(mapped_keys[1].0, keys[1].0);
+        let keys = rate_limit_keys("2001:db8:1:2:3:4:5:6".parse::<IpAddr>().unwrap());
+        assert_eq!(keys[0].0, "ip:2001:db8:1:2::/64");
+        assert_eq!(keys[1].0, "subnet:2001:db8:1::/48");
+    }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = RateLimitPolicy::PER_IP;
+        let mut failed = FailedAttempts::new("ip:192.0.2.17");
+        failed.latest_failure = 1000;
+        failed.failures = policy.free_attempts;
+        assert_eq!(policy.remaining_delay(&failed, 1000), 0);
+        failed.failures += 1;
+        assert_eq!(policy.remaining_delay(&failed, 1000), 2);
+        assert_eq!(policy.remaining_delay(&failed, 1001), 1);
+        assert_eq!(policy.remaining_delay(&failed, 1002), 0);
+        failed.failures += 2;
+        assert_eq!(policy.remaining_delay(&failed, 1000), 8);
+        failed.failures = u32::MAX;
+        assert_eq!(policy.remaining_delay(&failed, 1000), policy.max_delay);
+    }
+
+    #[test]
+    fn test_forwarded_client_ip() {
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.append(
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                );
+            }
+            headers
+        };
+        let ip = |text: &str| Some(text.parse::<IpAddr>().unwrap());
+
+        // the first entry is set by the visitor, the last one by the trusted proxy
+        let forged = headers(&[("x-forwarded-for", "198.51.100.1, 192.0.2.17")]);
+        assert_eq!(forwarded_client_ip(&forged, 1), ip("192.0.2.17"));
+        assert_eq!(forwarded_client_ip(&forged, 2), ip("198.51.100.1"));
+        assert_eq!(forwarded_client_ip(&forged, 3), None);
+        let multiple_headers = headers(&[
+            ("x-forwarded-for", "198.51.100.1"),
+            ("x-forwarded-for", "192.0.2.17"),
+        ]);
+        assert_eq!(forwarded_client_ip(&multiple_headers, 1), ip("192.0.2.17"));
+
+        let forwarded = headers(&[
+            ("forwarded", "for=198.51.100.1;proto=https"),
+            (
+                "forwarded",
+                r#"for="[2001:db8::1]:4711", by=192.0.2.1, For=192.0.2.17:80"#,
+            ),
+            ("x-forwarded-for", "203.0.113.1"),
+        ]);
+        assert_eq!(forwarded_client_ip(&forwarded, 1), ip("192.0.2.17"));
+        assert_eq!(forwarded_client_ip(&forwarded, 2), None);
+        assert_eq!(forwarded_client_ip(&forwarded, 3), ip("2001:db8::1"));
+        assert_eq!(forwarded_client_ip(&forwarded, 4), ip("198.51.100.1"));
+        assert_eq!(forwarded_client_ip(&headers(&[]), 1), None);
+    }
+
+    #[tokio::test]
+    async fn test_attempts_are_serialized() {
+        let rate_limiter = RateLimiter::new(Arc::new(MemoryAttemptStore::default()), 0);
+        let ip = |text: &str| text.parse::<IpAddr>().unwrap();
+        let is_waiting = |ip| {
+            let rate_limiter = &rate_limiter;
+            async move {
+                tokio::time::timeout(Duration::from_millis(50), rate_limiter.begin_attempt(ip))
+                    .await
+                    .is_err()
+            }
+        };
+
+        let attempt = rate_limiter.begin_attempt(ip("192.0.2.17")).await;
+        // the same address and another address of the subnet wait, but not other subnets
+        assert!(is_waiting(ip("192.0.2.17")).await);
+        assert!(is_waiting(ip("192.0.2.18")).await);
+        assert!(!is_waiting(ip("198.51.100.1")).await);
+        drop(attempt);
+        // the locks of finished and cancelled attempts aren't kept
+        assert!(rate_limiter.in_progress.lock().unwrap().is_empty());
+        assert!(!is_waiting(ip("192.0.2.18")).await);
+    }
+}
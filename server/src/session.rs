@@ -0,0 +1,227 @@
+use actix_web::{
+    cookie::{time::Duration, Cookie, SameSite},
+    dev::ServiceResponse,
+    http::Error as HttpError,
+    HttpMessage,
+};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// The state of a majority token, as validated against the token store. Kept by the visitor in a signed cookie,
+/// so the store is only queried when the session is revalidated.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Session {
+    /// The id of the majority token, not its secret
+    pub token_id: String,
+    pub can_certify: bool,
+    pub is_admin: bool,
+    /// Timestamp of the latest validation
+    pub validated_at: u64,
+    /// The value of [`AppData::revocation_generation`](crate::AppData::revocation_generation) at the latest validation
+    pub revocation_generation: u64,
+    /// The token need to be entered again after this timestamp
+    pub expires_at: u64,
+}
+
+/// Sign and check the session cookie. Its value is the hex-encoded signature, followed by a dot and the session in JSON.
+pub struct SessionSigner {
+    key: Vec<u8>,
+    /// Whether the cookie should only be sent over HTTPS
+    secure: bool,
+}
+
+impl SessionSigner {
+    pub const COOKIE_NAME: &'static str = "session";
+    pub const CSRF_COOKIE_NAME: &'static str = "csrf";
+
+    pub fn new(key: &[u8], secure: bool) -> Self {
+        Self {
+            key: key.to_vec(),
+            secure,
+        }
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        //unwrap: HMAC accept keys of any length
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).unwrap();
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    fn build_cookie(
+        &self,
+        name: &'static str,
+        value: String,
+        path: &str,
+        max_age: Option<Duration>,
+    ) -> Cookie<'static> {
+        let mut cookie = Cookie::build(name, value)
+            .path(path.to_string())
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(SameSite::Lax)
+            .finish();
+        if let Some(max_age) = max_age {
+            cookie.set_max_age(max_age);
+        }
+        cookie
+    }
+
+    pub fn to_cookie(&self, session: &Session, path: &str, now: u64) -> Cookie<'static> {
+        //unwrap: Session should never fail to serialize
+        let payload = serde_json::to_string(session).unwrap();
+        let mut value = to_hex(&self.mac(&payload).finalize().into_bytes());
+        value.push('.');
+        value.push_str(&payload);
+        let max_age = Duration::seconds(session.expires_at.saturating_sub(now) as i64);
+        self.build_cookie(Self::COOKIE_NAME, value, path, Some(max_age))
+    }
+
+    /// A cookie that replace the session cookie and make the browser forget it
+    pub fn removal_cookie(&self, path: &str) -> Cookie<'static> {
+        self.build_cookie(Self::COOKIE_NAME, String::new(), path, Some(Duration::ZERO))
+    }
+
+    /// A cookie with a new random nonce, from which the CSRF token of the visitor is derived. Kept until the browser is closed.
+    pub fn new_csrf_cookie(&self, path: &str) -> Cookie<'static> {
+        let nonce: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        self.build_cookie(Self::CSRF_COOKIE_NAME, nonce, path, None)
+    }
+
+    /// The token forms must contain to prove they were sent from a page of this site, for the visitor with this nonce.
+    /// It is signed, so it can't be forged by someone that managed to set the nonce cookie of the visitor.
+    pub fn csrf_token(&self, csrf_nonce: &str) -> String {
+        to_hex(
+            &self
+                .mac(&format!("csrf.{}", csrf_nonce))
+                .finalize()
+                .into_bytes(),
+        )
+    }
+
+    pub fn check_csrf_token(&self, csrf_nonce: &str, csrf_token: &str) -> bool {
+        from_hex(csrf_token).is_some_and(|signature| {
+            self.mac(&format!("csrf.{}", csrf_nonce))
+                .verify_slice(&signature)
+                .is_ok()
+        })
+    }
+
+    /// Return None if the signature is invalid (which is also the case if the key changed) or if the session expired
+    pub fn from_cookie(&self, cookie: &Cookie, now: u64) -> Option<Session> {
+        let (signature, payload) = cookie.value().split_once('.')?;
+        self.mac(payload).verify_slice(&from_hex(signature)?).ok()?;
+        let session: Session = serde_json::from_str(payload).ok()?;
+        if session.expires_at <= now {
+            return None;
+        }
+        Some(session)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Return None if the text isn't valid hexadecimal, including when its length is odd
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|start| u8::from_str_radix(text.get(start..start + 2)?, 16).ok())
+        .collect()
+}
+
+/// The cookie that contained the secret of the majority token before sessions were introduced
+pub const LEGACY_MAJORITY_TOKEN_COOKIE_NAME: &str = "majority_token";
+
+/// Make the browser forget the cookie with the secret of the majority token, replaced by the session cookie
+pub fn legacy_majority_token_removal_cookie(path: &str) -> Cookie<'static> {
+    let mut cookie = Cookie::build(LEGACY_MAJORITY_TOKEN_COOKIE_NAME, "")
+        .path(path.to_string())
+        .finish();
+    cookie.make_removal();
+    cookie
+}
+
+/// Cookies to set on the response, as decided while extracting the [`RequestData`](crate::extractor::RequestData).
+/// Stored in the extensions of the request, and added by [`apply_cookie_updates`].
+#[derive(Default, Clone)]
+pub struct CookieUpdates(pub Vec<Cookie<'static>>);
+
+/// Add the [`CookieUpdates`] of the request to the response, unless the handler already set a cookie with the same name
+pub fn apply_cookie_updates<B>(response: &mut ServiceResponse<B>) -> Result<(), HttpError> {
+    let updates = response
+        .request()
+        .extensions_mut()
+        .remove::<CookieUpdates>();
+    for cookie in updates.unwrap_or_default().0 {
+        if !response
+            .response()
+            .cookies()
+            .any(|existing| existing.name() == cookie.name())
+        {
+            response.response_mut().add_cookie(&cookie)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::session::{Session, SessionSigner};
+
+    #[test]
+    fn test_session_cookie() {
+        let signer = SessionSigner::new(b"key", true);
+        let session = Session {
+            token_id: "token".to_string(),
+            can_certify: true,
+            is_admin: false,
+            validated_at: 100,
+            revocation_generation: 1,
+            expires_at: 200,
+        };
+        let cookie = signer.to_cookie(&session, "/", 100);
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(signer.from_cookie(&cookie, 150), Some(session.clone()));
+        // expired
+        assert_eq!(signer.from_cookie(&cookie, 200), None);
+        // signed with another key
+        assert_eq!(
+            SessionSigner::new(b"other key", true).from_cookie(&cookie, 150),
+            None
+        );
+
+        // modified by the visitor
+        let mut tampered = cookie.clone();
+        tampered.set_value(
+            cookie
+                .value()
+                .replace("\"is_admin\":false", "\"is_admin\":true"),
+        );
+        assert_ne!(tampered.value(), cookie.value());
+        assert_eq!(signer.from_cookie(&tampered, 150), None);
+    }
+
+    #[test]
+    fn test_csrf_token() {
+        let signer = SessionSigner::new(b"key", true);
+        let nonce = signer.new_csrf_cookie("/");
+        let token = signer.csrf_token(nonce.value());
+        assert!(signer.check_csrf_token(nonce.value(), &token));
+
+        let other_nonce = signer.new_csrf_cookie("/");
+        assert!(!signer.check_csrf_token(other_nonce.value(), &token));
+        assert!(!signer.check_csrf_token(nonce.value(), ""));
+        assert!(!signer.check_csrf_token(nonce.value(), "not hex"));
+        // can't be computed without the key
+        assert!(!SessionSigner::new(b"other key", true).check_csrf_token(nonce.value(), &token));
+    }
+}
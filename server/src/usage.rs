@@ -0,0 +1,103 @@
+use std::{collections::HashMap, mem::take, net::IpAddr, sync::Arc, sync::Mutex, time::Duration};
+
+use database::{get_timestamp, model::NodeUsage, TokenHasher, UsageStore};
+use rand::{distributions::Alphanumeric, Rng};
+
+use crate::rate_limit::rate_limit_keys;
+
+/// Count how majority tokens are used, in memory, and periodically add the counts to the [`UsageStore`].
+/// The store is thus not queried on every request.
+pub struct UsageRecorder {
+    store: Arc<dyn UsageStore>,
+    /// Identify the counters of this server in the store. Picked randomly on startup.
+    node: String,
+    /// The usage since the latest flush, per token id
+    pending: Mutex<HashMap<String, NodeUsage>>,
+}
+
+impl UsageRecorder {
+    pub fn new(store: Arc<dyn UsageStore>) -> Self {
+        Self {
+            store,
+            node: rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(16)
+                .map(char::from)
+                .collect(),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn store(&self) -> &dyn UsageStore {
+        self.store.as_ref()
+    }
+
+    fn record(&self, token_id: &str, network: Option<&str>, update: impl FnOnce(&mut NodeUsage)) {
+        let mut pending = self.pending.lock().unwrap();
+        let usage = pending.entry(token_id.to_string()).or_default();
+        usage.record(get_timestamp(), network);
+        update(usage);
+    }
+
+    /// The token was entered
+    pub fn record_connection(&self, token_id: &str, network: Option<&str>) {
+        self.record(token_id, network, |usage| usage.connections += 1);
+    }
+
+    /// A request was made with a session of the token
+    pub fn record_request(&self, token_id: &str, network: Option<&str>) {
+        self.record(token_id, network, |usage| usage.requests += 1);
+    }
+
+    /// Add the pending usage to the store. Usage that couldn't be added is kept for the next flush.
+    pub async fn flush(&self) {
+        let pending = take(&mut *self.pending.lock().unwrap());
+        let mut failed_count = 0;
+        for (token_id, usage) in pending {
+            if let Err(e) = self
+                .store
+                .add_token_usage(&token_id, &self.node, &usage)
+                .await
+            {
+                log::error!(
+                    "an error occured while saving the usage of the majority token {} : {:?}",
+                    token_id,
+                    e
+                );
+                failed_count += 1;
+                self.pending
+                    .lock()
+                    .unwrap()
+                    .entry(token_id)
+                    .or_default()
+                    .add(&usage);
+            }
+        }
+        if failed_count > 0 {
+            log::warn!(
+                "the usage of {} majority tokens will be saved on the next flush",
+                failed_count
+            );
+        }
+    }
+
+    /// Flush every `interval`, forever
+    pub async fn flush_periodically(&self, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            self.flush().await;
+        }
+    }
+}
+
+/// Identify the subnet of the address, without revealing it, to count how many networks a token is used from
+pub fn network_key(hasher: &TokenHasher, ip: IpAddr) -> String {
+    let (subnet, _) = &rate_limit_keys(ip)[1];
+    hasher
+        .hash(subnet)
+        .trim_start_matches(TokenHasher::PREFIX)
+        .chars()
+        .take(16)
+        .collect()
+}
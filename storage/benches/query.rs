@@ -0,0 +1,224 @@
+//! Compare the bitset query engine with a naive evaluation over sets of slugs, on a synthetic archive of 10000 hacks
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pmd_hack_storage::{Hack, HackData, HackOrder, Query, Storage, Tag};
+
+const HACK_COUNT: usize = 10_000;
+const TAG_COUNT: usize = 200;
+
+fn make_storage() -> Storage {
+    let mut storage = Storage::default();
+    // a simple deterministic pseudo random generator, to not depend on an external crate
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next_random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    for hack_number in 0..HACK_COUNT {
+        let tag_count = 1 + next_random() % 8;
+        let tags = (0..tag_count)
+            .map(|_| Tag(format!("tag-{}", next_random() % TAG_COUNT as u64)))
+            .collect::<HashSet<_>>();
+        let hack = Hack {
+            data: HackData {
+                name: format!("Hack {}", next_random()),
+                authors: Vec::new(),
+                description: None,
+                tags,
+                source: None,
+                skytemple_db_id: None,
+                screenshots: Vec::new(),
+                links: HashMap::new(),
+                files: Vec::new(),
+            },
+            implied_tags: HashSet::new(),
+            folder: PathBuf::new(),
+        };
+        storage.add_hack(format!("hack-{}", hack_number), hack);
+    }
+    storage.rebuild_index();
+    storage
+}
+
+/// The evaluation used before the bitset engine, that clone a set of slug at every node and sort at the end
+fn naive_matching(query: &Query, storage: &Storage) -> HashSet<String> {
+    match query {
+        Query::AtLeastOneOfTag(tags) => {
+            let mut result = HashSet::new();
+            for tag in tags {
+                if let Some(v) = storage.tags.get_hack_for_tag(tag) {
+                    result.extend(v.iter().cloned());
+                }
+            }
+            result
+        }
+        Query::Or(queries) => {
+            let mut result = HashSet::new();
+            for query in queries {
+                result.extend(naive_matching(query, storage));
+            }
+            result
+        }
+        Query::Difference(first, second) => {
+            let second = naive_matching(second, storage);
+            naive_matching(first, storage)
+                .difference(&second)
+                .cloned()
+                .collect()
+        }
+        Query::Intersection(first, second) => {
+            let second = naive_matching(second, storage);
+            naive_matching(first, storage)
+                .intersection(&second)
+                .cloned()
+                .collect()
+        }
+        Query::AllOfTags(tags) => {
+            let mut result: HashSet<String> = storage.hacks.keys().cloned().collect();
+            for tag in tags {
+                match storage.tags.get_hack_for_tag(tag) {
+                    Some(v) => result.retain(|slug| v.contains(slug)),
+                    None => result.clear(),
+                }
+            }
+            result
+        }
+        Query::Not(query) => {
+            let excluded = naive_matching(query, storage);
+            naive_filter(storage, |slug, _| !excluded.contains(slug))
+        }
+        Query::Author(author) => {
+            let author = author.to_lowercase();
+            naive_filter(storage, |_, hack| {
+                hack.data
+                    .authors
+                    .iter()
+                    .any(|hack_author| hack_author.to_lowercase() == author)
+            })
+        }
+        Query::HasFileWithTag(tag) => {
+            let tag = storage.taginfo.get_canonical_tag(tag).unwrap_or(tag);
+            naive_filter(storage, |_, hack| {
+                hack.data
+                    .files
+                    .iter()
+                    .any(|file| file.tags.contains(tag) || file.implied_tags.contains(tag))
+            })
+        }
+        Query::HasSkytempleId => {
+            naive_filter(storage, |_, hack| hack.data.skytemple_db_id.is_some())
+        }
+        Query::NameMatches(text) => {
+            let text = text.to_lowercase();
+            naive_filter(storage, |_, hack| {
+                hack.data.name.to_lowercase().contains(&text)
+            })
+        }
+        Query::All => storage.hacks.keys().cloned().collect(),
+    }
+}
+
+fn naive_filter<F: Fn(&str, &Hack) -> bool>(storage: &Storage, predicate: F) -> HashSet<String> {
+    storage
+        .hacks
+        .iter()
+        .filter(|(slug, hack)| predicate(slug, hack))
+        .map(|(slug, _)| slug.clone())
+        .collect()
+}
+
+fn naive_get_matching<'a>(query: &Query, storage: &'a Storage) -> Vec<(String, &'a Hack)> {
+    let mut result = naive_matching(query, storage)
+        .into_iter()
+        .map(|slug| {
+            let hack = storage.hacks.get(&slug).unwrap();
+            (slug, hack)
+        })
+        .collect::<Vec<_>>();
+    result.sort_unstable_by(|(slug1, _), (slug2, _)| slug1.cmp(slug2));
+    result
+}
+
+/// A query similar to the one used on the main page, that hide hacks with some tags
+fn hidden_list_query() -> Query {
+    Query::Difference(
+        Box::new(Query::All),
+        Box::new(Query::Or(
+            (0..4)
+                .map(|tag| Query::AtLeastOneOfTag(vec![Tag(format!("tag-{}", tag))]))
+                .collect(),
+        )),
+    )
+}
+
+fn tag_intersection_query() -> Query {
+    Query::Intersection(
+        Box::new(Query::AtLeastOneOfTag(vec![
+            Tag("tag-10".into()),
+            Tag("tag-11".into()),
+            Tag("tag-12".into()),
+        ])),
+        Box::new(Query::AtLeastOneOfTag(vec![Tag("tag-20".into())])),
+    )
+}
+
+/// Mix negation with predicates that aren't indexed and are evaluated hack by hack
+fn unindexed_query() -> Query {
+    Query::Intersection(
+        Box::new(Query::Not(Box::new(Query::AllOfTags(vec![
+            Tag("tag-1".into()),
+            Tag("tag-2".into()),
+        ])))),
+        Box::new(Query::Or(vec![
+            Query::NameMatches("12".into()),
+            Query::Author("nobody".into()),
+            Query::HasFileWithTag(Tag("us".into())),
+            Query::HasSkytempleId,
+        ])),
+    )
+}
+
+fn bench_queries(c: &mut Criterion) {
+    let storage = make_storage();
+    for (name, query) in [
+        ("hidden list", hidden_list_query()),
+        ("tag intersection", tag_intersection_query()),
+        ("unindexed", unindexed_query()),
+    ] {
+        assert_eq!(
+            naive_get_matching(&query, &storage)
+                .into_iter()
+                .map(|(slug, _)| slug)
+                .collect::<Vec<_>>(),
+            query
+                .get_matching(&storage)
+                .0
+                .into_iter()
+                .map(|(slug, _)| slug)
+                .collect::<Vec<_>>()
+        );
+        let mut group = c.benchmark_group(name);
+        group.bench_function("naive", |b| {
+            b.iter(|| naive_get_matching(black_box(&query), &storage))
+        });
+        group.bench_function("bitset, by slug", |b| {
+            b.iter(|| black_box(&query).get_matching(&storage))
+        });
+        group.bench_function("bitset, by name", |b| {
+            b.iter(|| black_box(&query).get_matching_ordered(&storage, HackOrder::Name))
+        });
+        group.bench_function("bitset, count only", |b| {
+            b.iter(|| black_box(&query).get_matching_set(&storage).0.count())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_queries);
+criterion_main!(benches);
@@ -0,0 +1,155 @@
+/// A fixed-size set of hack or file ids (as attributed by [`crate::HackIndex`]), stored as a bitset
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct HackBitSet {
+    words: Vec<u64>,
+    /// number of ids that can be stored in this set
+    len: usize,
+}
+
+impl HackBitSet {
+    /// Create an empty set able to contain the ids in `0..len`
+    pub fn new_empty(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    /// Create a set containing every id in `0..len`
+    pub fn new_full(len: usize) -> Self {
+        let mut result = Self {
+            words: vec![u64::MAX; len.div_ceil(64)],
+            len,
+        };
+        result.clear_unused_bits();
+        result
+    }
+
+    /// Bits after `len` in the last word should always be unset
+    fn clear_unused_bits(&mut self) {
+        let used_bits_in_last_word = self.len % 64;
+        if used_bits_in_last_word != 0 {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << used_bits_in_last_word) - 1;
+            }
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.len
+    }
+
+    /// Panic if `id` is out of the range of this set
+    pub fn insert(&mut self, id: usize) {
+        assert!(id < self.len);
+        self.words[id / 64] |= 1 << (id % 64);
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        id < self.len && self.words[id / 64] & (1 << (id % 64)) != 0
+    }
+
+    pub fn union_with(&mut self, other: &Self) {
+        debug_assert_eq!(self.len, other.len);
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= other_word;
+        }
+    }
+
+    pub fn intersect_with(&mut self, other: &Self) {
+        debug_assert_eq!(self.len, other.len);
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word &= other_word;
+        }
+    }
+
+    /// Remove every element of `other` from this set
+    pub fn difference_with(&mut self, other: &Self) {
+        debug_assert_eq!(self.len, other.len);
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word &= !other_word;
+        }
+    }
+
+    /// Replace this set by every id in its range that isn't in it
+    pub fn complement(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = !*word;
+        }
+        self.clear_unused_bits();
+    }
+
+    pub fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// The number of ids that are in both sets, without allocating their intersection
+    pub fn intersection_count(&self, other: &Self) -> usize {
+        debug_assert_eq!(self.len, other.len);
+        self.words
+            .iter()
+            .zip(other.words.iter())
+            .map(|(word, other_word)| (word & other_word).count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Iterate over the ids of this set, in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(word_position, word)| {
+                let mut word = *word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        None
+                    } else {
+                        let bit = word.trailing_zeros() as usize;
+                        word &= word - 1;
+                        Some(word_position * 64 + bit)
+                    }
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HackBitSet;
+
+    #[test]
+    pub fn test_bitset_operations() {
+        let mut a = HackBitSet::new_empty(130);
+        a.insert(0);
+        a.insert(64);
+        a.insert(129);
+        let mut b = HackBitSet::new_empty(130);
+        b.insert(64);
+        b.insert(100);
+
+        let mut union = a.clone();
+        union.union_with(&b);
+        assert_eq!(union.iter().collect::<Vec<_>>(), vec![0, 64, 100, 129]);
+
+        let mut intersection = a.clone();
+        intersection.intersect_with(&b);
+        assert_eq!(intersection.iter().collect::<Vec<_>>(), vec![64]);
+
+        let mut difference = a.clone();
+        difference.difference_with(&b);
+        assert_eq!(difference.iter().collect::<Vec<_>>(), vec![0, 129]);
+
+        let mut complement = a;
+        complement.complement();
+        assert_eq!(complement.count(), 127);
+        assert!(!complement.contains(129));
+        assert_eq!(HackBitSet::new_full(130).count(), 130);
+    }
+}
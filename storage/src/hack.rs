@@ -63,6 +63,27 @@ pub enum HackLoadError {
     UnreferencedFile(PathBuf),
     #[error("The thing at {0:?} is not a file (an hack folder should only contain files)")]
     FileNotFile(PathBuf),
+    #[error(
+        "The tag {0} is an alias or a previous name of the tag {1}, which should be used instead"
+    )]
+    DeprecatedTagName(Tag, Tag),
+}
+
+/// Replace the aliases in `tags` by their canonical tag, recording the replaced aliases in `replaced`
+fn canonicalize_tags(
+    tags: HashSet<Tag>,
+    taginfo: &TagInfo,
+    replaced: &mut BTreeMap<Tag, Tag>,
+) -> HashSet<Tag> {
+    tags.into_iter()
+        .map(|tag| match taginfo.get_canonical_tag(&tag) {
+            Some(canonical) => {
+                replaced.insert(tag, canonical.clone());
+                canonical.clone()
+            }
+            None => tag,
+        })
+        .collect()
 }
 
 pub struct Hack {
@@ -82,7 +103,22 @@ impl Hack {
         let mut data: HackData = serde_json::from_reader(json_file)
             .map_err(|e| HackLoadError::CantParseReadFile(e, hack_data_path.clone()))?;
 
-        // add implied tags, ensuring there are no infinite loops and it is recursive
+        let mut replaced_aliases = BTreeMap::new();
+        data.tags = canonicalize_tags(
+            std::mem::take(&mut data.tags),
+            taginfo,
+            &mut replaced_aliases,
+        );
+        for file in data.files.iter_mut() {
+            file.tags = canonicalize_tags(
+                std::mem::take(&mut file.tags),
+                taginfo,
+                &mut replaced_aliases,
+            );
+        }
+
+        // add implied tags, ensuring there are no infinite loops and it is recursive.
+        // own tags and implied tags are kept separate.
         let implied_tags = taginfo.get_implied_tags(&data.tags);
         for file in data.files.iter_mut() {
             file.implied_tags = taginfo.get_implied_tags(&file.tags);
@@ -93,7 +129,12 @@ impl Hack {
             folder,
             implied_tags,
         };
-        let non_fatal_errors = result.check_files();
+        let mut non_fatal_errors = result.check_files();
+        non_fatal_errors.extend(
+            replaced_aliases
+                .into_iter()
+                .map(|(alias, canonical)| HackLoadError::DeprecatedTagName(alias, canonical)),
+        );
         Ok((result, non_fatal_errors))
     }
 
@@ -137,7 +178,7 @@ impl Hack {
         let mut r = self.data.tags.clone();
         r.extend(self.implied_tags.clone());
         for files in &self.data.files {
-            r.extend(files.implied_tags.clone());
+            r.extend(files.get_all_tags());
         }
         r
     }
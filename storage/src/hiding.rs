@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Query, Tag};
+
+/// How hacks matching an [`HidingRule`] are displayed in lists
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum HidingMode {
+    /// Listed in a separate section, that is collapsed by default
+    #[default]
+    Collapsed,
+    /// Not listed at all. The hack page is still accessible.
+    NeverListed,
+}
+
+/// A rule to hide some hacks from the lists of hacks, defined in the taginfo file
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct HidingRule {
+    /// An unique identifier for this rule
+    pub id: String,
+    /// The hacks to hide, in the textual syntax of [`Query`]
+    pub query: Query,
+    /// The id of the Fluent message explaining why those hacks are hidden
+    pub reason: String,
+    #[serde(default)]
+    pub mode: HidingMode,
+    /// If true, visitors can't make this rule less strict without a valid majority token
+    #[serde(default)]
+    pub requires_majority_token: bool,
+}
+
+impl HidingRule {
+    fn hide_tag(id: &str, tag: &str, requires_majority_token: bool) -> Self {
+        Self {
+            id: id.to_string(),
+            query: Query::AtLeastOneOfTag(vec![Tag(tag.to_string())]),
+            reason: format!("hidden-reason-{}", id),
+            mode: HidingMode::Collapsed,
+            requires_majority_token,
+        }
+    }
+}
+
+/// The rules used when the taginfo file doesn't define any
+pub fn default_hiding_rules() -> Vec<HidingRule> {
+    vec![
+        HidingRule::hide_tag("likely-offensive", "likely-offensive", false),
+        HidingRule::hide_tag("refused-skytemple", "refused-skytemple", false),
+        HidingRule::hide_tag("deprecated", "deprecated", false),
+        HidingRule::hide_tag("pornographic", "pornographic", true),
+    ]
+}
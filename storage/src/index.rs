@@ -0,0 +1,180 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{Hack, HackBitSet, Tag};
+
+/// The order in which hacks are returned
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HackOrder {
+    /// Sorted by slug
+    Slug,
+    /// Sorted by the name of the hack, then by slug
+    Name,
+}
+
+/// Give a dense id to each hack, and store, for each tag, the set of hack that has it.
+/// Ids are attributed by order of slug.
+///
+/// Each [`HackFile`](crate::HackFile) also get a dense id, attributed by order of hack slug then position in the hack,
+/// so the files of an hack have contiguous ids.
+#[derive(Default)]
+pub struct HackIndex {
+    slugs: Vec<String>,
+    slug_to_id: HashMap<String, usize>,
+    tag_postings: HashMap<Tag, HackBitSet>,
+    /// hack ids, sorted by hack name
+    by_name: Vec<usize>,
+    /// for each file id, the id of its hack and its position in the file list of this hack
+    files: Vec<(usize, usize)>,
+    /// for each hack id, the range of the ids of its files
+    hack_files: Vec<Range<usize>>,
+    /// for each tag, the set of files that has it, either directly, by implication, or because their hack has it
+    file_tag_postings: HashMap<Tag, HackBitSet>,
+}
+
+impl HackIndex {
+    pub fn build(hacks: &HashMap<String, Hack>) -> Self {
+        let mut slugs = hacks.keys().cloned().collect::<Vec<_>>();
+        slugs.sort_unstable();
+        let slug_to_id = slugs
+            .iter()
+            .enumerate()
+            .map(|(id, slug)| (slug.clone(), id))
+            .collect::<HashMap<_, _>>();
+
+        let mut tag_postings: HashMap<Tag, HackBitSet> = HashMap::new();
+        for (id, slug) in slugs.iter().enumerate() {
+            // unwrap: slugs come from the keys of hacks
+            for tag in hacks.get(slug).unwrap().all_tags() {
+                tag_postings
+                    .entry(tag)
+                    .or_insert_with(|| HackBitSet::new_empty(slugs.len()))
+                    .insert(id);
+            }
+        }
+
+        let mut files = Vec::new();
+        let mut hack_files = Vec::with_capacity(slugs.len());
+        for (hack_id, slug) in slugs.iter().enumerate() {
+            let file_count = hacks.get(slug).unwrap().data.files.len();
+            hack_files.push(files.len()..files.len() + file_count);
+            files.extend((0..file_count).map(|file_position| (hack_id, file_position)));
+        }
+
+        let mut file_tag_postings: HashMap<Tag, HackBitSet> = HashMap::new();
+        for (hack_id, slug) in slugs.iter().enumerate() {
+            let hack = hacks.get(slug).unwrap();
+            for (file_id, file) in hack_files[hack_id].clone().zip(hack.data.files.iter()) {
+                let file_tags = file
+                    .tags
+                    .iter()
+                    .chain(file.implied_tags.iter())
+                    .chain(hack.data.tags.iter())
+                    .chain(hack.implied_tags.iter());
+                for tag in file_tags {
+                    file_tag_postings
+                        .entry(tag.clone())
+                        .or_insert_with(|| HackBitSet::new_empty(files.len()))
+                        .insert(file_id);
+                }
+            }
+        }
+
+        // ids are sorted by slug, and sort_by_key is stable, so it is sorted by slug for identical names
+        let mut by_name = (0..slugs.len()).collect::<Vec<_>>();
+        by_name.sort_by_key(|id| &hacks.get(&slugs[*id]).unwrap().data.name);
+
+        Self {
+            slugs,
+            slug_to_id,
+            tag_postings,
+            by_name,
+            files,
+            hack_files,
+            file_tag_postings,
+        }
+    }
+
+    /// The number of indexed hacks
+    pub fn len(&self) -> usize {
+        self.slugs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slugs.is_empty()
+    }
+
+    pub fn get_id(&self, slug: &str) -> Option<usize> {
+        self.slug_to_id.get(slug).copied()
+    }
+
+    /// Panic if the id doesn't exist
+    pub fn get_slug(&self, id: usize) -> &str {
+        &self.slugs[id]
+    }
+
+    /// Return None if no hack has this tag
+    pub fn get_hacks_with_tag(&self, tag: &Tag) -> Option<&HackBitSet> {
+        self.tag_postings.get(tag)
+    }
+
+    pub fn new_empty_set(&self) -> HackBitSet {
+        HackBitSet::new_empty(self.len())
+    }
+
+    pub fn new_full_set(&self) -> HackBitSet {
+        HackBitSet::new_full(self.len())
+    }
+
+    /// The number of indexed files
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Return the id of the hack of this file, and the position of the file in the list of file of this hack.
+    /// Panic if the id doesn't exist.
+    pub fn get_file(&self, file_id: usize) -> (usize, usize) {
+        self.files[file_id]
+    }
+
+    /// Panic if the id doesn't exist
+    pub fn get_files_of_hack(&self, hack_id: usize) -> Range<usize> {
+        self.hack_files[hack_id].clone()
+    }
+
+    /// Return None if no file has this tag
+    pub fn get_files_with_tag(&self, tag: &Tag) -> Option<&HackBitSet> {
+        self.file_tag_postings.get(tag)
+    }
+
+    pub fn new_empty_file_set(&self) -> HackBitSet {
+        HackBitSet::new_empty(self.file_count())
+    }
+
+    pub fn new_full_file_set(&self) -> HackBitSet {
+        HackBitSet::new_full(self.file_count())
+    }
+
+    /// Return the set of every file of the hacks in `hack_set`
+    pub fn hacks_to_files(&self, hack_set: &HackBitSet) -> HackBitSet {
+        let mut result = self.new_empty_file_set();
+        for hack_id in hack_set.iter() {
+            for file_id in self.get_files_of_hack(hack_id) {
+                result.insert(file_id);
+            }
+        }
+        result
+    }
+
+    /// Return the ids in the set, sorted in the given order
+    pub fn sorted_ids(&self, set: &HackBitSet, order: HackOrder) -> Vec<usize> {
+        match order {
+            HackOrder::Slug => set.iter().collect(),
+            HackOrder::Name => self
+                .by_name
+                .iter()
+                .copied()
+                .filter(|id| set.contains(*id))
+                .collect(),
+        }
+    }
+}
@@ -1,15 +1,27 @@
 mod storage;
-pub use storage::{Storage, StorageLoadError};
+pub use storage::{Storage, StorageLoadError, TagLocation};
 
 mod hack;
-pub use hack::{Hack, HackData, HackLoadError};
+pub use hack::{Hack, HackData, HackFile, HackLoadError};
 
 mod tags;
 pub use tags::{Tag, Tags};
 pub const MAJORONLY_CATEGORY: &str = "majoronly";
 
+mod bitset;
+pub use bitset::HackBitSet;
+
+mod index;
+pub use index::{HackIndex, HackOrder};
+
 mod query;
 pub use query::{Query, QueryIssue};
 
+mod hiding;
+pub use hiding::{HidingMode, HidingRule};
+
 mod taginfo;
-pub use taginfo::{TagInfo, TagInfoLoadError};
+pub use taginfo::{TagInfo, TagInfoLoadError, TagLevel};
+
+mod torrent;
+pub use torrent::{TorrentCache, TorrentError, TorrentHashes};
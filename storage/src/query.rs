@@ -1,20 +1,38 @@
-use std::collections::HashSet;
+use std::{fmt, str::FromStr};
 
-use super::{Hack, Storage, Tag};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::{HackBitSet, HackOrder};
+
+use super::{Hack, HackFile, Storage, Tag};
 
 /// An error that happenned while performing a [`Query`]. Multiple may happend in a single query.
+#[derive(Error, Debug, PartialEq, Eq)]
 pub enum QueryIssue {
     /// an unknown tag is used
+    #[error("The tag {0:?} isn't used by any hack")]
     UnknownTag(Tag),
     /// The hack with the given slug can't be found (inconsistencie error with the database, internal error)
+    #[error("The hack {0:?} can't be found")]
     HackNotFound(String),
+    /// The textual representation of the query is invalid. `position` is the byte offset of the error.
+    #[error("Can't parse the query at position {position}: {message}")]
+    ParseError { position: usize, message: String },
 }
 
 /// A query describing the hack that should returned by a search
-#[derive(Clone)]
+///
+/// It can be written as text (see the [`Display`](fmt::Display) and [`FromStr`] implementations) using function calls, like
+/// `and(any_tag(translation, "fan-game"), not(author("someone")))`. The available functions are:
+/// `all`, `any_tag(tag, ...)`, `all_tags(tag, ...)`, `or(query, ...)`, `and(query, query)`, `diff(query, query)`,
+/// `not(query)`, `author("name")`, `file_tag(tag)`, `skytemple_id` and `name("text")`.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Query {
     /// At least one tag of the list should match (or)
     AtLeastOneOfTag(Vec<Tag>),
+    /// Every tag of the list should match
+    AllOfTags(Vec<Tag>),
     /// At least one query should match
     Or(Vec<Query>),
     /// Match every hack
@@ -23,75 +41,718 @@ pub enum Query {
     Difference(Box<Query>, Box<Query>),
     /// The element should match both query
     Intersection(Box<Query>, Box<Query>),
+    /// The element should not match the query
+    Not(Box<Query>),
+    /// One of the authors of the hack is the given one (case insensitive)
+    Author(String),
+    /// At least one file of the hack has the tag (directly or implied)
+    HasFileWithTag(Tag),
+    /// The hack is listed in the SkyTemple hack database
+    HasSkytempleId,
+    /// The name of the hack contains the given text (case insensitive)
+    NameMatches(String),
 }
 
 impl Query {
+    /// Return the matching hacks, sorted by slug
     pub fn get_matching<'a>(
         &self,
         storage: &'a Storage,
     ) -> (Vec<(String, &'a Hack)>, Vec<QueryIssue>) {
-        let (query_result, mut issues) = self.get_list_of_matching_hack(storage);
+        self.get_matching_ordered(storage, HackOrder::Slug)
+    }
 
-        let mut result = Vec::new();
-        for hack_slug in query_result.iter() {
+    pub fn get_matching_ordered<'a>(
+        &self,
+        storage: &'a Storage,
+        order: HackOrder,
+    ) -> (Vec<(String, &'a Hack)>, Vec<QueryIssue>) {
+        let (matching_set, mut issues) = self.get_matching_set(storage);
+
+        let mut result = Vec::with_capacity(matching_set.count());
+        for hack_id in storage.index.sorted_ids(&matching_set, order) {
+            let hack_slug = storage.index.get_slug(hack_id);
             match storage.hacks.get(hack_slug) {
                 Some(hack) => result.push((hack_slug.to_string(), hack)),
                 None => issues.push(QueryIssue::HackNotFound(hack_slug.to_string())),
             };
         }
 
-        result.sort_unstable_by(|(slug1, _), (slug2, _)| slug1.cmp(slug2));
+        (result, issues)
+    }
 
+    /// Return the ids (as attributed by [`Storage::index`]) of the matching hacks
+    pub fn get_matching_set(&self, storage: &Storage) -> (HackBitSet, Vec<QueryIssue>) {
+        let mut issues = Vec::new();
+        let result = self.get_matching_set_inner(storage, QueryLevel::Hack, &mut issues);
         (result, issues)
     }
 
-    fn get_list_of_matching_hack(&self, storage: &Storage) -> (HashSet<String>, Vec<QueryIssue>) {
+    /// Return the matching files, sorted by hack slug then by their position in the hack.
+    ///
+    /// A file matches a tag if it has it directly, by implication, or if its hack directly has it.
+    /// `file_tag` only match the files that have this tag, and the other conditions on hacks match every file of the hack.
+    pub fn get_matching_files<'a>(
+        &self,
+        storage: &'a Storage,
+    ) -> (Vec<(String, &'a Hack, &'a HackFile)>, Vec<QueryIssue>) {
+        let (matching_set, mut issues) = self.get_matching_file_set(storage);
+
+        let mut result = Vec::with_capacity(matching_set.count());
+        for file_id in matching_set.iter() {
+            let (hack_id, file_position) = storage.index.get_file(file_id);
+            let hack_slug = storage.index.get_slug(hack_id);
+            match storage.hacks.get(hack_slug) {
+                Some(hack) => {
+                    result.push((hack_slug.to_string(), hack, &hack.data.files[file_position]))
+                }
+                None => issues.push(QueryIssue::HackNotFound(hack_slug.to_string())),
+            };
+        }
+
+        (result, issues)
+    }
+
+    /// Return the file ids (as attributed by [`Storage::index`]) of the matching files
+    pub fn get_matching_file_set(&self, storage: &Storage) -> (HackBitSet, Vec<QueryIssue>) {
+        let mut issues = Vec::new();
+        let result = self.get_matching_set_inner(storage, QueryLevel::File, &mut issues);
+        (result, issues)
+    }
+
+    fn get_matching_set_inner(
+        &self,
+        storage: &Storage,
+        level: QueryLevel,
+        issues: &mut Vec<QueryIssue>,
+    ) -> HackBitSet {
         match self {
             Self::AtLeastOneOfTag(tags) => {
-                let mut issues = Vec::new();
-                let mut result = HashSet::new();
+                let mut result = level.new_empty_set(storage);
                 for tag in tags {
-                    match storage.tags.get_hack_for_tag(tag) {
-                        Some(v) => {
-                            result.extend(v.iter().cloned());
-                        }
-                        None => issues.push(QueryIssue::UnknownTag(tag.clone())),
-                    };
+                    if let Some(v) = level.get_with_tag(storage, tag, issues) {
+                        result.union_with(v);
+                    }
                 }
-                (result, issues)
+                result
             }
             Self::Or(queries) => {
-                let mut issues = Vec::new();
-                let mut result = HashSet::new();
+                let mut result = level.new_empty_set(storage);
                 for query in queries {
-                    let (r, i) = query.get_list_of_matching_hack(storage);
-                    result.extend(r);
-                    issues.extend(i);
+                    result.union_with(&query.get_matching_set_inner(storage, level, issues));
                 }
-                (result, issues)
+                result
             }
             Query::Difference(first_query, second_query) => {
-                let (first_result, mut issues) = first_query.get_list_of_matching_hack(storage);
-                let (second_result, second_issues) =
-                    second_query.get_list_of_matching_hack(storage);
-                let result = first_result
-                    .difference(&second_result)
-                    .cloned()
-                    .collect::<HashSet<String>>();
-                issues.extend(second_issues);
-                (result, issues)
+                let mut result = first_query.get_matching_set_inner(storage, level, issues);
+                result
+                    .difference_with(&second_query.get_matching_set_inner(storage, level, issues));
+                result
             }
             Query::Intersection(query_1, query_2) => {
-                let (result, mut issues) = query_1.get_list_of_matching_hack(storage);
-                let (result_2, issues_2) = query_2.get_list_of_matching_hack(storage);
-                let result = result.intersection(&result_2).cloned().collect();
-                issues.extend(issues_2);
-                (result, issues)
-            }
-            &Query::All => (
-                storage.hacks.keys().map(|x| x.to_string()).collect(),
-                Vec::new(),
+                let mut result = query_1.get_matching_set_inner(storage, level, issues);
+                result.intersect_with(&query_2.get_matching_set_inner(storage, level, issues));
+                result
+            }
+            Self::AllOfTags(tags) => {
+                let mut result = level.new_full_set(storage);
+                for tag in tags {
+                    match level.get_with_tag(storage, tag, issues) {
+                        Some(v) => result.intersect_with(v),
+                        None => result = level.new_empty_set(storage),
+                    };
+                }
+                result
+            }
+            Query::Not(query) => {
+                let mut result = query.get_matching_set_inner(storage, level, issues);
+                result.complement();
+                result
+            }
+            Query::Author(author) => {
+                let author = author.to_lowercase();
+                level.convert_hack_set(
+                    storage,
+                    Self::filter_hacks(storage, issues, |hack| {
+                        hack.data
+                            .authors
+                            .iter()
+                            .any(|hack_author| hack_author.to_lowercase() == author)
+                    }),
+                )
+            }
+            Query::HasFileWithTag(tag) => {
+                let tag = storage.taginfo.get_canonical_tag(tag).unwrap_or(tag);
+                match level {
+                    QueryLevel::Hack => Self::filter_hacks(storage, issues, |hack| {
+                        hack.data.files.iter().any(|file| file_has_tag(file, tag))
+                    }),
+                    QueryLevel::File => {
+                        Self::filter_files(storage, issues, |file| file_has_tag(file, tag))
+                    }
+                }
+            }
+            Query::HasSkytempleId => level.convert_hack_set(
+                storage,
+                Self::filter_hacks(storage, issues, |hack| hack.data.skytemple_db_id.is_some()),
+            ),
+            Query::NameMatches(text) => {
+                let text = text.to_lowercase();
+                level.convert_hack_set(
+                    storage,
+                    Self::filter_hacks(storage, issues, |hack| {
+                        hack.data.name.to_lowercase().contains(&text)
+                    }),
+                )
+            }
+            Query::All => level.new_full_set(storage),
+        }
+    }
+
+    /// Return the set of hacks for which `predicate` is true. Used for conditions that aren't indexed.
+    fn filter_hacks<F: Fn(&Hack) -> bool>(
+        storage: &Storage,
+        issues: &mut Vec<QueryIssue>,
+        predicate: F,
+    ) -> HackBitSet {
+        let mut result = storage.index.new_empty_set();
+        for hack_id in 0..storage.index.len() {
+            let hack_slug = storage.index.get_slug(hack_id);
+            match storage.hacks.get(hack_slug) {
+                Some(hack) => {
+                    if predicate(hack) {
+                        result.insert(hack_id)
+                    }
+                }
+                None => issues.push(QueryIssue::HackNotFound(hack_slug.to_string())),
+            }
+        }
+        result
+    }
+
+    /// Return the set of files for which `predicate` is true
+    fn filter_files<F: Fn(&HackFile) -> bool>(
+        storage: &Storage,
+        issues: &mut Vec<QueryIssue>,
+        predicate: F,
+    ) -> HackBitSet {
+        let mut result = storage.index.new_empty_file_set();
+        for hack_id in 0..storage.index.len() {
+            let hack_slug = storage.index.get_slug(hack_id);
+            match storage.hacks.get(hack_slug) {
+                Some(hack) => {
+                    for (file_id, file) in storage
+                        .index
+                        .get_files_of_hack(hack_id)
+                        .zip(hack.data.files.iter())
+                    {
+                        if predicate(file) {
+                            result.insert(file_id)
+                        }
+                    }
+                }
+                None => issues.push(QueryIssue::HackNotFound(hack_slug.to_string())),
+            }
+        }
+        result
+    }
+}
+
+fn file_has_tag(file: &HackFile, tag: &Tag) -> bool {
+    file.tags.contains(tag) || file.implied_tags.contains(tag)
+}
+
+/// Whether a query is evaluated to a set of hacks or a set of files
+#[derive(Clone, Copy)]
+enum QueryLevel {
+    Hack,
+    File,
+}
+
+impl QueryLevel {
+    fn new_empty_set(self, storage: &Storage) -> HackBitSet {
+        match self {
+            Self::Hack => storage.index.new_empty_set(),
+            Self::File => storage.index.new_empty_file_set(),
+        }
+    }
+
+    fn new_full_set(self, storage: &Storage) -> HackBitSet {
+        match self {
+            Self::Hack => storage.index.new_full_set(),
+            Self::File => storage.index.new_full_file_set(),
+        }
+    }
+
+    /// Return the elements that have this tag, or None (and report an issue if the tag is unknown) if there are none.
+    fn get_with_tag<'a>(
+        self,
+        storage: &'a Storage,
+        tag: &Tag,
+        issues: &mut Vec<QueryIssue>,
+    ) -> Option<&'a HackBitSet> {
+        let tag = storage.taginfo.get_canonical_tag(tag).unwrap_or(tag);
+        let result = match self {
+            Self::Hack => storage.index.get_hacks_with_tag(tag),
+            Self::File => storage.index.get_files_with_tag(tag),
+        };
+        // an hack without file may have a tag no file has
+        if result.is_none() && storage.index.get_hacks_with_tag(tag).is_none() {
+            issues.push(QueryIssue::UnknownTag(tag.clone()));
+        }
+        result
+    }
+
+    /// Convert the result of an hack-level condition to this level
+    fn convert_hack_set(self, storage: &Storage, hack_set: HackBitSet) -> HackBitSet {
+        match self {
+            Self::Hack => hack_set,
+            Self::File => storage.index.hacks_to_files(&hack_set),
+        }
+    }
+}
+
+/// Write a tag as a bare word when possible, or as a quoted string otherwise
+fn write_tag(f: &mut fmt::Formatter<'_>, tag: &Tag) -> fmt::Result {
+    if !tag.0.is_empty() && tag.0.chars().all(is_bare_word_char) {
+        write!(f, "{}", tag.0)
+    } else {
+        write_string(f, &tag.0)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn write_list<T, F>(f: &mut fmt::Formatter<'_>, elements: &[T], mut write_element: F) -> fmt::Result
+where
+    F: FnMut(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+{
+    for (position, element) in elements.iter().enumerate() {
+        if position != 0 {
+            write!(f, ", ")?;
+        }
+        write_element(f, element)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::AtLeastOneOfTag(tags) => {
+                write!(f, "any_tag(")?;
+                write_list(f, tags, write_tag)?;
+                write!(f, ")")
+            }
+            Self::AllOfTags(tags) => {
+                write!(f, "all_tags(")?;
+                write_list(f, tags, write_tag)?;
+                write!(f, ")")
+            }
+            Self::Or(queries) => {
+                write!(f, "or(")?;
+                write_list(f, queries, |f, query| query.fmt(f))?;
+                write!(f, ")")
+            }
+            Self::Intersection(query_1, query_2) => write!(f, "and({}, {})", query_1, query_2),
+            Self::Difference(query_1, query_2) => write!(f, "diff({}, {})", query_1, query_2),
+            Self::Not(query) => write!(f, "not({})", query),
+            Self::Author(author) => {
+                write!(f, "author(")?;
+                write_string(f, author)?;
+                write!(f, ")")
+            }
+            Self::HasFileWithTag(tag) => {
+                write!(f, "file_tag(")?;
+                write_tag(f, tag)?;
+                write!(f, ")")
+            }
+            Self::HasSkytempleId => write!(f, "skytemple_id"),
+            Self::NameMatches(text) => {
+                write!(f, "name(")?;
+                write_string(f, text)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn is_bare_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'
+}
+
+/// The maximum nesting depth of a parsed query, so deeply nested user input can't overflow the stack
+pub const MAX_QUERY_DEPTH: usize = 64;
+
+/// A recursive descent parser for the textual representation of [`Query`]
+struct QueryParser<'a> {
+    source: &'a str,
+    position: usize,
+    /// Number of queries currently being parsed, up to [`MAX_QUERY_DEPTH`]
+    depth: usize,
+}
+
+impl<'a> QueryParser<'a> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, QueryIssue> {
+        Err(QueryIssue::ParseError {
+            position: self.position,
+            message: message.into(),
+        })
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.source[self.position..].chars().next()
+    }
+
+    /// Consume `expected` if it is the next character, returning whether it was present
+    fn consume(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), QueryIssue> {
+        if self.consume(expected) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(found) => self.error(format!("expected {:?}, found {:?}", expected, found)),
+                None => self.error(format!(
+                    "expected {:?}, found the end of the query",
+                    expected
+                )),
+            }
+        }
+    }
+
+    fn parse_word(&mut self) -> Result<&'a str, QueryIssue> {
+        self.skip_whitespace();
+        let rest = &self.source[self.position..];
+        let len = rest
+            .find(|c: char| !is_bare_word_char(c))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return match rest.chars().next() {
+                Some(found) => self.error(format!("expected a word, found {:?}", found)),
+                None => self.error("expected a word, found the end of the query"),
+            };
+        }
+        self.position += len;
+        Ok(&rest[..len])
+    }
+
+    fn parse_string(&mut self) -> Result<String, QueryIssue> {
+        self.expect('"')?;
+        let mut result = String::new();
+        let mut chars = self.source[self.position..].char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += offset + 1;
+                    return Ok(result);
+                }
+                '\\' => match chars.next() {
+                    Some((_, escaped @ ('"' | '\\'))) => result.push(escaped),
+                    Some((escaped_offset, escaped)) => {
+                        self.position += escaped_offset;
+                        return self.error(format!("invalid escape sequence \\{}", escaped));
+                    }
+                    None => break,
+                },
+                c => result.push(c),
+            }
+        }
+        self.position = self.source.len();
+        self.error("unterminated string")
+    }
+
+    fn parse_tag(&mut self) -> Result<Tag, QueryIssue> {
+        if self.peek() == Some('"') {
+            Ok(Tag(self.parse_string()?))
+        } else {
+            Ok(Tag(self.parse_word()?.to_string()))
+        }
+    }
+
+    /// Parse a comma separated list of elements, up to and including the closing parenthesis
+    fn parse_list<T, F>(&mut self, mut parse_element: F) -> Result<Vec<T>, QueryIssue>
+    where
+        F: FnMut(&mut Self) -> Result<T, QueryIssue>,
+    {
+        let mut result = Vec::new();
+        if self.consume(')') {
+            return Ok(result);
+        }
+        loop {
+            result.push(parse_element(self)?);
+            if self.consume(')') {
+                return Ok(result);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_two_queries(&mut self) -> Result<(Box<Query>, Box<Query>), QueryIssue> {
+        let query_1 = self.parse_query()?;
+        self.expect(',')?;
+        let query_2 = self.parse_query()?;
+        self.expect(')')?;
+        Ok((Box::new(query_1), Box::new(query_2)))
+    }
+
+    fn parse_query(&mut self) -> Result<Query, QueryIssue> {
+        self.skip_whitespace();
+        if self.depth >= MAX_QUERY_DEPTH {
+            return self.error(format!(
+                "the query is nested more than {} levels deep",
+                MAX_QUERY_DEPTH
+            ));
+        }
+        self.depth += 1;
+        let query = self.parse_function();
+        self.depth -= 1;
+        query
+    }
+
+    fn parse_function(&mut self) -> Result<Query, QueryIssue> {
+        let function_position = self.position;
+        let function = self.parse_word()?;
+        match function {
+            "all" => return Ok(Query::All),
+            "skytemple_id" => return Ok(Query::HasSkytempleId),
+            _ => (),
+        };
+        self.expect('(')?;
+        Ok(match function {
+            "any_tag" => Query::AtLeastOneOfTag(self.parse_list(Self::parse_tag)?),
+            "all_tags" => Query::AllOfTags(self.parse_list(Self::parse_tag)?),
+            "or" => Query::Or(self.parse_list(Self::parse_query)?),
+            "and" => {
+                let (query_1, query_2) = self.parse_two_queries()?;
+                Query::Intersection(query_1, query_2)
+            }
+            "diff" => {
+                let (query_1, query_2) = self.parse_two_queries()?;
+                Query::Difference(query_1, query_2)
+            }
+            "not" => {
+                let query = self.parse_query()?;
+                self.expect(')')?;
+                Query::Not(Box::new(query))
+            }
+            "author" => {
+                let author = self.parse_string()?;
+                self.expect(')')?;
+                Query::Author(author)
+            }
+            "file_tag" => {
+                let tag = self.parse_tag()?;
+                self.expect(')')?;
+                Query::HasFileWithTag(tag)
+            }
+            "name" => {
+                let text = self.parse_string()?;
+                self.expect(')')?;
+                Query::NameMatches(text)
+            }
+            unknown => {
+                self.position = function_position;
+                return self.error(format!("unknown function {:?}", unknown));
+            }
+        })
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryIssue;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = QueryParser {
+            source,
+            position: 0,
+            depth: 0,
+        };
+        let query = parser.parse_query()?;
+        if parser.peek().is_some() {
+            return parser.error("unexpected text after the end of the query");
+        }
+        Ok(query)
+    }
+}
+
+/// Queries are serialized in their textual form
+impl Serialize for Query {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Query {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Query, QueryIssue, MAX_QUERY_DEPTH};
+    use crate::{Hack, HackData, HackFile, Storage, Tag};
+    use std::{
+        collections::{HashMap, HashSet},
+        path::PathBuf,
+    };
+
+    fn make_hack(hack_tags: &[&str], files: &[(&str, &[&str])]) -> Hack {
+        let to_tags = |tags: &[&str]| tags.iter().map(|tag| Tag(tag.to_string())).collect();
+        Hack {
+            data: HackData {
+                name: "hack".into(),
+                authors: Vec::new(),
+                description: None,
+                tags: to_tags(hack_tags),
+                source: None,
+                skytemple_db_id: None,
+                screenshots: Vec::new(),
+                links: HashMap::new(),
+                files: files
+                    .iter()
+                    .map(|(filename, tags)| HackFile {
+                        label: filename.to_string(),
+                        description: None,
+                        tags: to_tags(tags),
+                        implied_tags: HashSet::new(),
+                        filename: filename.to_string(),
+                    })
+                    .collect(),
+            },
+            implied_tags: HashSet::new(),
+            folder: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    pub fn test_file_query() {
+        let mut storage = Storage::default();
+        storage.add_hack(
+            "b".into(),
+            make_hack(&["xdelta"], &[("b-eu", &["eu"]), ("b-us", &["us"])]),
+        );
+        storage.add_hack(
+            "a".into(),
+            make_hack(&[], &[("a-eu", &["eu", "xdelta"]), ("a-us", &["us"])]),
+        );
+        storage.add_hack("c".into(), make_hack(&["xdelta"], &[]));
+        storage.rebuild_index();
+
+        let filenames = |query: &str| {
+            let (files, issues) = query.parse::<Query>().unwrap().get_matching_files(&storage);
+            assert!(issues.is_empty());
+            files
+                .iter()
+                .map(|(_, _, file)| file.filename.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(filenames("all_tags(eu, xdelta)"), vec!["a-eu", "b-eu"]);
+        assert_eq!(filenames("not(file_tag(eu))"), vec!["a-us", "b-us"]);
+        assert_eq!(filenames("diff(all, any_tag(xdelta))"), vec!["a-us"]);
+        assert_eq!(
+            Query::AllOfTags(vec![Tag("eu".into()), Tag("xdelta".into())])
+                .get_matching(&storage)
+                .0
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    pub fn test_query_text_round_trip() {
+        let query = Query::Or(vec![
+            Query::Intersection(
+                Box::new(Query::AllOfTags(vec![
+                    Tag("translation".into()),
+                    Tag("with space".into()),
+                ])),
+                Box::new(Query::Not(Box::new(Query::Author(
+                    "Some \"One\" \\o/".into(),
+                )))),
+            ),
+            Query::Difference(
+                Box::new(Query::AtLeastOneOfTag(Vec::new())),
+                Box::new(Query::HasFileWithTag(Tag("patch".into()))),
             ),
+            Query::HasSkytempleId,
+            Query::NameMatches("Explorers".into()),
+            Query::All,
+        ]);
+        let text = query.to_string();
+        assert_eq!(
+            text,
+            r#"or(and(all_tags(translation, "with space"), not(author("Some \"One\" \\o/"))), diff(any_tag(), file_tag(patch)), skytemple_id, name("Explorers"), all)"#
+        );
+        assert_eq!(text.parse::<Query>(), Ok(query));
+        assert_eq!(
+            " and ( all ,not(any_tag( a,\"b\" ) ) ) ".parse::<Query>(),
+            Ok(Query::Intersection(
+                Box::new(Query::All),
+                Box::new(Query::Not(Box::new(Query::AtLeastOneOfTag(vec![
+                    Tag("a".into()),
+                    Tag("b".into())
+                ]))))
+            ))
+        );
+    }
+
+    #[test]
+    pub fn test_query_parse_errors() {
+        let error_position = |text: &str| match text.parse::<Query>() {
+            Err(QueryIssue::ParseError { position, .. }) => position,
+            other => panic!("expected a parse error for {:?}, got {:?}", text, other),
+        };
+        assert_eq!(error_position("and(all, unknown(a))"), 9);
+        assert_eq!(error_position("not(all"), 7);
+        assert_eq!(error_position("all all"), 4);
+        assert_eq!(error_position("author(\"abc"), 11);
+        assert_eq!(error_position("author(abc)"), 7);
+        assert_eq!(error_position(""), 0);
+    }
+
+    #[test]
+    pub fn test_query_nesting_limit() {
+        let nested = |depth: usize| {
+            let mut text = "not(".repeat(depth - 1);
+            text.push_str("all");
+            text.push_str(&")".repeat(depth - 1));
+            text
+        };
+        assert!(nested(MAX_QUERY_DEPTH).parse::<Query>().is_ok());
+        match nested(MAX_QUERY_DEPTH + 1).parse::<Query>() {
+            Err(QueryIssue::ParseError { position, .. }) => {
+                assert_eq!(position, 4 * MAX_QUERY_DEPTH)
+            }
+            other => panic!("expected a parse error, got {:?}", other),
         }
+        // Far beyond the limit, this would overflow the stack without it
+        assert!(nested(1_000_000).parse::<Query>().is_err());
     }
 }
@@ -1,7 +1,8 @@
-use super::{Hack, HackLoadError, TagInfoLoadError, Tags};
-use crate::TagInfo;
+use super::{Hack, HackLoadError, Tag, TagInfoLoadError, Tags};
+use crate::{HackIndex, TagInfo, TagLevel};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
     fs::metadata,
     io,
     path::{Path, PathBuf},
@@ -30,6 +31,34 @@ pub enum StorageLoadError {
     TagForFileLacking(String, String, String),
     #[error("The tag {0} is used by {1:?} but doesn't exist in the taginfo file")]
     MissingTag(String, HashSet<String>),
+    #[error("A warning is present for the tag info file")]
+    TagInfoWarning(#[source] TagInfoLoadError),
+    #[error("The hack {0} does not contain any tag in the category {1}")]
+    TagForHackLacking(String, String),
+    #[error("{0} has multiple tags in the category {1}, which allow at most one: {2:?}")]
+    TooManyTagsInCategory(TagLocation, String, Vec<Tag>),
+    #[error("{0} has both the tags {1} and {2}, which are exclusive")]
+    ExclusiveTags(TagLocation, Tag, Tag),
+    #[error("{0} has the tag {1}, but tags of the category {2} should only be put on {3}")]
+    TagOnWrongLevel(TagLocation, Tag, String, TagLevel),
+}
+
+/// Where a tag was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagLocation {
+    /// slug of the hack
+    Hack(String),
+    /// slug of the hack, and filename of the file
+    File(String, String),
+}
+
+impl fmt::Display for TagLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hack(hack) => write!(f, "The hack {}", hack),
+            Self::File(hack, file) => write!(f, "The file {} of the hack {}", file, hack),
+        }
+    }
 }
 
 impl StorageLoadError {
@@ -37,6 +66,12 @@ impl StorageLoadError {
         match self {
             Self::MissingTag(_, _) => true,
             Self::TagForFileLacking(_, _, _) => true,
+            Self::TagInfoWarning(_) => true,
+            Self::TagForHackLacking(_, _) => true,
+            Self::TooManyTagsInCategory(_, _, _) => true,
+            Self::ExclusiveTags(_, _, _) => true,
+            Self::TagOnWrongLevel(_, _, _, _) => true,
+            Self::NonFatalErrorLoadHack(HackLoadError::DeprecatedTagName(_, _), _) => true,
             _ => false,
         }
     }
@@ -48,6 +83,8 @@ pub struct Storage {
     pub tags: Tags,
     pub taginfo: TagInfo,
     pub errors: Vec<StorageLoadError>,
+    /// Used to perform [`crate::Query`]. Should be rebuilt with [`Storage::rebuild_index`] after hacks are added.
+    pub index: HackIndex,
 }
 
 impl Storage {
@@ -63,18 +100,36 @@ impl Storage {
                 TagInfo::default()
             }
         };
+        errors.extend(
+            taginfo
+                .check()
+                .into_iter()
+                .map(StorageLoadError::TagInfoWarning),
+        );
         let mut result = Self {
             hacks: HashMap::new(),
             tags: Tags::default(),
             taginfo,
             errors,
+            index: HackIndex::default(),
         };
         let hacks_folder = root_folder.join("hacks");
         result.load_all_hacks_from_folder(&hacks_folder);
         result.warn_missing_tags();
+        let unused_tags = result.taginfo.check_unused_tags(&result.tags);
+        result.errors.extend(
+            unused_tags
+                .into_iter()
+                .map(StorageLoadError::TagInfoWarning),
+        );
+        result.rebuild_index();
         result
     }
 
+    pub fn rebuild_index(&mut self) {
+        self.index = HackIndex::build(&self.hacks);
+    }
+
     pub fn load_all_hacks_from_folder(&mut self, hacks_folder: &Path) {
         let dir_entry_iterator = match std::fs::read_dir(&hacks_folder) {
             Ok(v) => v,
@@ -151,7 +206,8 @@ impl Storage {
         self.add_hack(hack_name.to_string(), hack);
     }
 
-    fn add_hack(&mut self, name: String, hack: Hack) {
+    /// Add an hack, checking its tags. [`Storage::rebuild_index`] should be called once every hacks are added.
+    pub fn add_hack(&mut self, name: String, hack: Hack) {
         for tag in hack.all_tags() {
             self.tags.add_hack_with_tag(&tag, name.clone());
         }
@@ -179,10 +235,130 @@ impl Storage {
             }
         }
 
+        self.check_category_constraints(&name, &hack);
+
         // actually insert the hack
         self.hacks.insert(name, hack);
     }
 
+    fn get_category_id(&self, tag: &Tag) -> Option<&str> {
+        self.taginfo
+            .get_tag(tag)
+            .and_then(|tag_info| tag_info.category.as_deref())
+    }
+
+    /// Check the constraints of the categories and tags of the taginfo file, other than `required_for_file`
+    fn check_category_constraints(&mut self, name: &str, hack: &Hack) {
+        let mut errors = Vec::new();
+
+        let mut hack_tags = hack.data.tags.clone();
+        hack_tags.extend(hack.implied_tags.iter().cloned());
+
+        let mut categories = self.taginfo.categories.iter().collect::<Vec<_>>();
+        categories.sort_unstable_by_key(|(category_id, _)| *category_id);
+        for (category_id, category_info) in categories {
+            if category_info.required_for_hack
+                && !hack_tags
+                    .iter()
+                    .any(|tag| self.get_category_id(tag) == Some(category_id))
+            {
+                errors.push(StorageLoadError::TagForHackLacking(
+                    name.to_string(),
+                    category_id.to_string(),
+                ));
+            }
+        }
+
+        // for each level: its location, the tags directly on it, and every tag of it
+        let mut scopes = vec![(
+            TagLocation::Hack(name.to_string()),
+            TagLevel::Hack,
+            &hack.data.tags,
+            hack_tags.clone(),
+        )];
+        for file in &hack.data.files {
+            scopes.push((
+                TagLocation::File(name.to_string(), file.filename.to_string()),
+                TagLevel::File,
+                &file.tags,
+                file.get_all_tags(),
+            ));
+        }
+
+        for (location, level, own_tags, all_tags) in scopes {
+            let all_tags = all_tags.into_iter().collect::<BTreeSet<_>>();
+
+            let mut tags_by_category: BTreeMap<&str, Vec<Tag>> = BTreeMap::new();
+            for tag in &all_tags {
+                if let Some(category_id) = self.get_category_id(tag) {
+                    tags_by_category
+                        .entry(category_id)
+                        .or_default()
+                        .push(tag.clone());
+                }
+            }
+            for (category_id, tags) in tags_by_category {
+                if tags.len() > 1
+                    && self
+                        .taginfo
+                        .categories
+                        .get(category_id)
+                        .is_some_and(|category_info| category_info.at_most_one)
+                {
+                    errors.push(StorageLoadError::TooManyTagsInCategory(
+                        location.clone(),
+                        category_id.to_string(),
+                        tags,
+                    ));
+                }
+            }
+
+            // the relation is symmetric, so each pair is reported once
+            let mut exclusive_pairs = BTreeSet::new();
+            for tag in &all_tags {
+                if let Some(tag_info) = self.taginfo.get_tag(tag) {
+                    for other_tag in &tag_info.exclusive_with {
+                        if all_tags.contains(other_tag) {
+                            exclusive_pairs
+                                .insert((tag.min(other_tag).clone(), tag.max(other_tag).clone()));
+                        }
+                    }
+                }
+            }
+            for (tag_1, tag_2) in exclusive_pairs {
+                errors.push(StorageLoadError::ExclusiveTags(
+                    location.clone(),
+                    tag_1,
+                    tag_2,
+                ));
+            }
+
+            let mut own_tags = own_tags.iter().collect::<Vec<_>>();
+            own_tags.sort_unstable();
+            for tag in own_tags {
+                if let Some(category_id) = self.get_category_id(tag) {
+                    if let Some(only_on) = self
+                        .taginfo
+                        .categories
+                        .get(category_id)
+                        .and_then(|category_info| category_info.only_on)
+                    {
+                        if only_on != level {
+                            errors.push(StorageLoadError::TagOnWrongLevel(
+                                location.clone(),
+                                tag.clone(),
+                                category_id.to_string(),
+                                only_on,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        self.errors.extend(errors);
+    }
+
     fn warn_missing_tags(&mut self) {
         for (tag, users) in &self.tags.tag_list {
             if self.taginfo.get_tag(tag).is_none() {
@@ -192,3 +368,96 @@ impl Storage {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Storage, StorageLoadError, TagLocation};
+    use crate::{Hack, HackData, HackFile, Tag, TagInfo, TagLevel};
+    use std::{
+        collections::{HashMap, HashSet},
+        path::PathBuf,
+    };
+
+    fn to_tags(tags: &[&str]) -> HashSet<Tag> {
+        tags.iter().map(|tag| Tag(tag.to_string())).collect()
+    }
+
+    #[test]
+    pub fn test_category_constraints() {
+        let taginfo: TagInfo = serde_json::from_str(
+            r#"{
+                "tags": {
+                    "eu": { "category": "region" },
+                    "us": { "category": "region" },
+                    "explorers": { "category": "game" },
+                    "complete": { "exclusive-with": ["demo"] },
+                    "demo": {}
+                },
+                "categories": {
+                    "region": { "background-color": "", "border-color": "", "at-most-one": true, "only-on": "file" },
+                    "game": { "background-color": "", "border-color": "", "required-for-hack": true }
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut storage = Storage {
+            taginfo,
+            ..Storage::default()
+        };
+        storage.add_hack(
+            "hack".into(),
+            Hack {
+                data: HackData {
+                    name: "hack".into(),
+                    authors: Vec::new(),
+                    description: None,
+                    tags: to_tags(&["eu", "complete", "demo"]),
+                    source: None,
+                    skytemple_db_id: None,
+                    screenshots: Vec::new(),
+                    links: HashMap::new(),
+                    files: vec![HackFile {
+                        label: "patch".into(),
+                        description: None,
+                        tags: to_tags(&["eu", "us"]),
+                        implied_tags: HashSet::new(),
+                        filename: "patch.xdelta".into(),
+                    }],
+                },
+                implied_tags: HashSet::new(),
+                folder: PathBuf::new(),
+            },
+        );
+
+        let hack_location = TagLocation::Hack("hack".into());
+        let file_location = TagLocation::File("hack".into(), "patch.xdelta".into());
+        let errors = storage
+            .errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>();
+        let expected = [
+            StorageLoadError::TagForHackLacking("hack".into(), "game".into()),
+            StorageLoadError::ExclusiveTags(
+                hack_location.clone(),
+                Tag("complete".into()),
+                Tag("demo".into()),
+            ),
+            StorageLoadError::TagOnWrongLevel(
+                hack_location,
+                Tag("eu".into()),
+                "region".into(),
+                TagLevel::File,
+            ),
+            StorageLoadError::TooManyTagsInCategory(
+                file_location,
+                "region".into(),
+                vec![Tag("eu".into()), Tag("us".into())],
+            ),
+        ]
+        .iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>();
+        assert_eq!(errors, expected);
+    }
+}
@@ -1,9 +1,13 @@
-use crate::Tag;
+use crate::{
+    hiding::{default_hiding_rules, HidingRule},
+    Tag, Tags,
+};
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::Ordering,
     collections::{HashMap, HashSet},
+    fmt,
     fs::File,
     io,
     path::{Path, PathBuf},
@@ -16,23 +20,109 @@ pub enum TagInfoLoadError {
     CantOpenFile(#[source] io::Error, PathBuf),
     #[error("Can't read or parse the file {1:?} as a taginfo file")]
     CantParseReadFile(#[source] serde_json::Error, PathBuf),
+    #[error("The implications of tags form a cycle: {}", format_tag_path(.0))]
+    ImplicationCycle(Vec<Tag>),
+    #[error("The tag {0} implies the tag {1}, which isn't defined")]
+    UnknownImpliedTag(Tag, Tag),
+    #[error("The tag {0} is in the category {1:?}, which isn't defined")]
+    UnknownCategory(Tag, String),
+    #[error("The tag {0} is defined, but isn't used by any hack")]
+    UnusedTag(Tag),
+    #[error("The alias {0} of the tag {1} is also a name of the tag {2}")]
+    ConflictingAlias(Tag, Tag, Tag),
+    #[error("Multiple hiding rules have the id {0:?}")]
+    DuplicateHidingRule(String),
+}
+
+fn format_tag_path(path: &[Tag]) -> String {
+    path.iter()
+        .map(|tag| tag.0.as_str())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// State of a tag while looking for implication cycles
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VisitState {
+    InProgress,
+    Done,
 }
 
-#[derive(Deserialize, Serialize, Default)]
+#[derive(Deserialize, Serialize)]
 pub struct TagInfo {
     pub tags: HashMap<Tag, SingleTagInfo>,
     pub categories: HashMap<String, CategoryInfo>,
+    /// Rules used to hide hacks in lists. In order of display.
+    #[serde(default = "default_hiding_rules")]
+    #[serde(rename = "hiding-rules")]
+    pub hiding_rules: Vec<HidingRule>,
+    /// map each alias and previous name to its canonical tag. Built by [`TagInfo::rebuild_aliases`].
+    #[serde(skip)]
+    aliases: HashMap<Tag, Tag>,
+}
+
+impl Default for TagInfo {
+    fn default() -> Self {
+        Self {
+            tags: HashMap::new(),
+            categories: HashMap::new(),
+            hiding_rules: default_hiding_rules(),
+            aliases: HashMap::new(),
+        }
+    }
 }
 
 impl TagInfo {
     pub fn load_from_path(path: &Path) -> Result<Self, TagInfoLoadError> {
         let json_file =
             File::open(&path).map_err(|e| TagInfoLoadError::CantOpenFile(e, path.to_path_buf()))?;
-        let result = serde_json::from_reader(json_file)
+        let mut result: Self = serde_json::from_reader(json_file)
             .map_err(move |e| TagInfoLoadError::CantParseReadFile(e, path.to_path_buf()))?;
+        result.rebuild_aliases();
         Ok(result)
     }
 
+    /// Return the map of alias to canonical tag, and the conflicts found. In case of conflict, the first tag by name wins.
+    fn compute_aliases(&self) -> (HashMap<Tag, Tag>, Vec<TagInfoLoadError>) {
+        let mut aliases: HashMap<Tag, Tag> = HashMap::new();
+        let mut errors = Vec::new();
+        let mut tag_ids = self.tags.keys().collect::<Vec<_>>();
+        tag_ids.sort_unstable();
+        for tag_id in tag_ids {
+            let tag_info = &self.tags[tag_id];
+            for alias in tag_info.aliases.iter().chain(tag_info.renamed_from.iter()) {
+                if self.tags.contains_key(alias) {
+                    errors.push(TagInfoLoadError::ConflictingAlias(
+                        alias.clone(),
+                        tag_id.clone(),
+                        alias.clone(),
+                    ));
+                } else if let Some(other_tag_id) = aliases.get(alias) {
+                    if other_tag_id != tag_id {
+                        errors.push(TagInfoLoadError::ConflictingAlias(
+                            alias.clone(),
+                            tag_id.clone(),
+                            other_tag_id.clone(),
+                        ));
+                    }
+                } else {
+                    aliases.insert(alias.clone(), tag_id.clone());
+                }
+            }
+        }
+        (aliases, errors)
+    }
+
+    /// Should be called after [`TagInfo::tags`] is modified
+    pub fn rebuild_aliases(&mut self) {
+        self.aliases = self.compute_aliases().0;
+    }
+
+    /// Return the canonical tag if `tag` is an alias or a previous name of a tag
+    pub fn get_canonical_tag(&self, tag: &Tag) -> Option<&Tag> {
+        self.aliases.get(tag)
+    }
+
     pub fn to_json(&self) -> serde_json::Result<String> {
         serde_json::to_string_pretty(&self)
     }
@@ -104,6 +194,103 @@ impl TagInfo {
         tags
     }
 
+    /// Check the consistency of the tag informations. The problems found are only warnings, as tags can still be used.
+    pub fn check(&self) -> Vec<TagInfoLoadError> {
+        let mut errors = Vec::new();
+        let mut tag_ids = self.tags.keys().collect::<Vec<_>>();
+        tag_ids.sort_unstable();
+
+        for tag_id in &tag_ids {
+            let tag_info = &self.tags[*tag_id];
+            for implied_tag in &tag_info.implies {
+                if !self.tags.contains_key(implied_tag) {
+                    errors.push(TagInfoLoadError::UnknownImpliedTag(
+                        (*tag_id).clone(),
+                        implied_tag.clone(),
+                    ));
+                }
+            }
+            if let Some(category) = &tag_info.category {
+                if !self.categories.contains_key(category) {
+                    errors.push(TagInfoLoadError::UnknownCategory(
+                        (*tag_id).clone(),
+                        category.clone(),
+                    ));
+                }
+            }
+        }
+
+        errors.extend(self.compute_aliases().1);
+
+        let mut hiding_rule_ids = HashSet::new();
+        for hiding_rule in &self.hiding_rules {
+            if !hiding_rule_ids.insert(&hiding_rule.id) {
+                errors.push(TagInfoLoadError::DuplicateHidingRule(
+                    hiding_rule.id.clone(),
+                ));
+            }
+        }
+
+        let mut visit_states = HashMap::new();
+        for tag_id in tag_ids {
+            let mut path = Vec::new();
+            self.find_cycles(tag_id, &mut visit_states, &mut path, &mut errors);
+        }
+
+        errors
+    }
+
+    /// Depth-first search of the implication graph, reporting every cycle found once
+    fn find_cycles<'a>(
+        &'a self,
+        tag_id: &'a Tag,
+        visit_states: &mut HashMap<&'a Tag, VisitState>,
+        path: &mut Vec<&'a Tag>,
+        errors: &mut Vec<TagInfoLoadError>,
+    ) {
+        match visit_states.get(tag_id) {
+            Some(VisitState::Done) => return,
+            Some(VisitState::InProgress) => {
+                // unwrap: a tag in progress is always in the path
+                let cycle_start = path.iter().position(|t| *t == tag_id).unwrap();
+                let mut cycle = path[cycle_start..]
+                    .iter()
+                    .map(|t| (*t).clone())
+                    .collect::<Vec<_>>();
+                cycle.push(tag_id.clone());
+                errors.push(TagInfoLoadError::ImplicationCycle(cycle));
+                return;
+            }
+            None => (),
+        }
+        visit_states.insert(tag_id, VisitState::InProgress);
+        path.push(tag_id);
+        if let Some(tag_info) = self.tags.get(tag_id) {
+            for implied_tag in &tag_info.implies {
+                self.find_cycles(implied_tag, visit_states, path, errors);
+            }
+        }
+        path.pop();
+        visit_states.insert(tag_id, VisitState::Done);
+    }
+
+    /// Return a warning for each defined tag that isn't in `tags`
+    pub fn check_unused_tags(&self, tags: &Tags) -> Vec<TagInfoLoadError> {
+        let mut unused_tags = self
+            .tags
+            .keys()
+            .filter(|tag| tags.get_hack_for_tag(tag).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        unused_tags.sort_unstable();
+        unused_tags
+            .into_iter()
+            .map(TagInfoLoadError::UnusedTag)
+            .collect()
+    }
+
+    /// Return the tags that are recursively implied by `base_tags`, but not the `base_tags` themselves.
+    /// Cycles are tolerated.
     pub fn get_implied_tags(&self, base_tags: &HashSet<Tag>) -> HashSet<Tag> {
         let mut implied_tags = HashSet::new();
         let mut all_tags = base_tags.clone();
@@ -122,7 +309,7 @@ impl TagInfo {
             }
         }
 
-        all_tags
+        implied_tags
     }
 }
 
@@ -142,6 +329,37 @@ pub struct SingleTagInfo {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub label: Option<String>,
+    /// Other names accepted for this tag in URLs and queries. Hacks using them are accepted, with a warning.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<Tag>,
+    /// Previous names of this tag. Hacks using them are still accepted, with a warning.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "renamed-from")]
+    pub renamed_from: Vec<Tag>,
+    /// Tags that can't be present together with this one
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "exclusive-with")]
+    pub exclusive_with: Vec<Tag>,
+}
+
+/// Whether a tag is put on an hack or on one of its files
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagLevel {
+    Hack,
+    File,
+}
+
+impl fmt::Display for TagLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hack => write!(f, "hacks"),
+            Self::File => write!(f, "files"),
+        }
+    }
 }
 
 fn is_false(b: &bool) -> bool {
@@ -156,6 +374,113 @@ pub struct CategoryInfo {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_false")]
     pub required_for_file: bool,
+    /// Each hack should have at least one tag of this category (directly or by implication)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
+    pub required_for_hack: bool,
+    /// An hack or a file can't have more than one tag of this category
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
+    pub at_most_one: bool,
+    /// If set, tags of this category can only be directly put on this level
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only_on: Option<TagLevel>,
     #[serde(default)]
     pub priority: u32,
 }
+
+#[cfg(test)]
+mod test {
+    use super::{TagInfo, TagInfoLoadError};
+    use crate::Tag;
+    use std::collections::HashSet;
+
+    #[test]
+    pub fn test_taginfo_check() {
+        let taginfo: TagInfo = serde_json::from_str(
+            r#"{
+                "tags": {
+                    "a": { "implies": ["b"] },
+                    "b": { "implies": ["c", "missing"] },
+                    "c": { "implies": ["a"], "category": "unknown" },
+                    "d": { "implies": ["a"] }
+                },
+                "categories": {}
+            }"#,
+        )
+        .unwrap();
+        let errors = taginfo
+            .check()
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                TagInfoLoadError::UnknownImpliedTag(Tag("b".into()), Tag("missing".into()))
+                    .to_string(),
+                TagInfoLoadError::UnknownCategory(Tag("c".into()), "unknown".into()).to_string(),
+                "The implications of tags form a cycle: a -> b -> c -> a".to_string(),
+            ]
+        );
+
+        let implied = taginfo.get_implied_tags(&vec![Tag("d".into())].into_iter().collect());
+        let expected: HashSet<Tag> = ["a", "b", "c", "missing"]
+            .iter()
+            .map(|tag| Tag(tag.to_string()))
+            .collect();
+        assert_eq!(implied, expected);
+    }
+
+    #[test]
+    pub fn test_taginfo_aliases() {
+        let mut taginfo: TagInfo = serde_json::from_str(
+            r#"{
+                "tags": {
+                    "a": { "aliases": ["alias-a", "shared"], "renamed-from": ["old-a"] },
+                    "b": { "aliases": ["shared", "c"] },
+                    "c": {}
+                },
+                "categories": {}
+            }"#,
+        )
+        .unwrap();
+        // aliases are only resolved once rebuilt, as loading from a path does
+        assert_eq!(taginfo.get_canonical_tag(&Tag("alias-a".into())), None);
+        taginfo.rebuild_aliases();
+
+        let canonical = |tag: &str| taginfo.get_canonical_tag(&Tag(tag.into())).cloned();
+        assert_eq!(canonical("alias-a"), Some(Tag("a".into())));
+        assert_eq!(canonical("old-a"), Some(Tag("a".into())));
+        // the first tag by name wins a conflicting alias
+        assert_eq!(canonical("shared"), Some(Tag("a".into())));
+        // an alias that is also a tag doesn't shadow it
+        assert_eq!(canonical("c"), None);
+        assert_eq!(canonical("a"), None);
+        assert_eq!(canonical("unknown"), None);
+
+        let errors = taginfo
+            .check()
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                TagInfoLoadError::ConflictingAlias(
+                    Tag("shared".into()),
+                    Tag("b".into()),
+                    Tag("a".into())
+                )
+                .to_string(),
+                TagInfoLoadError::ConflictingAlias(
+                    Tag("c".into()),
+                    Tag("b".into()),
+                    Tag("c".into())
+                )
+                .to_string(),
+            ]
+        );
+    }
+}
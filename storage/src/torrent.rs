@@ -0,0 +1,284 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, metadata, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use thiserror::Error;
+
+/// Size of the leaf blocks of the v2 merkle tree, fixed by BEP 52
+const BLOCK_SIZE: u64 = 16 * 1024;
+const MIN_PIECE_LENGTH: u64 = BLOCK_SIZE;
+const MAX_PIECE_LENGTH: u64 = 16 * 1024 * 1024;
+/// The piece length is chosen to have approximately this number of pieces
+const TARGET_PIECE_COUNT: u64 = 1024;
+
+#[derive(Error, Debug)]
+pub enum TorrentError {
+    #[error("Can't read the metadata of the file at {1:?}")]
+    CantReadMetadata(#[source] io::Error, PathBuf),
+    #[error("Can't read the file at {1:?} to hash it")]
+    CantReadFile(#[source] io::Error, PathBuf),
+}
+
+/// The piece hashes of a single file, for both BitTorrent v1 and v2
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TorrentHashes {
+    /// size of the hashed file, used to check the cache is up to date
+    pub size: u64,
+    /// modification time (in seconds since the epoch) of the hashed file, used to check the cache is up to date
+    pub mtime: u64,
+    pub piece_length: u64,
+    /// SHA-1 of each pieces (v1)
+    pub pieces_v1: Vec<[u8; 20]>,
+    /// root of the SHA-256 merkle tree of the file (v2). None for an empty file.
+    pub pieces_root: Option<[u8; 32]>,
+    /// SHA-256 merkle hashes at the piece level (v2). Empty if the file fit in a single piece.
+    pub piece_layer: Vec<[u8; 32]>,
+}
+
+fn piece_length_for_size(size: u64) -> u64 {
+    (size / TARGET_PIECE_COUNT)
+        .next_power_of_two()
+        .clamp(MIN_PIECE_LENGTH, MAX_PIECE_LENGTH)
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Compute the root of a merkle tree, padding the layer with `padding` up to `width` (a power of two)
+fn merkle_root(mut layer: Vec<[u8; 32]>, width: usize, padding: [u8; 32]) -> [u8; 32] {
+    layer.resize(width.max(1), padding);
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+    }
+    layer[0]
+}
+
+fn get_mtime(path: &Path) -> Result<(u64, u64), TorrentError> {
+    let file_metadata =
+        metadata(path).map_err(|e| TorrentError::CantReadMetadata(e, path.to_path_buf()))?;
+    let mtime = file_metadata
+        .modified()
+        .map_err(|e| TorrentError::CantReadMetadata(e, path.to_path_buf()))?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    Ok((file_metadata.len(), mtime))
+}
+
+impl TorrentHashes {
+    pub fn compute(path: &Path) -> Result<Self, TorrentError> {
+        let (size, mtime) = get_mtime(path)?;
+        let piece_length = piece_length_for_size(size);
+        let blocks_per_piece = (piece_length / BLOCK_SIZE) as usize;
+
+        let mut file = File::open(path).map_err(|e| TorrentError::CantReadFile(e, path.into()))?;
+        let mut pieces_v1 = Vec::new();
+        let mut pieces_blocks = Vec::new();
+        let mut piece_blocks = Vec::with_capacity(blocks_per_piece);
+        let mut piece_hasher_v1 = Sha1::new();
+        let mut block = vec![0; BLOCK_SIZE as usize];
+        let mut remaining = size;
+        while remaining > 0 {
+            let block_len = remaining.min(BLOCK_SIZE) as usize;
+            file.read_exact(&mut block[..block_len])
+                .map_err(|e| TorrentError::CantReadFile(e, path.into()))?;
+            remaining -= block_len as u64;
+
+            piece_hasher_v1.update(&block[..block_len]);
+            piece_blocks.push(Sha256::digest(&block[..block_len]).into());
+
+            if piece_blocks.len() == blocks_per_piece || remaining == 0 {
+                pieces_v1.push(piece_hasher_v1.finalize_reset().into());
+                pieces_blocks.push(std::mem::take(&mut piece_blocks));
+            }
+        }
+
+        let (pieces_root, piece_layer) = match pieces_blocks.len() {
+            0 => (None, Vec::new()),
+            1 => {
+                // a file that fit in a single piece doesn't have piece layers
+                let blocks = pieces_blocks.pop().unwrap();
+                let width = blocks.len().next_power_of_two();
+                (Some(merkle_root(blocks, width, [0; 32])), Vec::new())
+            }
+            _ => {
+                let piece_layer = pieces_blocks
+                    .into_iter()
+                    .map(|blocks| merkle_root(blocks, blocks_per_piece, [0; 32]))
+                    .collect::<Vec<_>>();
+                let padding = merkle_root(Vec::new(), blocks_per_piece, [0; 32]);
+                let width = piece_layer.len().next_power_of_two();
+                (
+                    Some(merkle_root(piece_layer.clone(), width, padding)),
+                    piece_layer,
+                )
+            }
+        };
+
+        Ok(Self {
+            size,
+            mtime,
+            piece_length,
+            pieces_v1,
+            pieces_root,
+            piece_layer,
+        })
+    }
+
+    /// Generate an hybrid v1/v2 .torrent file for a single file torrent. `web_seed` is an URL the file can be downloaded from (BEP 19).
+    pub fn to_torrent_file(&self, name: &str, web_seed: &str) -> Vec<u8> {
+        let mut file_tree_entry = BTreeMap::new();
+        file_tree_entry.insert(b"length".to_vec(), Bencode::Int(self.size));
+        if let Some(pieces_root) = &self.pieces_root {
+            file_tree_entry.insert(b"pieces root".to_vec(), Bencode::bytes(pieces_root));
+        }
+        let mut file_tree_file = BTreeMap::new();
+        file_tree_file.insert(Vec::new(), Bencode::Dict(file_tree_entry));
+        let mut file_tree = BTreeMap::new();
+        file_tree.insert(name.as_bytes().to_vec(), Bencode::Dict(file_tree_file));
+
+        let mut info = BTreeMap::new();
+        info.insert(b"file tree".to_vec(), Bencode::Dict(file_tree));
+        info.insert(b"length".to_vec(), Bencode::Int(self.size));
+        info.insert(b"meta version".to_vec(), Bencode::Int(2));
+        info.insert(b"name".to_vec(), Bencode::bytes(name.as_bytes()));
+        info.insert(b"piece length".to_vec(), Bencode::Int(self.piece_length));
+        info.insert(b"pieces".to_vec(), Bencode::Bytes(self.pieces_v1.concat()));
+
+        let mut torrent = BTreeMap::new();
+        torrent.insert(b"info".to_vec(), Bencode::Dict(info));
+        if let (Some(pieces_root), false) = (&self.pieces_root, self.piece_layer.is_empty()) {
+            let mut piece_layers = BTreeMap::new();
+            piece_layers.insert(
+                pieces_root.to_vec(),
+                Bencode::Bytes(self.piece_layer.concat()),
+            );
+            torrent.insert(b"piece layers".to_vec(), Bencode::Dict(piece_layers));
+        }
+        torrent.insert(
+            b"url-list".to_vec(),
+            Bencode::List(vec![Bencode::bytes(web_seed.as_bytes())]),
+        );
+
+        let mut result = Vec::new();
+        Bencode::Dict(torrent).encode(&mut result);
+        result
+    }
+}
+
+/// A bencoded value. Dictionary are sorted by their raw key, as required.
+enum Bencode {
+    Int(u64),
+    Bytes(Vec<u8>),
+    List(Vec<Bencode>),
+    Dict(BTreeMap<Vec<u8>, Bencode>),
+}
+
+impl Bencode {
+    fn bytes(value: &[u8]) -> Self {
+        Self::Bytes(value.to_vec())
+    }
+
+    fn encode_bytes(bytes: &[u8], output: &mut Vec<u8>) {
+        output.extend(bytes.len().to_string().as_bytes());
+        output.push(b':');
+        output.extend(bytes);
+    }
+
+    fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Self::Int(value) => {
+                output.push(b'i');
+                output.extend(value.to_string().as_bytes());
+                output.push(b'e');
+            }
+            Self::Bytes(bytes) => Self::encode_bytes(bytes, output),
+            Self::List(list) => {
+                output.push(b'l');
+                for value in list {
+                    value.encode(output);
+                }
+                output.push(b'e');
+            }
+            Self::Dict(dict) => {
+                output.push(b'd');
+                for (key, value) in dict {
+                    Self::encode_bytes(key, output);
+                    value.encode(output);
+                }
+                output.push(b'e');
+            }
+        }
+    }
+}
+
+/// Store the computed [`TorrentHashes`] on disk, as hashing big files is slow
+pub struct TorrentCache {
+    folder: PathBuf,
+}
+
+impl TorrentCache {
+    pub fn new(folder: PathBuf) -> Self {
+        Self { folder }
+    }
+
+    fn cache_path(&self, hack_slug: &str, filename: &str) -> PathBuf {
+        self.folder
+            .join(hack_slug)
+            .join(format!("{}.json", filename))
+    }
+
+    fn read_cached(&self, cache_path: &Path, size: u64, mtime: u64) -> Option<TorrentHashes> {
+        let cache_file = File::open(cache_path).ok()?;
+        let cached: TorrentHashes = serde_json::from_reader(cache_file).ok()?;
+        if cached.size == size && cached.mtime == mtime {
+            Some(cached)
+        } else {
+            None
+        }
+    }
+
+    fn write_cached(&self, cache_path: &Path, hashes: &TorrentHashes) -> io::Result<()> {
+        if let Some(parent) = cache_path.parent() {
+            create_dir_all(parent)?;
+        }
+        let cache_file = File::create(cache_path)?;
+        serde_json::to_writer(cache_file, hashes)?;
+        Ok(())
+    }
+
+    /// Return the hashes of the file of the given hack at `path`, computing and caching them if they are absent or outdated.
+    pub fn get_or_compute(
+        &self,
+        hack_slug: &str,
+        filename: &str,
+        path: &Path,
+    ) -> Result<TorrentHashes, TorrentError> {
+        let (size, mtime) = get_mtime(path)?;
+        let cache_path = self.cache_path(hack_slug, filename);
+        if let Some(cached) = self.read_cached(&cache_path, size, mtime) {
+            return Ok(cached);
+        }
+        let hashes = TorrentHashes::compute(path)?;
+        if let Err(e) = self.write_cached(&cache_path, &hashes) {
+            warn!(
+                "Can't write the torrent hashes cache at {:?}: {}",
+                cache_path, e
+            );
+        }
+        Ok(hashes)
+    }
+}